@@ -13,8 +13,12 @@ mod telemetry;
 mod telemetry_layer;
 mod trace;
 
-pub use crate::telemetry::{BlackholeTelemetry, Telemetry};
+pub use crate::telemetry::{BlackholeTelemetry, CountingTelemetry, TeeTelemetry, Telemetry};
 pub use crate::telemetry_layer::TelemetryLayer;
 pub use crate::trace::{
-    current_dist_trace_ctx, register_dist_tracing_root, Event, Span, TraceCtxError,
+    current_baggage, current_dist_trace_ctx, current_dist_trace_ctx_with_ancestry,
+    current_dist_trace_root, get_baggage, register_dist_tracing_root,
+    register_dist_tracing_root_with_visitor, report_trace_start, set_baggage, with_current_trace,
+    with_current_visitor, Event, Link, Span, TraceCtxError, TraceFlag, MAX_BAGGAGE_BYTES,
+    MAX_BAGGAGE_ENTRIES,
 };
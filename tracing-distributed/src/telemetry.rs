@@ -1,5 +1,9 @@
 use crate::trace::{Event, Span};
+use std::fmt;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
 
 /// Represents the ability to publish events and spans to some arbitrary backend.
 pub trait Telemetry {
@@ -18,10 +22,62 @@ pub trait Telemetry {
 
     /// Report an `Event` to this Telemetry instance's backend.
     fn report_event(&self, event: Event<Self::Visitor, Self::SpanId, Self::TraceId>);
+
+    /// The current time, used to timestamp spans and events.
+    ///
+    /// Defaults to the system clock. Overriding this allows tests to produce reproducible
+    /// timestamps and durations.
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    /// Whether events with the given metadata are recorded and reported.
+    ///
+    /// Checked before an event's fields are visited, so that events this returns `false` for
+    /// cost no more than the check. Unlike filtering in `Layer::enabled`, this only applies to
+    /// this `Telemetry`, not to other layers of the subscriber. Defaults to `true`.
+    fn event_enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    /// Whether the layer tracks how long each span is entered for, reporting it as the span's
+    /// `busy` time.
+    ///
+    /// Checked when a span is created. Tracking reads the clock via [`now`](Self::now) each time
+    /// a span is entered or exited, and locks the span's extensions to update its total, so it
+    /// adds noticeable overhead to spans entered often, e.g. those instrumenting futures, which
+    /// are entered on every poll. Defaults to `false`.
+    fn track_busy_time(&self) -> bool {
+        false
+    }
+
+    /// Report the start of a trace, as requested via `report_trace_start`, e.g. to make the trace
+    /// visible before its local root span completes.
+    ///
+    /// `event` is parented to the span `report_trace_start` was called in, usually the local
+    /// root of the trace, and has the metadata of that span but no fields of its own. `root`
+    /// holds the fields that span recorded so far. Defaults to reporting nothing.
+    fn report_trace_start(
+        &self,
+        _event: Event<Self::Visitor, Self::SpanId, Self::TraceId>,
+        _root: &Self::Visitor,
+    ) {
+    }
+
+    /// A key derived from the fields of the local root span of a trace, e.g. the value of a field
+    /// to sample the trace on, reported in `Span::root_key` and `Event::root_key` for every span
+    /// and event of the trace.
+    ///
+    /// Computed once per local root, from the fields it recorded by the time it is registered via
+    /// `register_dist_tracing_root`, so that spans and events reported before the root completes
+    /// carry it too. Defaults to `None`.
+    fn root_key(&self, _root: &Self::Visitor) -> Option<String> {
+        None
+    }
 }
 
 /// Visitor that records no information when visiting tracing fields.
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct BlackholeVisitor;
 
 impl tracing::field::Visit for BlackholeVisitor {
@@ -56,6 +112,175 @@ where
     fn report_event(&self, _: Event<Self::Visitor, Self::SpanId, Self::TraceId>) {}
 }
 
+/// Telemetry implementation that counts the spans and events it is asked to report, without
+/// publishing them to any backend. For measuring instrumentation overhead, e.g. in load tests.
+///
+/// Clones share the same counters, so a clone kept before the telemetry is moved into a
+/// `TelemetryLayer` can be used to read them.
+pub struct CountingTelemetry<S, T> {
+    spans: Arc<AtomicUsize>,
+    events: Arc<AtomicUsize>,
+    _ids: PhantomData<fn() -> (S, T)>,
+}
+
+impl<S, T> CountingTelemetry<S, T> {
+    /// Returns the number of spans reported so far.
+    pub fn span_count(&self) -> usize {
+        self.spans.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of events reported so far.
+    pub fn event_count(&self) -> usize {
+        self.events.load(Ordering::Relaxed)
+    }
+}
+
+impl<S, T> Default for CountingTelemetry<S, T> {
+    fn default() -> Self {
+        CountingTelemetry {
+            spans: Arc::new(AtomicUsize::new(0)),
+            events: Arc::new(AtomicUsize::new(0)),
+            _ids: PhantomData,
+        }
+    }
+}
+
+impl<S, T> Clone for CountingTelemetry<S, T> {
+    fn clone(&self) -> Self {
+        CountingTelemetry {
+            spans: self.spans.clone(),
+            events: self.events.clone(),
+            _ids: PhantomData,
+        }
+    }
+}
+
+impl<S, T> fmt::Debug for CountingTelemetry<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountingTelemetry")
+            .field("spans", &self.span_count())
+            .field("events", &self.event_count())
+            .finish()
+    }
+}
+
+impl<SpanId, TraceId> Telemetry for CountingTelemetry<SpanId, TraceId>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+{
+    type Visitor = BlackholeVisitor;
+    type TraceId = TraceId;
+    type SpanId = SpanId;
+
+    fn mk_visitor(&self) -> Self::Visitor {
+        Default::default()
+    }
+
+    fn report_span(&self, _: Span<Self::Visitor, Self::SpanId, Self::TraceId>) {
+        self.spans.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn report_event(&self, _: Event<Self::Visitor, Self::SpanId, Self::TraceId>) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Telemetry implementation that reports every span and event to two `Telemetry`
+/// capabilities, e.g. to write the same trace data to two backends, each sampling it
+/// independently.
+///
+/// Fields are recorded once, by the visitor of the first capability, so options applying to
+/// the visitor, such as redaction, are taken from the first; the second's visitor is never
+/// used. Each span and event is then cloned, fields included, for the second, and each
+/// capability converts and reports its copy on its own, so reporting costs about as much as
+/// with either capability in a `TelemetryLayer` of its own. Events are only reported to a
+/// capability whose `event_enabled` returns `true` for them. `now` is taken from the first, as
+/// is `root_key` unless the first has none.
+#[derive(Debug, Clone)]
+pub struct TeeTelemetry<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> TeeTelemetry<A, B> {
+    /// Returns a new `TeeTelemetry` reporting to `first` and `second`.
+    pub fn new(first: A, second: B) -> Self {
+        TeeTelemetry { first, second }
+    }
+
+    /// Returns the first `Telemetry` capability.
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    /// Returns the second `Telemetry` capability.
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+}
+
+impl<A, B> Telemetry for TeeTelemetry<A, B>
+where
+    A: Telemetry,
+    A::Visitor: Clone,
+    B: Telemetry<Visitor = A::Visitor, SpanId = A::SpanId, TraceId = A::TraceId>,
+{
+    type Visitor = A::Visitor;
+    type TraceId = A::TraceId;
+    type SpanId = A::SpanId;
+
+    fn mk_visitor(&self) -> Self::Visitor {
+        self.first.mk_visitor()
+    }
+
+    fn report_span(&self, span: Span<Self::Visitor, Self::SpanId, Self::TraceId>) {
+        self.second.report_span(span.clone());
+        self.first.report_span(span);
+    }
+
+    fn report_event(&self, event: Event<Self::Visitor, Self::SpanId, Self::TraceId>) {
+        let first = self.first.event_enabled(event.meta);
+        let second = self.second.event_enabled(event.meta);
+        match (first, second) {
+            (true, true) => {
+                self.second.report_event(event.clone());
+                self.first.report_event(event);
+            }
+            (true, false) => self.first.report_event(event),
+            (false, true) => self.second.report_event(event),
+            (false, false) => {}
+        }
+    }
+
+    fn report_trace_start(
+        &self,
+        event: Event<Self::Visitor, Self::SpanId, Self::TraceId>,
+        root: &Self::Visitor,
+    ) {
+        self.second.report_trace_start(event.clone(), root);
+        self.first.report_trace_start(event, root);
+    }
+
+    fn now(&self) -> SystemTime {
+        self.first.now()
+    }
+
+    fn event_enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        self.first.event_enabled(metadata) || self.second.event_enabled(metadata)
+    }
+
+    fn track_busy_time(&self) -> bool {
+        self.first.track_busy_time() || self.second.track_busy_time()
+    }
+
+    fn root_key(&self, root: &Self::Visitor) -> Option<String> {
+        self.first
+            .root_key(root)
+            .or_else(|| self.second.root_key(root))
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
@@ -101,5 +326,10 @@ pub(crate) mod test {
             let mut events = self.events.lock().unwrap();
             events.push(event);
         }
+
+        // the visitor holds no fields, so the key is the same for every trace
+        fn root_key(&self, _root: &BlackholeVisitor) -> Option<String> {
+            Some("root".to_string())
+        }
     }
 }
@@ -2,10 +2,12 @@ use crate::telemetry::Telemetry;
 use crate::trace;
 use std::any::TypeId;
 use std::collections::HashMap;
-use std::time::SystemTime;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tracing::span::{Attributes, Id, Record};
 use tracing::{Event, Subscriber};
-use tracing_subscriber::{layer::Context, registry, Layer};
+use tracing_subscriber::{layer::Context, registry, registry::LookupSpan, Layer};
 
 #[cfg(feature = "use_parking_lot")]
 use parking_lot::RwLock;
@@ -21,10 +23,16 @@ pub struct TelemetryLayer<Telemetry, SpanId, TraceId> {
     pub(crate) trace_ctx_registry: TraceCtxRegistry<SpanId, TraceId>,
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct TraceCtx<SpanId, TraceId> {
     pub(crate) parent_span: Option<SpanId>,
     pub(crate) trace_id: TraceId,
+    // name of the span registered as the local root of the trace
+    pub(crate) root_name: &'static str,
+    // shared by every span and event below the local root
+    pub(crate) flag: trace::TraceFlag,
+    // `Telemetry::root_key` of the local root, as of its registration
+    pub(crate) root_key: Option<Arc<str>>,
 }
 
 // resolvable via downcast_ref, to avoid propagating 'T' parameter of TelemetryLayer where not req'd
@@ -42,15 +50,29 @@ where
         (self.promote_span_id)(id)
     }
 
+    pub(crate) fn is_local_root(&self, id: &Id) -> bool {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let trace_ctx_registry = self.registry.read().unwrap();
+        #[cfg(feature = "use_parking_lot")]
+        let trace_ctx_registry = self.registry.read();
+
+        trace_ctx_registry.contains_key(id)
+    }
+
     pub(crate) fn record_trace_ctx(
         &self,
         trace_id: TraceId,
         remote_parent_span: Option<SpanId>,
         id: Id,
+        root_name: &'static str,
+        root_key: Option<Arc<str>>,
     ) {
         let trace_ctx = TraceCtx {
             trace_id,
             parent_span: remote_parent_span,
+            root_name,
+            flag: trace::TraceFlag::default(),
+            root_key,
         };
 
         #[cfg(not(feature = "use_parking_lot"))]
@@ -94,6 +116,9 @@ where
                                 TraceCtx {
                                     trace_id: local_trace_root.trace_id.clone(),
                                     parent_span: None,
+                                    root_name: local_trace_root.root_name,
+                                    flag: local_trace_root.flag.clone(),
+                                    root_key: local_trace_root.root_key.clone(),
                                 }
                             };
 
@@ -103,6 +128,9 @@ where
                                     TraceCtx {
                                         trace_id: local_trace_root.trace_id.clone(),
                                         parent_span: None,
+                                        root_name: local_trace_root.root_name,
+                                        flag: local_trace_root.flag.clone(),
+                                        root_key: local_trace_root.root_key.clone(),
                                     },
                                 ));
                             }
@@ -117,6 +145,9 @@ where
                         TraceCtx {
                             trace_id: already_evaluated.trace_id.clone(),
                             parent_span: None,
+                            root_name: already_evaluated.root_name,
+                            flag: already_evaluated.flag.clone(),
+                            root_key: already_evaluated.root_key.clone(),
                         }
                     };
 
@@ -126,6 +157,9 @@ where
                             TraceCtx {
                                 trace_id: already_evaluated.trace_id.clone(),
                                 parent_span: None,
+                                root_name: already_evaluated.root_name,
+                                flag: already_evaluated.flag.clone(),
+                                root_key: already_evaluated.root_key.clone(),
                             },
                         ));
                     }
@@ -169,6 +203,84 @@ where
             trace_ctx_registry,
         }
     }
+
+    /// The `Telemetry` capability this layer publishes to, e.g. to flush it before exit.
+    pub fn telemetry(&self) -> &T {
+        &self.telemetry
+    }
+}
+
+impl<TraceId, SpanId, V, T> TelemetryLayer<T, SpanId, TraceId>
+where
+    TraceId: 'static + Clone + Eq + Send + Sync,
+    SpanId: 'static + Clone + Eq + Send + Sync,
+    V: 'static + tracing::field::Visit + Send + Sync,
+    T: 'static + Telemetry<Visitor = V, TraceId = TraceId, SpanId = SpanId>,
+{
+    // reports the start of the trace the span with the given id is part of, see `report_trace_start`
+    fn report_trace_start(
+        dispatch: &tracing::Dispatch,
+        id: &Id,
+    ) -> Result<(), trace::TraceCtxError> {
+        let layer = dispatch
+            .downcast_ref::<Self>()
+            .ok_or(trace::TraceCtxError::TelemetryLayerNotRegistered)?;
+        let registry = dispatch
+            .downcast_ref::<registry::Registry>()
+            .ok_or(trace::TraceCtxError::RegistrySubscriberNotRegistered)?;
+
+        let iter = itertools::unfold(Some(id.clone()), |st| match st {
+            Some(target_id) => {
+                // failure here indicates a broken parent id span link, panic is valid
+                let res = registry
+                    .span(target_id)
+                    .expect("span data not found during eval_ctx for report_trace_start");
+                *st = res.parent().map(|x| x.id());
+                Some(res)
+            }
+            None => None,
+        });
+        let trace_ctx = layer
+            .trace_ctx_registry
+            .eval_ctx(iter)
+            .ok_or(trace::TraceCtxError::NoParentNodeHasTraceCtx)?;
+
+        let span = registry
+            .span(id)
+            .expect("span data not found during report_trace_start");
+        let extensions = span.extensions();
+        let root: &V = extensions.get().expect("should be present on all spans");
+        let baggage = extensions
+            .get::<Baggage>()
+            .map(|Baggage(entries)| entries.clone())
+            .unwrap_or_default();
+        let event = trace::Event {
+            trace_id: trace_ctx.trace_id,
+            root_name: trace_ctx.root_name,
+            trace_flag: trace_ctx.flag,
+            root_key: trace_ctx.root_key,
+            parent_id: Some(layer.trace_ctx_registry.promote_span_id(id.clone())),
+            initialized_at: layer.telemetry.now(),
+            meta: span.metadata(),
+            service_name: layer.service_name,
+            baggage,
+            values: layer.telemetry.mk_visitor(),
+        };
+
+        layer.telemetry.report_trace_start(event, root);
+        Ok(())
+    }
+
+    // computes `Telemetry::root_key` from the visitor of the span with the given id, see
+    // `register_dist_tracing_root`
+    fn root_key(dispatch: &tracing::Dispatch, id: &Id) -> Option<Arc<str>> {
+        let layer = dispatch.downcast_ref::<Self>()?;
+        let registry = dispatch.downcast_ref::<registry::Registry>()?;
+        let span = registry.span(id)?;
+        let extensions = span.extensions();
+        let root: &V = extensions.get()?;
+        layer.telemetry.root_key(root).map(Arc::from)
+    }
 }
 
 impl<S, TraceId, SpanId, V, T> Layer<S> for TelemetryLayer<T, SpanId, TraceId>
@@ -181,8 +293,19 @@ where
 {
     fn new_span(&self, attrs: &Attributes, id: &Id, ctx: Context<S>) {
         let span = ctx.span(id).expect("span data not found during new_span");
+        // inherited as of the span's creation, so that it is only looked up on the span itself
+        let baggage = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<Baggage>().cloned());
+
         let mut extensions_mut = span.extensions_mut();
-        extensions_mut.insert(SpanInitAt::new());
+        extensions_mut.insert(SpanInitAt(self.telemetry.now()));
+        if self.telemetry.track_busy_time() {
+            extensions_mut.insert(BusyTime::default());
+        }
+        if let Some(baggage) = baggage {
+            extensions_mut.insert(baggage);
+        }
 
         let mut visitor: V = self.telemetry.mk_visitor();
         attrs.record(&mut visitor);
@@ -198,7 +321,42 @@ where
         values.record(visitor);
     }
 
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        // spans are entered on every poll of the futures they instrument, so skip the lookup
+        if !self.telemetry.track_busy_time() {
+            return;
+        }
+
+        let span = ctx.span(id).expect("span data not found during on_enter");
+        let mut extensions_mut = span.extensions_mut();
+        if let Some(busy_time) = extensions_mut.get_mut::<BusyTime>() {
+            if busy_time.entered == 0 {
+                busy_time.entered_at = Some(self.telemetry.now());
+            }
+            busy_time.entered += 1;
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if !self.telemetry.track_busy_time() {
+            return;
+        }
+
+        let span = ctx.span(id).expect("span data not found during on_exit");
+        let mut extensions_mut = span.extensions_mut();
+        if let Some(busy_time) = extensions_mut.get_mut::<BusyTime>() {
+            busy_time.entered = busy_time.entered.saturating_sub(1);
+            if busy_time.entered == 0 {
+                busy_time.stop(self.telemetry.now());
+            }
+        }
+    }
+
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if !self.telemetry.event_enabled(event.metadata()) {
+            return;
+        }
+
         let parent_id = if let Some(parent_id) = event.parent() {
             // explicit parent
             Some(parent_id.clone())
@@ -213,7 +371,7 @@ where
         match parent_id {
             None => {} // not part of a trace, don't bother recording via honeycomb
             Some(parent_id) => {
-                let initialized_at = SystemTime::now();
+                let initialized_at = self.telemetry.now();
 
                 let mut visitor = self.telemetry.mk_visitor();
                 event.record(&mut visitor);
@@ -232,12 +390,30 @@ where
 
                 // only report event if it's part of a trace
                 if let Some(parent_trace_ctx) = self.trace_ctx_registry.eval_ctx(iter) {
+                    let parent = ctx.span(&parent_id);
+                    if *event.metadata().level() == tracing::Level::ERROR {
+                        if let Some(parent) = &parent {
+                            parent.extensions_mut().replace(Errored);
+                        }
+                    }
+                    let baggage = parent
+                        .and_then(|parent| {
+                            parent
+                                .extensions()
+                                .get::<Baggage>()
+                                .map(|Baggage(entries)| entries.clone())
+                        })
+                        .unwrap_or_default();
                     let event = trace::Event {
                         trace_id: parent_trace_ctx.trace_id,
+                        root_name: parent_trace_ctx.root_name,
+                        trace_flag: parent_trace_ctx.flag,
+                        root_key: parent_trace_ctx.root_key,
                         parent_id: Some(self.trace_ctx_registry.promote_span_id(parent_id)),
                         initialized_at,
                         meta: event.metadata(),
-                        service_name: &self.service_name,
+                        service_name: self.service_name,
+                        baggage,
                         values: visitor,
                     };
 
@@ -271,8 +447,23 @@ where
             let SpanInitAt(initialized_at) = extensions_mut
                 .remove()
                 .expect("should be present on all spans");
+            let follows_from = extensions_mut
+                .remove::<FollowsFrom<SpanId, TraceId>>()
+                .map(|FollowsFrom(links)| links)
+                .unwrap_or_default();
+            let baggage = extensions_mut
+                .remove::<Baggage>()
+                .map(|Baggage(entries)| entries)
+                .unwrap_or_default();
+
+            let errored = extensions_mut.remove::<Errored>().is_some();
 
-            let completed_at = SystemTime::now();
+            let completed_at = self.telemetry.now();
+            let busy = extensions_mut.remove::<BusyTime>().map(|mut busy_time| {
+                // in case the span is closed while still entered
+                busy_time.stop(completed_at);
+                busy_time.busy
+            });
 
             let parent_id = match trace_ctx.parent_span {
                 None => span
@@ -282,11 +473,19 @@ where
             };
 
             let span = trace::Span {
+                local_root: self.trace_ctx_registry.is_local_root(&id),
+                follows_from,
+                baggage,
+                busy,
+                errored,
                 id: self.trace_ctx_registry.promote_span_id(id),
                 meta: span.metadata(),
                 parent_id,
                 initialized_at,
                 trace_id: trace_ctx.trace_id,
+                root_name: trace_ctx.root_name,
+                trace_flag: trace_ctx.flag,
+                root_key: trace_ctx.root_key,
                 completed_at,
                 service_name: self.service_name,
                 values: visitor,
@@ -296,41 +495,120 @@ where
         };
     }
 
+    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: Context<'_, S>) {
+        let iter = itertools::unfold(Some(follows.clone()), |st| match st {
+            Some(target_id) => {
+                let res = ctx
+                    .span(target_id)
+                    .expect("span data not found during eval_ctx");
+                *st = res.parent().map(|x| x.id());
+                Some(res)
+            }
+            None => None,
+        });
+
+        // spans outside of any trace are never reported, so there is nothing to link to
+        if let Some(trace_ctx) = self.trace_ctx_registry.eval_ctx(iter) {
+            let link = trace::Link {
+                span_id: self.trace_ctx_registry.promote_span_id(follows.clone()),
+                trace_id: trace_ctx.trace_id,
+            };
+
+            let span = ctx
+                .span(id)
+                .expect("span data not found during on_follows_from");
+            let mut extensions_mut = span.extensions_mut();
+            match extensions_mut.get_mut::<FollowsFrom<SpanId, TraceId>>() {
+                Some(FollowsFrom(links)) => links.push(link),
+                None => extensions_mut.insert(FollowsFrom(vec![link])),
+            }
+        }
+    }
+
     // FIXME: do I need to do something here? I think no (better to require explicit re-marking as root after copy).
     // called when span copied, needed iff span has trace id/etc already? nah,
     // fn on_id_change(&self, _old: &Id, _new: &Id, _ctx: Context<'_, S>) {}
 
     unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
         // This `downcast_raw` impl allows downcasting this layer to any of
-        // its components (currently just trace ctx registry)
+        // its components (the trace ctx registry)
         // as well as to the layer's type itself (technique borrowed from formatting subscriber)
         match () {
             _ if id == TypeId::of::<Self>() => Some(self as *const Self as *const ()),
             _ if id == TypeId::of::<TraceCtxRegistry<SpanId, TraceId>>() => Some(
                 &self.trace_ctx_registry as *const TraceCtxRegistry<SpanId, TraceId> as *const (),
             ),
+            _ if id == TypeId::of::<ReportTraceStart<SpanId, TraceId>>() => {
+                let report_trace_start: &'static ReportTraceStart<SpanId, TraceId> =
+                    &ReportTraceStart {
+                        report: Self::report_trace_start,
+                        ids: PhantomData,
+                    };
+                Some(report_trace_start as *const ReportTraceStart<SpanId, TraceId> as *const ())
+            }
+            _ if id == TypeId::of::<RootKey<SpanId, TraceId>>() => {
+                let root_key: &'static RootKey<SpanId, TraceId> = &RootKey {
+                    key: Self::root_key,
+                    ids: PhantomData,
+                };
+                Some(root_key as *const RootKey<SpanId, TraceId> as *const ())
+            }
             _ => None,
         }
     }
 }
 
+// resolvable via downcast_ref, to report the start of a trace without knowing the 'T' parameter
+// of TelemetryLayer
+pub(crate) struct ReportTraceStart<SpanId, TraceId> {
+    pub(crate) report: fn(&tracing::Dispatch, &Id) -> Result<(), trace::TraceCtxError>,
+    ids: PhantomData<fn() -> (SpanId, TraceId)>,
+}
+
+// resolvable via downcast_ref, to compute the root key of a trace on registering its root
+// without knowing the 'T' parameter of TelemetryLayer
+pub(crate) struct RootKey<SpanId, TraceId> {
+    pub(crate) key: fn(&tracing::Dispatch, &Id) -> Option<Arc<str>>,
+    ids: PhantomData<fn() -> (SpanId, TraceId)>,
+}
+
 // TODO: delete?
-struct LazyTraceCtx<SpanId, TraceId>(TraceCtx<SpanId, TraceId>);
+pub(crate) struct LazyTraceCtx<SpanId, TraceId>(pub(crate) TraceCtx<SpanId, TraceId>);
 
 struct SpanInitAt(SystemTime);
 
-impl SpanInitAt {
-    fn new() -> Self {
-        let initialized_at = SystemTime::now();
+// time a span has been entered for, if tracked
+#[derive(Default)]
+struct BusyTime {
+    busy: Duration,
+    // number of times the span is currently entered, e.g. on several threads at once
+    entered: usize,
+    // when the span was last entered while not already entered
+    entered_at: Option<SystemTime>,
+}
 
-        Self(initialized_at)
+impl BusyTime {
+    fn stop(&mut self, now: SystemTime) {
+        if let Some(entered_at) = self.entered_at.take() {
+            self.busy += now.duration_since(entered_at).unwrap_or_default();
+        }
     }
 }
 
+struct FollowsFrom<SpanId, TraceId>(Vec<trace::Link<SpanId, TraceId>>);
+
+// marks a span in which an `ERROR` level event occurred
+struct Errored;
+
+// baggage in effect for a span, shared with the spans it was inherited by
+#[derive(Clone)]
+pub(crate) struct Baggage(pub(crate) Arc<Vec<(String, String)>>);
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::telemetry::test::{SpanId, TestTelemetry, TraceId};
+    use crate::TraceCtxError;
     use std::sync::Arc;
     use std::sync::Mutex;
     use std::time::Duration;
@@ -422,6 +700,306 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_counting_telemetry() {
+        let telemetry = crate::CountingTelemetry::<SpanId, TraceId>::default();
+        let layer = TelemetryLayer::new("test_svc_name", telemetry.clone(), |x| x);
+        let subscriber = layer.with_subscriber(registry::Registry::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            trace::register_dist_tracing_root::<SpanId, TraceId>(explicit_trace_id(), None)
+                .unwrap();
+            tracing::info!("first");
+            tracing::info_span!("child").in_scope(|| tracing::info!("second"));
+        });
+
+        assert_eq!(telemetry.span_count(), 2);
+        assert_eq!(telemetry.event_count(), 2);
+    }
+
+    #[test]
+    fn test_current_dist_trace_ctx_with_ancestry() {
+        let layer = TelemetryLayer::new(
+            "test_svc_name",
+            crate::BlackholeTelemetry::<SpanId, TraceId>::default(),
+            |x| x,
+        );
+        let subscriber = layer.with_subscriber(registry::Registry::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("outer");
+            let _outer_guard = outer.enter();
+            let root = tracing::info_span!("root");
+            let _root_guard = root.enter();
+            trace::register_dist_tracing_root::<SpanId, TraceId>(explicit_trace_id(), None)
+                .unwrap();
+            let child = tracing::info_span!("child");
+            let _child_guard = child.enter();
+
+            let (trace_id, ancestry) =
+                trace::current_dist_trace_ctx_with_ancestry::<SpanId, TraceId>().unwrap();
+            assert_eq!(trace_id, explicit_trace_id());
+            assert_eq!(ancestry, vec![root.id().unwrap(), child.id().unwrap()]);
+        });
+    }
+
+    #[test]
+    fn test_visitor_type_mismatch() {
+        let layer = TelemetryLayer::new(
+            "test_svc_name",
+            crate::BlackholeTelemetry::<SpanId, TraceId>::default(),
+            |x| x,
+        );
+        let subscriber = layer.with_subscriber(registry::Registry::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::info_span!("root");
+            let _root_guard = root.enter();
+
+            let res = trace::register_dist_tracing_root_with_visitor::<SpanId, TraceId, String, _>(
+                explicit_trace_id(),
+                None,
+                |_| (),
+            );
+            assert_eq!(res, Err(trace::TraceCtxError::VisitorTypeMismatch));
+            // the span was not registered
+            assert_eq!(
+                trace::current_dist_trace_ctx::<SpanId, TraceId>(),
+                Err(trace::TraceCtxError::NoParentNodeHasTraceCtx)
+            );
+
+            trace::register_dist_tracing_root_with_visitor::<
+                SpanId,
+                TraceId,
+                crate::telemetry::BlackholeVisitor,
+                _,
+            >(explicit_trace_id(), None, |_| ())
+            .unwrap();
+            let res = trace::with_current_visitor::<SpanId, TraceId, String, _>(|_| ());
+            assert_eq!(res, Err(trace::TraceCtxError::VisitorTypeMismatch));
+        });
+    }
+
+    #[test]
+    fn test_with_current_trace() {
+        let layer = TelemetryLayer::new(
+            "test_svc_name",
+            crate::BlackholeTelemetry::<SpanId, TraceId>::default(),
+            |x| x,
+        );
+        let subscriber = layer.with_subscriber(registry::Registry::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::info_span!("root");
+            let _root_guard = root.enter();
+            trace::register_dist_tracing_root::<SpanId, TraceId>(explicit_trace_id(), None)
+                .unwrap();
+            trace::set_baggage::<SpanId, TraceId>("tenant", "a").unwrap();
+
+            let child = tracing::info_span!("child");
+            let _child_guard = child.enter();
+            for _ in 0..2 {
+                let res = trace::with_current_trace::<
+                    SpanId,
+                    TraceId,
+                    crate::telemetry::BlackholeVisitor,
+                    _,
+                >(|trace_id, root_name, root_key, _, baggage| {
+                    (*trace_id, root_name, root_key.is_none(), baggage.to_vec())
+                });
+                assert_eq!(
+                    res,
+                    Ok((
+                        explicit_trace_id(),
+                        "root",
+                        true,
+                        vec![("tenant".to_string(), "a".to_string())]
+                    ))
+                );
+            }
+
+            let res = trace::with_current_trace::<SpanId, TraceId, String, _>(|_, _, _, _, _| ());
+            assert_eq!(res, Err(trace::TraceCtxError::VisitorTypeMismatch));
+        });
+    }
+
+    #[test]
+    fn test_follows_from() {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let cap = TestTelemetry::new(spans.clone(), Arc::new(Mutex::new(Vec::new())));
+        let layer = TelemetryLayer::new("test_svc_name", cap, |x| x);
+        let subscriber = layer.with_subscriber(registry::Registry::default());
+
+        let producer_id = tracing::subscriber::with_default(subscriber, || {
+            let untraced = tracing::info_span!("untraced");
+            let producer = tracing::info_span!("producer");
+            producer.in_scope(|| {
+                trace::register_dist_tracing_root::<SpanId, TraceId>(explicit_trace_id(), None)
+                    .unwrap();
+            });
+
+            let consumer = tracing::info_span!("consumer");
+            consumer.in_scope(|| {
+                trace::register_dist_tracing_root::<SpanId, TraceId>(explicit_trace_id() + 1, None)
+                    .unwrap();
+            });
+            consumer.follows_from(&producer);
+            consumer.follows_from(&untraced);
+
+            producer.id().unwrap()
+        });
+
+        let spans = spans.lock().unwrap();
+        let consumer = spans
+            .iter()
+            .find(|span| span.meta.name() == "consumer")
+            .unwrap();
+        assert_eq!(consumer.trace_id, explicit_trace_id() + 1);
+        assert_eq!(
+            consumer.follows_from,
+            vec![trace::Link {
+                span_id: producer_id,
+                trace_id: explicit_trace_id(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_errored() {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let cap = TestTelemetry::new(spans.clone(), Arc::new(Mutex::new(Vec::new())));
+        let layer = TelemetryLayer::new("test_svc_name", cap, |x| x);
+        let subscriber = layer.with_subscriber(registry::Registry::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::info_span!("root");
+            let _root = root.enter();
+            trace::register_dist_tracing_root::<SpanId, TraceId>(explicit_trace_id(), None)
+                .unwrap();
+            tracing::info_span!("failed").in_scope(|| {
+                tracing::error!("first");
+                tracing::error!("second");
+            });
+            tracing::info_span!("ok").in_scope(|| tracing::warn!("not an error"));
+        });
+
+        let spans = spans.lock().unwrap();
+        let errored = |name: &str| {
+            spans
+                .iter()
+                .find(|span| span.meta.name() == name)
+                .unwrap()
+                .errored
+        };
+        assert!(errored("failed"));
+        assert!(!errored("ok"));
+        // only events directly within a span count
+        assert!(!errored("root"));
+    }
+
+    #[test]
+    fn test_baggage() {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let cap = TestTelemetry::new(spans.clone(), events.clone());
+        let layer = TelemetryLayer::new("test_svc_name", cap, |x| x);
+        let subscriber = layer.with_subscriber(registry::Registry::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::info_span!("root");
+            let _root = root.enter();
+            assert_eq!(
+                trace::set_baggage::<SpanId, TraceId>("tenant_id", "a"),
+                Err(TraceCtxError::NoParentNodeHasTraceCtx)
+            );
+            trace::register_dist_tracing_root::<SpanId, TraceId>(explicit_trace_id(), None)
+                .unwrap();
+            trace::set_baggage::<SpanId, TraceId>("tenant_id", "a").unwrap();
+            trace::set_baggage::<SpanId, TraceId>("region", "eu").unwrap();
+
+            tracing::info_span!("child").in_scope(|| {
+                trace::set_baggage::<SpanId, TraceId>("tenant_id", "b").unwrap();
+                tracing::info!("event");
+                assert_eq!(
+                    trace::get_baggage::<SpanId, TraceId>("tenant_id"),
+                    Ok(Some("b".to_string()))
+                );
+            });
+            assert_eq!(
+                trace::get_baggage::<SpanId, TraceId>("tenant_id"),
+                Ok(Some("a".to_string()))
+            );
+            assert_eq!(trace::get_baggage::<SpanId, TraceId>("missing"), Ok(None));
+
+            assert_eq!(
+                trace::set_baggage::<SpanId, TraceId>("big", "x".repeat(trace::MAX_BAGGAGE_BYTES)),
+                Err(TraceCtxError::BaggageLimitExceeded)
+            );
+            assert_eq!(
+                trace::current_baggage::<SpanId, TraceId>().unwrap().len(),
+                2
+            );
+        });
+
+        let spans = spans.lock().unwrap();
+        let baggage = |name| {
+            spans
+                .iter()
+                .find(|span| span.meta.name() == name)
+                .unwrap()
+                .baggage
+                .to_vec()
+        };
+        let entry = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(
+            baggage("child"),
+            vec![entry("tenant_id", "b"), entry("region", "eu")]
+        );
+        assert_eq!(
+            baggage("root"),
+            vec![entry("tenant_id", "a"), entry("region", "eu")]
+        );
+        assert_eq!(
+            events.lock().unwrap()[0].baggage.to_vec(),
+            vec![entry("tenant_id", "b"), entry("region", "eu")]
+        );
+    }
+
+    #[test]
+    fn test_root_key() {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let cap = TestTelemetry::new(spans.clone(), events.clone());
+        let layer = TelemetryLayer::new("test_svc_name", cap, |x| x);
+        let subscriber = layer.with_subscriber(registry::Registry::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::info_span!("root");
+            let _root_guard = root.enter();
+            trace::register_dist_tracing_root::<SpanId, TraceId>(explicit_trace_id(), None)
+                .unwrap();
+            let child = tracing::info_span!("child");
+            let _child_guard = child.enter();
+            tracing::info!("event");
+            let res = trace::with_current_trace::<
+                SpanId,
+                TraceId,
+                crate::telemetry::BlackholeVisitor,
+                _,
+            >(|_, _, root_key, _, _| root_key.map(str::to_string));
+            assert_eq!(res, Ok(Some("root".to_string())));
+        });
+
+        let spans = spans.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        for span in spans.iter() {
+            assert_eq!(span.root_key.as_deref(), Some("root"));
+        }
+        assert_eq!(events.lock().unwrap()[0].root_key.as_deref(), Some("root"));
+    }
+
     fn with_test_scenario_runner<F>(f: F)
     where
         F: Fn(),
@@ -445,13 +1023,18 @@ mod tests {
 
         assert_eq!(root_span.parent_id, Some(explicit_parent_span_id()));
         assert_eq!(root_span.trace_id, expected_trace_id);
+        assert!(root_span.local_root);
+        assert_eq!(root_span.root_name, "f");
 
         for (span, event) in child_spans.iter().zip(events.iter()) {
             // confirm parent and trace ids are as expected
             assert_eq!(span.parent_id, Some(root_span.id.clone()));
+            assert!(!span.local_root);
             assert_eq!(event.parent_id, Some(span.id.clone()));
             assert_eq!(span.trace_id, explicit_trace_id());
             assert_eq!(event.trace_id, explicit_trace_id());
+            assert_eq!(span.root_name, "f");
+            assert_eq!(event.root_name, "f");
         }
     }
 }
@@ -1,8 +1,15 @@
-use crate::telemetry_layer::TraceCtxRegistry;
-use std::time::SystemTime;
+use crate::telemetry_layer::{
+    Baggage, LazyTraceCtx, ReportTraceStart, RootKey, TraceCtx, TraceCtxRegistry,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tracing_subscriber::registry::LookupSpan;
 
 /// Register the current span as the local root of a distributed trace.
+///
+/// The `Telemetry::root_key` of the trace is computed here, from the fields recorded on the span
+/// so far.
 pub fn register_dist_tracing_root<SpanId, TraceId>(
     trace_id: TraceId,
     remote_parent_span: Option<SpanId>,
@@ -12,14 +19,18 @@ where
     TraceId: 'static + Clone + Send + Sync,
 {
     let span = tracing::Span::current();
+    let root_name = span.metadata().map(|meta| meta.name()).unwrap_or_default();
     span.with_subscriber(|(current_span_id, dispatch)| {
         if let Some(trace_ctx_registry) =
             dispatch.downcast_ref::<TraceCtxRegistry<SpanId, TraceId>>()
         {
+            let root_key = root_key::<SpanId, TraceId>(dispatch, current_span_id);
             trace_ctx_registry.record_trace_ctx(
                 trace_id,
                 remote_parent_span,
                 current_span_id.clone(),
+                root_name,
+                root_key,
             );
             Ok(())
         } else {
@@ -29,6 +40,88 @@ where
     .ok_or(TraceCtxError::NoEnabledSpan)?
 }
 
+/// Register the current span as the local root of a distributed trace, as
+/// `register_dist_tracing_root`, and pass the visitor holding its fields to `f`, e.g. to record
+/// fields on the root span, as `with_current_visitor` does. `f` runs before the span is
+/// registered, so `Telemetry::root_key` sees the fields it records.
+///
+/// Fails with `TraceCtxError::VisitorTypeMismatch`, without registering the span, if `Visitor`
+/// is not the visitor type of the `Telemetry` capability of the registered `TelemetryLayer`.
+pub fn register_dist_tracing_root_with_visitor<SpanId, TraceId, Visitor, T>(
+    trace_id: TraceId,
+    remote_parent_span: Option<SpanId>,
+    f: impl FnOnce(&mut Visitor) -> T,
+) -> Result<T, TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+    Visitor: 'static,
+{
+    let span = tracing::Span::current();
+    let root_name = span.metadata().map(|meta| meta.name()).unwrap_or_default();
+    span.with_subscriber(|(current_span_id, dispatch)| {
+        let trace_ctx_registry = dispatch
+            .downcast_ref::<TraceCtxRegistry<SpanId, TraceId>>()
+            .ok_or(TraceCtxError::TelemetryLayerNotRegistered)?;
+
+        let registry = dispatch
+            .downcast_ref::<tracing_subscriber::Registry>()
+            .ok_or(TraceCtxError::RegistrySubscriberNotRegistered)?;
+
+        // failure here indicates a broken span link, panic is valid
+        let span = registry
+            .span(current_span_id)
+            .expect("span data not found during register_dist_tracing_root_with_visitor");
+        let res = span
+            .extensions_mut()
+            .get_mut::<Visitor>()
+            .map(f)
+            .ok_or(TraceCtxError::VisitorTypeMismatch)?;
+
+        let root_key = root_key::<SpanId, TraceId>(dispatch, current_span_id);
+        trace_ctx_registry.record_trace_ctx(
+            trace_id,
+            remote_parent_span,
+            current_span_id.clone(),
+            root_name,
+            root_key,
+        );
+        Ok(res)
+    })
+    .ok_or(TraceCtxError::NoEnabledSpan)?
+}
+
+// Computes the `Telemetry::root_key` of the span with the given id via the registered
+// `TelemetryLayer`, if any.
+fn root_key<SpanId, TraceId>(dispatch: &tracing::Dispatch, id: &tracing::Id) -> Option<Arc<str>>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+{
+    let root_key = dispatch.downcast_ref::<RootKey<SpanId, TraceId>>()?;
+    (root_key.key)(dispatch, id)
+}
+
+/// Report the start of the trace the current span is part of to the `Telemetry` capability of
+/// the registered `TelemetryLayer`, via `Telemetry::report_trace_start`, e.g. right after
+/// registering the current span as the local root of the trace via `register_dist_tracing_root`.
+///
+/// Fails if the current span is not part of a distributed trace.
+pub fn report_trace_start<SpanId, TraceId>() -> Result<(), TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+{
+    let span = tracing::Span::current();
+    span.with_subscriber(|(current_span_id, dispatch)| {
+        let report_trace_start = dispatch
+            .downcast_ref::<ReportTraceStart<SpanId, TraceId>>()
+            .ok_or(TraceCtxError::TelemetryLayerNotRegistered)?;
+        (report_trace_start.report)(dispatch, current_span_id)
+    })
+    .ok_or(TraceCtxError::NoEnabledSpan)?
+}
+
 /// Retrieve the distributed trace context associated with the current span. Returns the
 /// `TraceId`, if any, that the current span is associated with along with the `SpanId`
 /// belonging to the current span.
@@ -36,6 +129,257 @@ pub fn current_dist_trace_ctx<SpanId, TraceId>() -> Result<(TraceId, SpanId), Tr
 where
     SpanId: 'static + Clone + Send + Sync,
     TraceId: 'static + Clone + Send + Sync,
+{
+    with_current_trace_ctx(|trace_ctx, current_span_id, trace_ctx_registry, _| {
+        (
+            trace_ctx.trace_id,
+            trace_ctx_registry.promote_span_id(current_span_id.clone()),
+        )
+    })
+}
+
+/// Retrieve the distributed trace context associated with the current span, along with the ids
+/// of the spans from the local root of the trace (see `register_dist_tracing_root`) down to the
+/// current span, inclusive. The `SpanId`s are ordered root-first, so the last one belongs to the
+/// current span.
+///
+/// Only spans within this process are included, not the remote parent span of the trace. The
+/// result is a snapshot of the span tree at the time of the call, and allocates in proportion to
+/// the depth of the current span.
+pub fn current_dist_trace_ctx_with_ancestry<SpanId, TraceId>(
+) -> Result<(TraceId, Vec<SpanId>), TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+{
+    with_current_trace_ctx(|trace_ctx, current_span_id, trace_ctx_registry, registry| {
+        let mut ancestry = Vec::new();
+        let mut next = Some(current_span_id.clone());
+        while let Some(id) = next {
+            // failure here indicates a broken parent id span link, panic is valid
+            let span = registry
+                .span(&id)
+                .expect("span data not found during current_dist_trace_ctx_with_ancestry");
+            next = if trace_ctx_registry.is_local_root(&id) {
+                None
+            } else {
+                span.parent().map(|parent| parent.id())
+            };
+            ancestry.push(trace_ctx_registry.promote_span_id(id));
+        }
+        ancestry.reverse();
+
+        (trace_ctx.trace_id, ancestry)
+    })
+}
+
+/// Retrieve the `TraceId` of the trace the current span is part of, along with the name of the
+/// span registered as its local root via `register_dist_tracing_root`.
+pub fn current_dist_trace_root<SpanId, TraceId>() -> Result<(TraceId, &'static str), TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+{
+    with_current_trace_ctx::<SpanId, TraceId, _, _>(|trace_ctx, _, _, _| {
+        (trace_ctx.trace_id, trace_ctx.root_name)
+    })
+}
+
+/// Maximum number of baggage entries in effect for a span, as recommended by the W3C Baggage
+/// specification.
+pub const MAX_BAGGAGE_ENTRIES: usize = 64;
+
+/// Maximum total length, in bytes, of the keys and values of the baggage entries in effect for a
+/// span, as recommended by the W3C Baggage specification.
+pub const MAX_BAGGAGE_BYTES: usize = 8192;
+
+/// Sets a baggage entry on the current span, replacing any entry with the same key.
+///
+/// Baggage is a set of key/value pairs, such as a tenant id, carried along with the trace
+/// context: spans created within the current span, after this call, inherit its baggage, and
+/// each span's baggage is reported along with it in `Span::baggage`. Entries set on a span
+/// are not visible to its parent, nor to spans created before the entry was set.
+///
+/// Fails if the current span is not part of a distributed trace, or if setting the entry would
+/// exceed `MAX_BAGGAGE_ENTRIES` or `MAX_BAGGAGE_BYTES`.
+pub fn set_baggage<SpanId, TraceId>(
+    key: impl Into<String>,
+    value: impl Into<String>,
+) -> Result<(), TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+{
+    let (key, value) = (key.into(), value.into());
+    with_current_trace_ctx::<SpanId, TraceId, _, _>(|_, current_span_id, _, registry| {
+        // failure here indicates a broken span link, panic is valid
+        let span = registry
+            .span(current_span_id)
+            .expect("span data not found during set_baggage");
+        let mut extensions_mut = span.extensions_mut();
+
+        let mut entries = extensions_mut
+            .get_mut::<Baggage>()
+            .map(|Baggage(entries)| (**entries).clone())
+            .unwrap_or_default();
+        match entries.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => entries.push((key, value)),
+        }
+
+        let bytes: usize = entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+        if entries.len() > MAX_BAGGAGE_ENTRIES || bytes > MAX_BAGGAGE_BYTES {
+            return Err(TraceCtxError::BaggageLimitExceeded);
+        }
+
+        extensions_mut.replace(Baggage(Arc::new(entries)));
+        Ok(())
+    })?
+}
+
+/// Returns the value of the baggage entry with the given key in effect for the current span, if
+/// any. See `set_baggage`.
+pub fn get_baggage<SpanId, TraceId>(key: &str) -> Result<Option<String>, TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+{
+    let baggage = current_baggage::<SpanId, TraceId>()?;
+    Ok(baggage.into_iter().find(|(k, _)| k == key).map(|(_, v)| v))
+}
+
+/// Returns all baggage entries in effect for the current span, in the order they were first
+/// set, e.g. to propagate them to another process. See `set_baggage`.
+pub fn current_baggage<SpanId, TraceId>() -> Result<Vec<(String, String)>, TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+{
+    with_current_trace_ctx::<SpanId, TraceId, _, _>(|_, current_span_id, _, registry| {
+        // failure here indicates a broken span link, panic is valid
+        let span = registry
+            .span(current_span_id)
+            .expect("span data not found during current_baggage");
+        let extensions = span.extensions();
+        extensions
+            .get::<Baggage>()
+            .map(|Baggage(entries)| (**entries).clone())
+            .unwrap_or_default()
+    })
+}
+
+/// Passes the visitor holding the fields recorded on the current span to `f`, e.g. to record
+/// values not known when the span was created, under names it did not declare.
+///
+/// `Visitor` must be the visitor type of the `Telemetry` capability of the registered
+/// `TelemetryLayer`, or this fails with `TraceCtxError::VisitorTypeMismatch`. Fails as well if
+/// the current span is not part of a distributed trace.
+pub fn with_current_visitor<SpanId, TraceId, Visitor, T>(
+    f: impl FnOnce(&mut Visitor) -> T,
+) -> Result<T, TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+    Visitor: 'static,
+{
+    with_current_trace_ctx::<SpanId, TraceId, _, _>(|_, current_span_id, _, registry| {
+        // failure here indicates a broken span link, panic is valid
+        let span = registry
+            .span(current_span_id)
+            .expect("span data not found during with_current_visitor");
+        let mut extensions_mut = span.extensions_mut();
+        extensions_mut
+            .get_mut::<Visitor>()
+            .map(f)
+            .ok_or(TraceCtxError::VisitorTypeMismatch)
+    })?
+}
+
+/// Passes the `TraceId` of the trace the current span is part of, the name and
+/// `Telemetry::root_key` of its local root span, along with the current span's visitor and
+/// baggage, to `f`.
+///
+/// Unlike `current_dist_trace_root` and `current_baggage`, they are borrowed rather than
+/// cloned, so this does not allocate once the current span's trace context has been evaluated,
+/// e.g. by an earlier call. Fails as `with_current_visitor` does.
+pub fn with_current_trace<SpanId, TraceId, Visitor, T>(
+    f: impl FnOnce(&TraceId, &'static str, Option<&str>, &Visitor, &[(String, String)]) -> T,
+) -> Result<T, TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+    Visitor: 'static,
+{
+    let span = tracing::Span::current();
+    span.with_subscriber(|(current_span_id, dispatch)| {
+        let trace_ctx_registry = dispatch
+            .downcast_ref::<TraceCtxRegistry<SpanId, TraceId>>()
+            .ok_or(TraceCtxError::TelemetryLayerNotRegistered)?;
+
+        let registry = dispatch
+            .downcast_ref::<tracing_subscriber::Registry>()
+            .ok_or(TraceCtxError::RegistrySubscriberNotRegistered)?;
+
+        // failure here indicates a broken span link, panic is valid
+        let span = registry
+            .span(current_span_id)
+            .expect("span data not found during with_current_trace");
+        if span
+            .extensions()
+            .get::<LazyTraceCtx<SpanId, TraceId>>()
+            .is_none()
+        {
+            let iter = itertools::unfold(Some(current_span_id.clone()), |st| match st {
+                Some(target_id) => {
+                    // failure here indicates a broken parent id span link, panic is valid
+                    let res = registry
+                        .span(target_id)
+                        .expect("span data not found during eval_ctx for with_current_trace");
+                    *st = res.parent().map(|x| x.id());
+                    Some(res)
+                }
+                None => None,
+            });
+            // caches the trace context in the extensions of the span and its ancestors
+            trace_ctx_registry
+                .eval_ctx(iter)
+                .ok_or(TraceCtxError::NoParentNodeHasTraceCtx)?;
+        }
+
+        let extensions = span.extensions();
+        let LazyTraceCtx(trace_ctx) = extensions
+            .get::<LazyTraceCtx<SpanId, TraceId>>()
+            .expect("cached by eval_ctx");
+        let visitor = extensions
+            .get::<Visitor>()
+            .ok_or(TraceCtxError::VisitorTypeMismatch)?;
+        let baggage = extensions
+            .get::<Baggage>()
+            .map(|Baggage(entries)| entries.as_slice())
+            .unwrap_or_default();
+        Ok(f(
+            &trace_ctx.trace_id,
+            trace_ctx.root_name,
+            trace_ctx.root_key.as_deref(),
+            visitor,
+            baggage,
+        ))
+    })
+    .ok_or(TraceCtxError::NoEnabledSpan)?
+}
+
+// Evaluates the trace context of the current span, passing it to `f` along with the span's id and
+// the registries it was found in.
+fn with_current_trace_ctx<SpanId, TraceId, T, F>(f: F) -> Result<T, TraceCtxError>
+where
+    SpanId: 'static + Clone + Send + Sync,
+    TraceId: 'static + Clone + Send + Sync,
+    F: FnOnce(
+        TraceCtx<SpanId, TraceId>,
+        &tracing::Id,
+        &TraceCtxRegistry<SpanId, TraceId>,
+        &tracing_subscriber::Registry,
+    ) -> T,
 {
     let span = tracing::Span::current();
     span.with_subscriber(|(current_span_id, dispatch)| {
@@ -59,15 +403,11 @@ where
             None => None,
         });
 
-        trace_ctx_registry
+        let trace_ctx = trace_ctx_registry
             .eval_ctx(iter)
-            .map(|x| {
-                (
-                    x.trace_id,
-                    trace_ctx_registry.promote_span_id(current_span_id.clone()),
-                )
-            })
-            .ok_or(TraceCtxError::NoParentNodeHasTraceCtx)
+            .ok_or(TraceCtxError::NoParentNodeHasTraceCtx)?;
+
+        Ok(f(trace_ctx, current_span_id, trace_ctx_registry, registry))
     })
     .ok_or(TraceCtxError::NoEnabledSpan)?
 }
@@ -85,15 +425,22 @@ pub enum TraceCtxError {
     NoEnabledSpan,
     /// Attempted to evaluate the current distributed trace context but none was found. If this occurs, you should check to make sure that `register_dist_tracing_root` is called in some parent of the current span.
     NoParentNodeHasTraceCtx,
+    /// Setting a baggage entry would exceed `MAX_BAGGAGE_ENTRIES` or `MAX_BAGGAGE_BYTES`.
+    BaggageLimitExceeded,
+    /// The current span has no visitor of the requested type, which must be the visitor type of the `Telemetry` capability of the registered `TelemetryLayer`.
+    VisitorTypeMismatch,
 }
 
 /// A `Span` holds ready-to-publish information gathered during the lifetime of a `tracing::Span`.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Span<Visitor, SpanId, TraceId> {
     /// id identifying this span
     pub id: SpanId,
     /// `TraceId` identifying the trace to which this span belongs
     pub trace_id: TraceId,
+    /// name of the span registered as the local root of this trace via `register_dist_tracing_root`
+    pub root_name: &'static str,
     /// optional parent span id
     pub parent_id: Option<SpanId>,
     /// UTC time at which this span was initialized
@@ -104,15 +451,65 @@ pub struct Span<Visitor, SpanId, TraceId> {
     pub meta: &'static tracing::Metadata<'static>,
     /// name of the service on which this span occured
     pub service_name: &'static str,
+    /// whether this span was registered as the local root of its trace via `register_dist_tracing_root`
+    pub local_root: bool,
+    /// spans this span follows from, as recorded via `tracing::Span::follows_from`, in the order recorded
+    pub follows_from: Vec<Link<SpanId, TraceId>>,
+    /// baggage entries in effect for this span when it closed, as set via `set_baggage`, in the order they were first set. Shared with the span's context rather than copied
+    pub baggage: Arc<Vec<(String, String)>>,
+    /// key derived from the fields of the local root of this trace via `Telemetry::root_key`, as of its registration
+    pub root_key: Option<Arc<str>>,
+    /// total time this span was entered for, if tracked via `Telemetry::track_busy_time`. The rest of its lifetime, from `initialized_at` to `completed_at`, it was idle
+    pub busy: Option<Duration>,
+    /// whether an event at the `ERROR` level occurred directly within this span, i.e. with this span as its parent
+    pub errored: bool,
+    /// flag shared by the spans and events of this trace under the same local root, see `TraceFlag`
+    pub trace_flag: TraceFlag,
     /// values accumulated by visiting fields observed by the `tracing::Span` this span was derived from
     pub values: Visitor,
 }
 
+/// A causal link from a span to a span it follows from, other than its parent, e.g. the span
+/// which enqueued the work the span performs.
+///
+/// Only spans which are part of a trace at the time `tracing::Span::follows_from` is called are
+/// linked to, as other spans are not reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link<SpanId, TraceId> {
+    /// id of the linked span
+    pub span_id: SpanId,
+    /// `TraceId` of the trace the linked span belongs to, which may differ from the linking span's
+    pub trace_id: TraceId,
+}
+
+/// A flag shared by the spans and events of a trace under the same local root, registered via
+/// `register_dist_tracing_root`, which any of them may set, e.g. to keep the whole trace once one
+/// of its spans asks for it.
+///
+/// Starts unset. It is held in the context of the trace rather than in any global state.
+#[derive(Debug, Clone, Default)]
+pub struct TraceFlag(Arc<AtomicBool>);
+
+impl TraceFlag {
+    /// Sets the flag for the whole trace.
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the flag has been set by any span or event of the trace.
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 /// An `Event` holds ready-to-publish information derived from a `tracing::Event`.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Event<Visitor, SpanId, TraceId> {
     /// `TraceId` identifying the trace to which this event belongs
     pub trace_id: TraceId,
+    /// name of the span registered as the local root of this trace via `register_dist_tracing_root`
+    pub root_name: &'static str,
     /// optional parent span id
     pub parent_id: Option<SpanId>,
     /// UTC time at which this event was initialized
@@ -121,6 +518,12 @@ pub struct Event<Visitor, SpanId, TraceId> {
     pub meta: &'static tracing::Metadata<'static>,
     /// name of the service on which this event occured
     pub service_name: &'static str,
+    /// baggage entries in effect for this event's parent span when it occured, as set via `set_baggage`. Shared with the span's context rather than copied
+    pub baggage: Arc<Vec<(String, String)>>,
+    /// key derived from the fields of the local root of this trace via `Telemetry::root_key`, as of its registration
+    pub root_key: Option<Arc<str>>,
+    /// flag shared by the spans and events of this trace under the same local root, see `TraceFlag`
+    pub trace_flag: TraceFlag,
     /// values accumulated by visiting the fields of the `tracing::Event` this event was derived from
     pub values: Visitor,
 }
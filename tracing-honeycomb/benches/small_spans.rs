@@ -0,0 +1,92 @@
+//! Measures the allocations made, and time taken, to report spans with few fields.
+//!
+//! Run with `cargo bench --bench small_spans`. Allocations are counted by a global allocator
+//! wrapping the system allocator, so the counts are exact and comparable across changes.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use tracing_honeycomb::{ReportError, Reporter, TraceId};
+use tracing_subscriber::layer::SubscriberExt;
+
+const ITERATIONS: usize = 100_000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// discards reported data, so that only the layer's own work is measured
+struct NullReporter;
+
+impl Reporter for NullReporter {
+    fn report_data(
+        &self,
+        _data: HashMap<String, libhoney::Value>,
+        _timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        Ok(())
+    }
+}
+
+fn measure(name: &str, f: impl Fn()) {
+    // warm up, e.g. to populate the registry's span slab
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    println!(
+        "{:<12} {:>8.2} allocations/span {:>10.0} ns/span",
+        name,
+        allocations as f64 / ITERATIONS as f64,
+        elapsed.as_nanos() as f64 / ITERATIONS as f64,
+    );
+}
+
+fn main() {
+    let layer =
+        tracing_honeycomb::new_honeycomb_telemetry_layer_with_reporter("bench", NullReporter);
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::info_span!("root");
+        let _guard = root.enter();
+        tracing_honeycomb::register_dist_tracing_root(TraceId::new(), None).unwrap();
+
+        measure("no fields", || tracing::info_span!("span").in_scope(|| {}));
+        measure("one field", || {
+            tracing::info_span!("span", user = "alice").in_scope(|| {})
+        });
+        measure("two fields", || {
+            tracing::info_span!("span", user = "alice", count = 3).in_scope(|| {})
+        });
+    });
+}
@@ -0,0 +1,85 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "use_parking_lot")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "use_parking_lot"))]
+use std::sync::{Mutex, PoisonError};
+
+/// Source of the timestamps of spans and events, from which their `duration_ms` is derived.
+///
+/// Implemented for closures returning a `SystemTime`.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> SystemTime + Send + Sync,
+{
+    fn now(&self) -> SystemTime {
+        self()
+    }
+}
+
+impl fmt::Debug for dyn Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+/// Clock reading the system time. This is the default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock which only moves when told to, for reproducible timestamps and durations in tests.
+///
+/// Clones share the same time, so a clone kept before the clock is handed to the layer can be
+/// used to advance it.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl ManualClock {
+    /// Returns a new `ManualClock` stopped at `now`.
+    pub fn new(now: SystemTime) -> Self {
+        ManualClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Sets the current time.
+    pub fn set(&self, now: SystemTime) {
+        *self.lock() = now;
+    }
+
+    /// Moves the current time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.lock() += duration;
+    }
+
+    #[cfg(not(feature = "use_parking_lot"))]
+    fn lock(&self) -> std::sync::MutexGuard<'_, SystemTime> {
+        self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "use_parking_lot")]
+    fn lock(&self) -> parking_lot::MutexGuard<'_, SystemTime> {
+        self.now.lock()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.lock()
+    }
+}
@@ -4,13 +4,146 @@ use crate::TraceId;
 
 /// A port of beeline-nodejs's code for the same functionality.
 ///
-/// Samples deterministically on a given TraceId via a SHA-1 hash.
+/// Samples deterministically on a given TraceId via a SHA-1 hash. The algorithm is part of
+/// this crate's stable behavior, and matches the Honeycomb beelines so that a trace is kept or
+/// dropped consistently across services:
+///
+/// 1. Take the SHA-1 digest of the trace id's UTF-8 bytes.
+/// 2. Read the first 4 bytes of the digest as a big-endian `u32`.
+/// 3. Keep the trace if that value is `<= u32::MAX / sample_rate`.
+///
+/// This keeps the same share of traces as a `hash % sample_rate == 0` check would, but is the
+/// comparison the beelines make, so a service using them decides every trace alike.
+///
+/// As in the beelines, the trace id is hashed exactly as propagated, so different encodings of
+/// the same 128-bit id, e.g. hyphenated or uppercase, are sampled independently of each other.
+///
+/// `sample_rate` must be non-zero.
 ///
 /// https://github.com/honeycombio/beeline-nodejs/blob/main/lib/deterministic_sampler.js
 pub(crate) fn sample(sample_rate: u32, trace_id: &TraceId) -> bool {
-    let sum = Sha1::digest(trace_id.as_ref());
+    sample_key(sample_rate, &trace_id.0)
+}
+
+/// Samples deterministically on an arbitrary key, such as a field value, using the same
+/// algorithm as `sample`, so that a key equal to a trace id is sampled alike.
+///
+/// `sample_rate` must be non-zero.
+pub(crate) fn sample_key(sample_rate: u32, key: &str) -> bool {
+    decide(sample_rate, &Sha1::digest(key.as_bytes())[..])
+}
+
+/// Samples as `sample`, but on the hash of the trace id prefixed with the big-endian bytes
+/// of `salt`, so that samplers with different salts decide independently of each other.
+///
+/// `sample_rate` must be non-zero.
+pub(crate) fn sample_salted(sample_rate: u32, trace_id: &TraceId, salt: u64) -> bool {
+    let mut hasher = Sha1::new();
+    hasher.update(salt.to_be_bytes());
+    hasher.update(trace_id.0.as_bytes());
+    decide(sample_rate, &hasher.finalize()[..])
+}
+
+fn decide(sample_rate: u32, sum: &[u8]) -> bool {
     // Since we are operating on u32's in rust, there is no need for the original's `>>> 0`.
-    let upper_bound = std::u32::MAX / sample_rate;
+    let upper_bound = u32::MAX / sample_rate;
 
     u32::from_be_bytes([sum[0], sum[1], sum[2], sum[3]]) <= upper_bound
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // pins the sampling algorithm: these decisions must not change across versions
+    #[test]
+    fn samples_fixed_trace_ids() {
+        let cases: &[(&str, u32, bool)] = &[
+            // first 4 digest bytes: 299056892
+            ("4bf92f3577b34da6a3ce929d0e0e4736", 1, true),
+            ("4bf92f3577b34da6a3ce929d0e0e4736", 2, true),
+            ("4bf92f3577b34da6a3ce929d0e0e4736", 10, true),
+            ("4bf92f3577b34da6a3ce929d0e0e4736", 100, false),
+            // first 4 digest bytes: 4249172635
+            ("80f198ee56343ba864fe8b2a57d3eff7", 1, true),
+            ("80f198ee56343ba864fe8b2a57d3eff7", 2, false),
+            // first 4 digest bytes: 1715473995
+            ("trace-1", 2, true),
+            ("trace-1", 10, false),
+            // first 4 digest bytes: 1996805
+            ("trace-18", 100, true),
+        ];
+
+        for (trace_id, sample_rate, expected) in cases {
+            assert_eq!(
+                sample(*sample_rate, &TraceId::from(*trace_id)),
+                *expected,
+                "trace id {} at sample rate {}",
+                trace_id,
+                sample_rate
+            );
+        }
+    }
+
+    // expected decisions follow beeline-nodejs's deterministic_sampler.js, i.e.
+    // `sha1(id).readUInt32BE(0) <= (MAX_UINT32 / rate) >>> 0`, for ids in the forms propagated
+    // by the beelines (hyphenated UUIDs) and by OpenTelemetry (32 hex characters)
+    #[test]
+    fn matches_beeline_decisions() {
+        let cases: &[(&str, [bool; 4])] = &[
+            // first 4 digest bytes: 1584359824
+            (
+                "b7b7ed3a-3b1a-4b6c-9a4b-1c4b2d8e0f11",
+                [true, false, false, false],
+            ),
+            // first 4 digest bytes: 170162541
+            (
+                "0af7651916cd43dd8448eb211c80319c",
+                [true, true, true, false],
+            ),
+            // first 4 digest bytes: 529187087
+            ("abcdef", [true, true, false, false]),
+            // first 4 digest bytes: 3946410775
+            (
+                "8d2a6b1f3e4c5d6a7b8c9d0e1f2a3b4c",
+                [false, false, false, false],
+            ),
+        ];
+
+        for (trace_id, expected) in cases {
+            for (sample_rate, expected) in [2, 5, 10, 100].iter().zip(expected) {
+                assert_eq!(
+                    sample(*sample_rate, &TraceId::from(*trace_id)),
+                    *expected,
+                    "trace id {} at sample rate {}",
+                    trace_id,
+                    sample_rate
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn salt_changes_decisions_at_the_same_rate() {
+        let ids: Vec<_> = (0..2000u128).map(TraceId::from_u128).collect();
+        let kept =
+            |salt: u64| -> Vec<bool> { ids.iter().map(|id| sample_salted(4, id, salt)).collect() };
+        let (a, b) = (kept(1), kept(2));
+        assert_eq!(a, kept(1));
+        assert_ne!(a, b);
+        for decisions in &[a, b] {
+            let count = decisions.iter().filter(|kept| **kept).count();
+            assert!((400..600).contains(&count), "kept {} of 2000", count);
+        }
+    }
+
+    #[test]
+    fn samples_trace_ids_as_propagated() {
+        // the same 128-bit id in two encodings, hashed as two different strings
+        let id = TraceId::from("80f198ee56343ba864fe8b2a57d3eff7");
+        let hyphenated = TraceId::from("80f198ee-5634-3ba8-64fe-8b2a57d3eff7");
+        assert_eq!(id.as_u128(), hyphenated.as_u128());
+        assert!(!sample(2, &id));
+        assert!(sample(2, &hyphenated));
+    }
+}
@@ -0,0 +1,163 @@
+//! Names of the fields this crate sets on every reported span and event.
+//!
+//! These are Honeycomb's reserved columns, along with the span and event metadata provided by
+//! `tracing`. Reference them when querying, or when implementing a [`Reporter`](crate::Reporter)
+//! which interprets reported data, rather than repeating the names.
+//!
+//! A span or event field recorded with one of these names is reported as `tracing.<name>`
+//! instead, e.g. a `duration_ms` field recorded by the application is reported as
+//! `tracing.duration_ms`, so it neither overwrites nor is overwritten by the crate's own value.
+
+/// Trace id of the span or event.
+pub const TRACE_ID: &str = "trace.trace_id";
+
+/// Id of the span. Not set on events.
+pub const SPAN_ID: &str = "trace.span_id";
+
+/// Id of the parent span of the span or event. Not set on the root span of a trace.
+pub const PARENT_ID: &str = "trace.parent_id";
+
+/// Name of the service which reported the span or event.
+pub const SERVICE_NAME: &str = "service_name";
+
+/// `tracing` level of the span or event, e.g. `INFO`.
+pub const LEVEL: &str = "level";
+
+/// Timestamp column. Not set as a field, but from the span or event's timestamp.
+pub const TIMESTAMP: &str = "Timestamp";
+
+/// `tracing` name of the span or event.
+pub const NAME: &str = "name";
+
+/// `tracing` target of the span or event, usually its module path.
+pub const TARGET: &str = "target";
+
+/// Elapsed time between the span being opened and closed, in fractional milliseconds. Not set
+/// on events.
+pub const DURATION_MS: &str = "duration_ms";
+
+/// Part of [`DURATION_MS`] during which the span was entered, in fractional milliseconds. Only
+/// set on spans if [`Builder::with_busy_time`](crate::Builder::with_busy_time) is enabled. Not
+/// reserved, so that fields of this name are kept as they are when it is off, and takes
+/// precedence over a field of the same name when it is on, as does [`IDLE_MS`].
+pub const BUSY_MS: &str = "busy_ms";
+
+/// Part of [`DURATION_MS`] during which the span was not entered, e.g. while a future it
+/// instruments awaited, in fractional milliseconds. Set alongside [`BUSY_MS`].
+pub const IDLE_MS: &str = "idle_ms";
+
+/// Marks an event as a span event, i.e. an annotation of the span it occurred in rather than a
+/// span of its own. Set to [`SPAN_EVENT`] on events, not set on spans.
+pub const ANNOTATION_TYPE: &str = "meta.annotation_type";
+
+/// Value of [`ANNOTATION_TYPE`] on events.
+pub const SPAN_EVENT: &str = "span_event";
+
+/// Value of [`ANNOTATION_TYPE`] on the event marking the start of a trace, reported if enabled
+/// via [`Builder::with_trace_start_events`](crate::Builder::with_trace_start_events).
+pub const TRACE_START: &str = "trace_start";
+
+/// Marks a span as the root of its trace within this service, i.e. the span registered via
+/// `register_dist_tracing_root`, which Honeycomb highlights in the trace view. Set to
+/// [`ROOT_SPAN`] on such spans, not set on other spans nor on events.
+pub const SPAN_TYPE: &str = "meta.span_type";
+
+/// Value of [`SPAN_TYPE`] on root spans.
+pub const ROOT_SPAN: &str = "root";
+
+/// OpenTelemetry kind of the span, in the lowercase form used by Honeycomb's own OpenTelemetry
+/// ingest: `server`, `client`, `producer`, `consumer` or `internal`. Set from the span's
+/// [`OTEL_KIND`] field if it holds one of these kinds, in any case, `internal` otherwise. Not
+/// set on events.
+pub const SPAN_KIND: &str = "span.kind";
+
+/// Span field from which [`SPAN_KIND`] is set, e.g. `tracing::info_span!("request", otel.kind =
+/// "server")`, following `tracing-opentelemetry`. Not reserved, and only consumed if it holds a
+/// known kind, being reported as is otherwise.
+pub const OTEL_KIND: &str = "otel.kind";
+
+/// Value of [`ANNOTATION_TYPE`] on span links, reported for each span a span follows from, as
+/// recorded via `tracing::Span::follows_from`.
+///
+/// A link is reported with [`PARENT_ID`] set to the id of the linking span, along with
+/// [`LINK_TRACE_ID`] and [`LINK_SPAN_ID`]. Honeycomb lists a span's links alongside it in the
+/// trace view, navigating to the linked span, which may belong to another trace. A span
+/// following from several spans is reported with one link for each.
+pub const LINK: &str = "link";
+
+/// Trace id of the span linked to by a span link.
+pub const LINK_TRACE_ID: &str = "trace.link.trace_id";
+
+/// Id of the span linked to by a span link.
+pub const LINK_SPAN_ID: &str = "trace.link.span_id";
+
+/// Number of recorded fields dropped from the span or event for exceeding the configured maximum
+/// number of fields. Only set when fields were dropped.
+pub const FIELDS_DROPPED: &str = "meta.fields_dropped";
+
+/// Name of the library which reported the span or event, `tracing-honeycomb` unless
+/// configured via [`Builder::with_library`](crate::Builder::with_library).
+///
+/// Reserved, so a field recorded as `library.name` is reported as `tracing.library.name`, as
+/// is `library.version` below.
+pub const LIBRARY_NAME: &str = "library.name";
+
+/// Version of the library which reported the span or event, that of this crate unless
+/// configured via [`Builder::with_library`](crate::Builder::with_library).
+pub const LIBRARY_VERSION: &str = "library.version";
+
+/// Default value of [`LIBRARY_NAME`].
+pub const DEFAULT_LIBRARY_NAME: &str = "tracing-honeycomb";
+
+/// Default value of [`LIBRARY_VERSION`], the version of this crate.
+pub const DEFAULT_LIBRARY_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Set to `true` on spans which failed, if a [`StatusMapping`](crate::StatusMapping) is
+/// configured. Unlike the names above, not reserved: a span field recorded as `error` is
+/// reported under its own name, and itself marks the span as failed.
+pub const ERROR: &str = "error";
+
+/// OpenTelemetry status code of a span, if [`StatusMapping::StatusCode`](crate::StatusMapping)
+/// is configured: `2` (`STATUS_CODE_ERROR`) on spans which failed, `0` (`STATUS_CODE_UNSET`)
+/// otherwise. Not reserved, and takes precedence over a span field of the same name.
+pub const STATUS_CODE: &str = "status_code";
+
+/// Message of an event, as formatted from the format string passed to a macro such as
+/// `tracing::info!("hello {}", name)`. Reported under another name if configured via
+/// [`Builder::with_message_field`](crate::Builder::with_message_field). Not reserved, as
+/// `tracing` records the message as an ordinary field with this name.
+pub const MESSAGE: &str = "message";
+
+/// Source file of the callsite of the span or event, if
+/// [`Builder::with_source_location`](crate::Builder::with_source_location) is enabled. Not
+/// reserved, and takes precedence over a field of the same name, as do the two names below.
+pub const CODE_FILEPATH: &str = "code.filepath";
+
+/// Line number of the callsite of the span or event, if source locations are enabled.
+pub const CODE_LINENO: &str = "code.lineno";
+
+/// Module path of the callsite of the span or event, if source locations are enabled.
+pub const CODE_NAMESPACE: &str = "code.namespace";
+
+/// Every name above, except `BUSY_MS`, `IDLE_MS`, `OTEL_KIND`, `ERROR`, `STATUS_CODE`,
+/// `MESSAGE` and the `code.*` names, and other than values such as `SPAN_EVENT`. Span and
+/// event fields with these names are prefixed with `tracing.`.
+pub const RESERVED: [&str; 17] = [
+    SPAN_ID,
+    TRACE_ID,
+    PARENT_ID,
+    SERVICE_NAME,
+    LEVEL,
+    TIMESTAMP,
+    NAME,
+    TARGET,
+    DURATION_MS,
+    ANNOTATION_TYPE,
+    SPAN_TYPE,
+    SPAN_KIND,
+    FIELDS_DROPPED,
+    LINK_TRACE_ID,
+    LINK_SPAN_ID,
+    LIBRARY_NAME,
+    LIBRARY_VERSION,
+];
@@ -1,61 +1,2107 @@
 use chrono::{DateTime, Utc};
 
-use crate::reporter::Reporter;
-use crate::visitor::{event_to_values, span_to_values, HoneycombVisitor};
+use crate::clock::{Clock, SystemClock};
+use crate::reporter::{LibhoneyReporter, ReportError, Reporter};
+use crate::rich_reporter::{Flatten, Flattening, HoneycombEvent, HoneycombSpan, RichReporter};
+use crate::sampler::{Sampler, SamplingStats};
+use crate::tail_sampling::{recorded_error, PendingTrace, TailSampling};
+use crate::trace_start::TRACE_START_TARGET;
+use crate::visitor::{
+    event_to_values, insert_source_location, links_to_values, span_to_values, FieldConverter,
+    FieldType, HoneycombVisitor, KeyMapping, RedactedFields, StatusMapping, TimestampFormat,
+    DEFAULT_MAX_ERROR_SOURCES, DEFAULT_MAX_FIELD_LEN,
+};
+use std::borrow::Borrow;
 use std::collections::HashMap;
-use tracing_distributed::{Event, Span, Telemetry};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Once};
+use std::time::{Duration, SystemTime};
+use tracing_distributed::{Event, Span, Telemetry, TraceFlag};
 
-use crate::{SpanId, TraceId};
+#[cfg(not(feature = "use_parking_lot"))]
+use std::sync::PoisonError;
+
+use crate::{fields, SpanId, TraceId};
+
+// bounds how often the sweeper thread wakes, e.g. if the tail sampling timeout is zero
+const MIN_SWEEP_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How `HoneycombTelemetry` handles errors returned by its reporter
+#[derive(Clone, Default)]
+pub enum ErrorHandling {
+    /// Log errors to stderr. This is the default.
+    #[default]
+    Log,
+    /// Silently drop errors.
+    Ignore,
+    /// Increment the provided counter for each error.
+    Count(Arc<AtomicUsize>),
+    /// Panic on error.
+    Panic,
+    /// Pass errors to the provided handler, e.g. to route them to a logger or metric.
+    ///
+    /// Errors from [`ev.send`](libhoney::Event::send) on a `LibhoneyReporter` are
+    /// passed as [`ReportError::Transmission`].
+    Handler(Arc<dyn Fn(&ReportError) + Send + Sync>),
+}
+
+impl ErrorHandling {
+    /// Pass errors to the provided handler.
+    pub fn handler<F: Fn(&ReportError) + Send + Sync + 'static>(handler: F) -> Self {
+        ErrorHandling::Handler(Arc::new(handler))
+    }
+
+    pub(crate) fn handle(&self, err: ReportError) {
+        match self {
+            ErrorHandling::Log => {
+                // unable to report telemetry (eg buffer full) so log msg to stderr
+                eprintln!("error sending event to honeycomb, {}", err);
+            }
+            ErrorHandling::Ignore => {}
+            ErrorHandling::Count(count) => {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+            ErrorHandling::Panic => panic!("error sending event to honeycomb, {}", err),
+            ErrorHandling::Handler(handler) => handler(&err),
+        }
+    }
+}
+
+impl fmt::Debug for ErrorHandling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorHandling::Log => f.write_str("Log"),
+            ErrorHandling::Ignore => f.write_str("Ignore"),
+            ErrorHandling::Count(count) => f.debug_tuple("Count").field(count).finish(),
+            ErrorHandling::Panic => f.write_str("Panic"),
+            ErrorHandling::Handler(_) => f.write_str("Handler(..)"),
+        }
+    }
+}
+
+fn library_fields(name: String, version: String) -> [(String, libhoney::Value); 2] {
+    [
+        (
+            fields::LIBRARY_NAME.to_string(),
+            libhoney::Value::String(name),
+        ),
+        (
+            fields::LIBRARY_VERSION.to_string(),
+            libhoney::Value::String(version),
+        ),
+    ]
+}
 
 /// Telemetry capability that publishes Honeycomb events and spans to some backend
 #[derive(Debug)]
 pub struct HoneycombTelemetry<R> {
+    state: Arc<TelemetryState<R>>,
+    // the thread deciding tail-sampled traces which expire while no new data is recorded
+    sweeper: Once,
+}
+
+// shared with the sweeper thread, which holds it weakly so that it exits once the telemetry is
+// dropped
+#[derive(Debug)]
+struct TelemetryState<R> {
     reporter: R,
-    sample_rate: Option<u32>,
+    sampler: Arc<dyn Sampler>,
+    error_handling: ErrorHandling,
+    tail_sampling: Option<TailSampling>,
+    redacted_fields: Option<Arc<RedactedFields>>,
+    key_mapping: Option<KeyMapping>,
+    max_field_len: usize,
+    escape_control_chars: bool,
+    max_error_sources: usize,
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    max_flatten_depth: usize,
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    nest_values: bool,
+    force_sample_field: Option<&'static str>,
+    clock: Box<dyn Clock>,
+    global_fields: HashMap<String, libhoney::Value>,
+    max_fields: Option<usize>,
+    field_converters: Option<Arc<HashMap<String, FieldConverter>>>,
+    field_types: Option<Arc<HashMap<String, FieldType>>>,
+    sampling_stats: SamplingStats,
+    timestamp_field: Option<(String, TimestampFormat)>,
+    min_level: Option<tracing::Level>,
+    min_span_duration: Option<Duration>,
+    track_busy_time: bool,
+    status_mapping: Option<StatusMapping>,
+    message_field: Option<String>,
+    source_location: bool,
+    // `library.name` and `library.version`, built once rather than per report
+    library: [(String, libhoney::Value); 2],
+    trace_id_fields: bool,
+    trace_start: bool,
 }
 
-impl<R: Reporter> HoneycombTelemetry<R> {
-    pub(crate) fn new(reporter: R, sample_rate: Option<u32>) -> Self {
-        HoneycombTelemetry {
+impl<R: RichReporter> HoneycombTelemetry<R> {
+    pub(crate) fn new(reporter: R, sampler: Box<dyn Sampler>) -> Self {
+        let state = TelemetryState {
             reporter,
-            sample_rate,
+            sampler: Arc::from(sampler),
+            error_handling: ErrorHandling::default(),
+            tail_sampling: None,
+            redacted_fields: None,
+            key_mapping: None,
+            max_field_len: DEFAULT_MAX_FIELD_LEN,
+            escape_control_chars: false,
+            max_error_sources: DEFAULT_MAX_ERROR_SOURCES,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            max_flatten_depth: crate::visitor::DEFAULT_MAX_FLATTEN_DEPTH,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            nest_values: false,
+            force_sample_field: None,
+            clock: Box::new(SystemClock),
+            global_fields: HashMap::new(),
+            max_fields: None,
+            field_converters: None,
+            field_types: None,
+            sampling_stats: SamplingStats::new(),
+            timestamp_field: None,
+            min_level: None,
+            min_span_duration: None,
+            track_busy_time: false,
+            status_mapping: None,
+            message_field: None,
+            source_location: false,
+            library: library_fields(
+                fields::DEFAULT_LIBRARY_NAME.to_string(),
+                fields::DEFAULT_LIBRARY_VERSION.to_string(),
+            ),
+            trace_id_fields: true,
+            trace_start: false,
+        };
+        HoneycombTelemetry {
+            state: Arc::new(state),
+            sweeper: Once::new(),
+        }
+    }
+
+    // the telemetry is only configured before it is shared with the sweeper thread
+    fn state_mut(&mut self) -> &mut TelemetryState<R> {
+        Arc::get_mut(&mut self.state).expect("configured before use")
+    }
+
+    pub(crate) fn with_error_handling(mut self, error_handling: ErrorHandling) -> Self {
+        self.state_mut().error_handling = error_handling;
+        self
+    }
+
+    pub(crate) fn with_tail_sampling(mut self, tail_sampling: Option<TailSampling>) -> Self {
+        self.state_mut().tail_sampling = tail_sampling;
+        self
+    }
+
+    pub(crate) fn with_redacted_fields(mut self, redacted_fields: Option<RedactedFields>) -> Self {
+        self.state_mut().redacted_fields = redacted_fields.map(Arc::new);
+        self
+    }
+
+    pub(crate) fn with_key_mapping(mut self, key_mapping: Option<KeyMapping>) -> Self {
+        self.state_mut().key_mapping = key_mapping;
+        self
+    }
+
+    pub(crate) fn with_max_field_len(mut self, max_field_len: usize) -> Self {
+        self.state_mut().max_field_len = max_field_len;
+        self
+    }
+
+    pub(crate) fn with_escape_control_chars(mut self, escape_control_chars: bool) -> Self {
+        self.state_mut().escape_control_chars = escape_control_chars;
+        self
+    }
+
+    pub(crate) fn with_max_error_sources(mut self, max_error_sources: usize) -> Self {
+        self.state_mut().max_error_sources = max_error_sources;
+        self
+    }
+
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    pub(crate) fn with_max_flatten_depth(mut self, max_flatten_depth: usize) -> Self {
+        self.state_mut().max_flatten_depth = max_flatten_depth;
+        self
+    }
+
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    pub(crate) fn with_nested_values(mut self, nest_values: bool) -> Self {
+        self.state_mut().nest_values = nest_values;
+        self
+    }
+
+    pub(crate) fn with_force_sample_field(
+        mut self,
+        force_sample_field: Option<&'static str>,
+    ) -> Self {
+        self.state_mut().force_sample_field = force_sample_field;
+        self
+    }
+
+    pub(crate) fn with_clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.state_mut().clock = clock;
+        self
+    }
+
+    pub(crate) fn with_global_fields(
+        mut self,
+        global_fields: HashMap<String, libhoney::Value>,
+    ) -> Self {
+        self.state_mut().global_fields = global_fields;
+        self
+    }
+
+    pub(crate) fn with_max_fields(mut self, max_fields: Option<usize>) -> Self {
+        self.state_mut().max_fields = max_fields;
+        self
+    }
+
+    pub(crate) fn with_field_converters(
+        mut self,
+        field_converters: HashMap<String, FieldConverter>,
+    ) -> Self {
+        self.state_mut().field_converters = if field_converters.is_empty() {
+            None
+        } else {
+            Some(Arc::new(field_converters))
+        };
+        self
+    }
+
+    pub(crate) fn with_field_types(mut self, field_types: HashMap<String, FieldType>) -> Self {
+        self.state_mut().field_types = if field_types.is_empty() {
+            None
+        } else {
+            Some(Arc::new(field_types))
+        };
+        self
+    }
+
+    pub(crate) fn with_sampling_stats(mut self, sampling_stats: SamplingStats) -> Self {
+        self.state_mut().sampling_stats = sampling_stats;
+        self
+    }
+
+    pub(crate) fn with_timestamp_field(
+        mut self,
+        timestamp_field: Option<(String, TimestampFormat)>,
+    ) -> Self {
+        self.state_mut().timestamp_field = timestamp_field;
+        self
+    }
+
+    pub(crate) fn with_min_level(mut self, min_level: Option<tracing::Level>) -> Self {
+        self.state_mut().min_level = min_level;
+        self
+    }
+
+    pub(crate) fn with_min_span_duration(mut self, min_span_duration: Option<Duration>) -> Self {
+        self.state_mut().min_span_duration = min_span_duration;
+        self
+    }
+
+    pub(crate) fn with_busy_time(mut self, track_busy_time: bool) -> Self {
+        self.state_mut().track_busy_time = track_busy_time;
+        self
+    }
+
+    pub(crate) fn with_status_mapping(mut self, status_mapping: Option<StatusMapping>) -> Self {
+        self.state_mut().status_mapping = status_mapping;
+        self
+    }
+
+    pub(crate) fn with_message_field(mut self, message_field: Option<String>) -> Self {
+        self.state_mut().message_field = message_field;
+        self
+    }
+
+    pub(crate) fn with_source_location(mut self, source_location: bool) -> Self {
+        self.state_mut().source_location = source_location;
+        self
+    }
+
+    pub(crate) fn with_library(mut self, (name, version): (String, String)) -> Self {
+        self.state_mut().library = library_fields(name, version);
+        self
+    }
+
+    pub(crate) fn with_trace_id_fields(mut self, trace_id_fields: bool) -> Self {
+        self.state_mut().trace_id_fields = trace_id_fields;
+        self
+    }
+
+    pub(crate) fn with_trace_start(mut self, trace_start: bool) -> Self {
+        if trace_start {
+            crate::trace_start::enable();
+        }
+        self.state_mut().trace_start = trace_start;
+        self
+    }
+
+    /// The reporter spans and events are reported to.
+    pub fn reporter(&self) -> &R {
+        &self.state.reporter
+    }
+
+    /// Counts of the spans and events kept and dropped by sampling.
+    pub fn sampling_stats(&self) -> &SamplingStats {
+        &self.state.sampling_stats
+    }
+
+    /// Transmits any data buffered by the reporter.
+    ///
+    /// With tail sampling, traces still pending are decided first, from the data recorded so
+    /// far.
+    pub fn flush(&self) -> Result<(), ReportError> {
+        self.state.decide_pending();
+        self.state.reporter.flush()
+    }
+
+    /// Shuts the reporter down, transmitting any buffered data.
+    ///
+    /// With tail sampling, traces still pending are decided first, from the data recorded so
+    /// far.
+    pub fn shutdown(&self) -> Result<(), ReportError> {
+        self.state.decide_pending();
+        self.state.reporter.shutdown()
+    }
+}
+
+impl<R: RichReporter + Send + Sync + 'static> HoneycombTelemetry<R> {
+    // spawns the sweeper on first use, if tail sampling
+    fn start_sweeper(&self) {
+        if self.state.tail_sampling.is_none() {
+            return;
+        }
+
+        self.sweeper.call_once(|| {
+            let state = Arc::downgrade(&self.state);
+            std::thread::spawn(move || {
+                let mut wait = Duration::from_secs(0);
+                loop {
+                    std::thread::sleep(wait.max(MIN_SWEEP_INTERVAL));
+                    match state.upgrade() {
+                        Some(state) => wait = state.decide_expired(),
+                        None => break,
+                    }
+                }
+            });
+        });
+    }
+
+    // decides traces pending for tail sampling while the telemetry is alive, for a
+    // `FlushGuard` to call before flushing the reporter
+    pub(crate) fn pending_decider(&self) -> Arc<dyn Fn() + Send + Sync> {
+        let state = Arc::downgrade(&self.state);
+        Arc::new(move || {
+            if let Some(state) = state.upgrade() {
+                state.decide_pending();
+            }
+        })
+    }
+}
+
+impl<R: RichReporter> TelemetryState<R> {
+    /// Adds the fields reported on every span and event to flattened data.
+    fn complete(
+        &self,
+        mut data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> (HashMap<String, libhoney::Value>, DateTime<Utc>) {
+        // span and event fields take precedence over global fields
+        for (name, value) in &self.global_fields {
+            data.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+        if let Some((name, format)) = &self.timestamp_field {
+            data.insert(name.clone(), format.format(timestamp));
+        }
+        data.extend(self.library.iter().cloned());
+        if !self.trace_id_fields {
+            for name in &[fields::TRACE_ID, fields::SPAN_ID, fields::PARENT_ID] {
+                data.remove(*name);
+            }
+        }
+        (data, timestamp)
+    }
+
+    fn hand_over_span(&self, span: HoneycombSpan) {
+        let flattening = Flattening::new(self, false);
+        if let Err(err) = self.reporter.report_span(span, &flattening) {
+            self.error_handling.handle(err);
+        }
+    }
+
+    fn hand_over_event(&self, event: HoneycombEvent, trace_start: bool) {
+        let flattening = Flattening::new(self, trace_start);
+        if let Err(err) = self.reporter.report_event(event, &flattening) {
+            self.error_handling.handle(err);
+        }
+    }
+
+    /// Returns whether the span closed sooner than `min_span_duration` after it was opened.
+    /// Local root spans are never too short, so that their traces stay complete.
+    fn is_too_short(&self, span: &Span<HoneycombVisitor, SpanId, TraceId>) -> bool {
+        match self.min_span_duration {
+            Some(min_span_duration) if !span.local_root => span
+                .completed_at
+                .duration_since(span.initialized_at)
+                .is_ok_and(|duration| duration < min_span_duration),
+            _ => false,
+        }
+    }
+
+    fn should_report(&self, trace_id: &TraceId, root_name: &str, key: Option<&str>) -> bool {
+        self.sampler.should_sample_keyed(trace_id, root_name, key)
+    }
+
+    /// Returns the value of the sampler's key field recorded on the local root of the trace,
+    /// falling back to the given baggage, if any.
+    fn sample_key<'a>(
+        &self,
+        root_key: Option<&'a str>,
+        baggage: &'a [(String, String)],
+    ) -> Option<&'a str> {
+        let field = self.sampler.key_field()?;
+        root_key.or_else(|| {
+            baggage
+                .iter()
+                .find(|(key, _)| key == field)
+                .map(|(_, value)| value.as_str())
+        })
+    }
+
+    /// Returns whether the trace is forced to be kept, given whether the span or event being
+    /// reported recorded `force_sample_field` as `true`, marking the trace as forced if so.
+    fn is_forced(&self, trace_flag: &TraceFlag, force_sample: bool) -> bool {
+        if self.force_sample_field.is_none() {
+            return false;
+        }
+
+        if force_sample {
+            trace_flag.set();
+        }
+        trace_flag.is_set()
+    }
+
+    fn sample_event(&self, event: Event<HoneycombVisitor, SpanId, TraceId>, trace_start: bool) {
+        if let Some(tail_sampling) = &self.tail_sampling {
+            let (trace_id, root_name) = (event.trace_id.clone(), event.root_name);
+            self.is_forced(&event.trace_flag, event.values.force_sample);
+            let has_error = recorded_error(&event.values, event.meta);
+            let event = Buffered::Event(event, trace_start);
+            let ready = tail_sampling.record(&trace_id, root_name, event, false, has_error);
+            self.decide(ready);
+        } else {
+            let sampled_in = self.is_forced(&event.trace_flag, event.values.force_sample)
+                || self.should_report(
+                    &event.trace_id,
+                    event.root_name,
+                    self.sample_key(event.root_key.as_deref(), &event.baggage),
+                );
+            self.sampling_stats.record_event(sampled_in);
+            if sampled_in {
+                self.hand_over_event(event, trace_start);
+            }
+        }
+    }
+
+    fn event_values(
+        &self,
+        event: Event<HoneycombVisitor, SpanId, TraceId>,
+        trace_start: bool,
+    ) -> (HashMap<String, libhoney::Value>, DateTime<Utc>) {
+        let (meta, root_name) = (event.meta, event.root_name);
+        let (mut data, timestamp) = event_to_values(event, self.key_mapping.as_ref());
+        if trace_start {
+            data.insert(
+                fields::ANNOTATION_TYPE.to_string(),
+                libhoney::Value::from(fields::TRACE_START),
+            );
+            data.insert(fields::NAME.to_string(), libhoney::Value::from(root_name));
+            data.insert(
+                fields::TARGET.to_string(),
+                libhoney::Value::from(TRACE_START_TARGET),
+            );
+        }
+        if let Some(message_field) = &self.message_field {
+            if let Some(message) = data.remove(fields::MESSAGE) {
+                data.insert(message_field.clone(), message);
+            }
+        }
+        if self.source_location {
+            insert_source_location(&mut data, meta);
         }
+        (data, timestamp)
     }
 
-    #[inline]
-    fn report_data(&self, data: HashMap<String, libhoney::Value>, timestamp: DateTime<Utc>) {
-        self.reporter.report_data(data, timestamp);
+    fn span_values(
+        &self,
+        span: Span<HoneycombVisitor, SpanId, TraceId>,
+    ) -> (HashMap<String, libhoney::Value>, DateTime<Utc>) {
+        let meta = span.meta;
+        // judged before key mapping, which may rename the `error` field
+        let status = self
+            .status_mapping
+            .map(|status_mapping| (status_mapping, StatusMapping::failed(&span)));
+        let (mut data, timestamp) = span_to_values(span, self.key_mapping.as_ref());
+        if let Some((status_mapping, failed)) = status {
+            status_mapping.apply(&mut data, failed);
+        }
+        if self.source_location {
+            insert_source_location(&mut data, meta);
+        }
+        (data, timestamp)
     }
 
-    fn should_report(&self, trace_id: &TraceId) -> bool {
-        if let Some(sample_rate) = self.sample_rate {
-            crate::deterministic_sampler::sample(sample_rate, trace_id)
+    fn report_span(&self, span: HoneycombSpan) {
+        if self.is_too_short(&span) {
+            return;
+        }
+        if let Some(tail_sampling) = &self.tail_sampling {
+            let trace_id = span.trace_id.clone();
+            self.is_forced(&span.trace_flag, span.values.force_sample);
+            let (root_name, local_root) = (span.root_name, span.local_root);
+            let has_error = recorded_error(&span.values, span.meta);
+            let span = Buffered::Span(span);
+            let ready = tail_sampling.record(&trace_id, root_name, span, local_root, has_error);
+            self.decide(ready);
         } else {
-            true
+            let sampled_in = self.is_forced(&span.trace_flag, span.values.force_sample)
+                || self.should_report(
+                    &span.trace_id,
+                    span.root_name,
+                    self.sample_key(span.root_key.as_deref(), &span.baggage),
+                );
+            self.sampling_stats.record_span(sampled_in);
+            if sampled_in {
+                self.hand_over_span(span);
+            }
+        }
+    }
+
+    /// Decides traces which have been pending for the tail sampling timeout, returning how long
+    /// until the next pending trace expires.
+    fn decide_expired(&self) -> Duration {
+        match &self.tail_sampling {
+            Some(tail_sampling) => {
+                let (expired, next_expiry) = tail_sampling.take_expired();
+                self.decide(expired);
+                next_expiry.unwrap_or_else(|| tail_sampling.timeout())
+            }
+            None => Duration::from_secs(0),
+        }
+    }
+
+    /// Decides all traces pending for tail sampling.
+    fn decide_pending(&self) {
+        if let Some(tail_sampling) = &self.tail_sampling {
+            self.decide(tail_sampling.drain());
+        }
+    }
+
+    /// Decides whether to report traces buffered for tail sampling.
+    fn decide(&self, ready: Vec<(TraceId, PendingTrace)>) {
+        for (trace_id, trace) in ready {
+            // every item carries the field as recorded on the local root when registered, and the
+            // local root span, which holds the field if recorded later, is buffered last
+            let key = self
+                .sampler
+                .key_field()
+                .and_then(|field| trace.data.iter().rev().find_map(|item| item.key(field)));
+            let forced = trace
+                .data
+                .iter()
+                .any(|item| self.is_forced(item.trace_flag(), false));
+            let sampled_in = forced
+                || trace.has_error
+                || self.should_report(&trace_id, trace.root_name, key.as_deref());
+            for item in trace.data {
+                match item {
+                    Buffered::Span(span) => {
+                        self.sampling_stats.record_span(sampled_in);
+                        if sampled_in {
+                            self.hand_over_span(span);
+                        }
+                    }
+                    Buffered::Event(event, trace_start) => {
+                        self.sampling_stats.record_event(sampled_in);
+                        if sampled_in {
+                            self.hand_over_event(event, trace_start);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: RichReporter> Flatten for TelemetryState<R> {
+    fn span(&self, span: HoneycombSpan) -> Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)> {
+        let mut batch = links_to_values(&span);
+        batch.push(self.span_values(span));
+        batch
+            .into_iter()
+            .map(|(data, timestamp)| self.complete(data, timestamp))
+            .collect()
+    }
+
+    fn event(
+        &self,
+        event: HoneycombEvent,
+        trace_start: bool,
+    ) -> (HashMap<String, libhoney::Value>, DateTime<Utc>) {
+        let (data, timestamp) = self.event_values(event, trace_start);
+        self.complete(data, timestamp)
+    }
+}
+
+/// A span or event buffered for tail sampling, flattened only once its trace is kept
+#[derive(Debug)]
+pub(crate) enum Buffered {
+    Span(HoneycombSpan),
+    // whether the event marks the start of the trace
+    Event(HoneycombEvent, bool),
+}
+
+impl Buffered {
+    /// Returns the value of the given field, recorded on the local root of the trace when
+    /// registered, on the span or event, or in its baggage.
+    fn key(&self, field: &str) -> Option<String> {
+        let (root_key, values, baggage) = match self {
+            Buffered::Span(span) => (&span.root_key, &span.values, &span.baggage),
+            Buffered::Event(event, _) => (&event.root_key, &event.values, &event.baggage),
+        };
+        if let Some(root_key) = root_key {
+            return Some(root_key.to_string());
         }
+        match values.values.get(field) {
+            Some(libhoney::Value::String(value)) => Some(value.clone()),
+            Some(value) => Some(value.to_string()),
+            None => baggage
+                .iter()
+                .find(|(key, _)| key == field)
+                .map(|(_, value)| value.clone()),
+        }
+    }
+
+    fn trace_flag(&self) -> &TraceFlag {
+        match self {
+            Buffered::Span(span) => &span.trace_flag,
+            Buffered::Event(event, _) => &event.trace_flag,
+        }
+    }
+}
+
+impl<R: Reporter + Borrow<LibhoneyReporter>> HoneycombTelemetry<R> {
+    /// Passes `f` the libhoney client spans and events are reported to, e.g. to send a
+    /// one-off event such as a deploy marker with the same config and connection pool:
+    ///
+    /// ```no_run
+    /// # let layer = tracing_honeycomb::Builder::new_libhoney("my-service", libhoney::Config {
+    /// #     options: libhoney::client::Options::default(),
+    /// #     transmission_options: libhoney::transmission::Options::default(),
+    /// # })
+    /// # .build();
+    /// use libhoney::{json, FieldHolder};
+    ///
+    /// layer.telemetry().with_client(|client| {
+    ///     let mut event = client.new_event();
+    ///     event.add_field("deploy.version", json!("1.2.3"));
+    ///     event.send(client)
+    /// })?;
+    /// # Ok::<(), libhoney::Error>(())
+    /// ```
+    ///
+    /// The client's lock is held while `f` runs, blocking spans and events from being reported
+    /// meanwhile, so `f` should not do more than use the client. Data sent via the client
+    /// directly is not sampled nor otherwise processed by the layer.
+    pub fn with_client<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut libhoney::Client<libhoney::transmission::Transmission>) -> T,
+    {
+        let reporter: &LibhoneyReporter = self.state.reporter.borrow();
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut client = reporter.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut client = reporter.lock();
+
+        f(&mut client)
     }
 }
 
-impl<R: Reporter> Telemetry for HoneycombTelemetry<R> {
+impl<R: RichReporter + Send + Sync + 'static> Telemetry for HoneycombTelemetry<R> {
     type Visitor = HoneycombVisitor;
     type TraceId = TraceId;
     type SpanId = SpanId;
 
     fn mk_visitor(&self) -> Self::Visitor {
-        Default::default()
+        HoneycombVisitor {
+            values: Default::default(),
+            redacted_fields: self.state.redacted_fields.clone(),
+            max_field_len: self.state.max_field_len,
+            escape_control_chars: self.state.escape_control_chars,
+            max_error_sources: self.state.max_error_sources,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            max_flatten_depth: self.state.max_flatten_depth,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            nest_values: self.state.nest_values,
+            force_sample_field: self.state.force_sample_field,
+            force_sample: false,
+            max_fields: self.state.max_fields,
+            field_converters: self.state.field_converters.clone(),
+            field_types: self.state.field_types.clone(),
+            sampler: Some(self.state.sampler.clone()),
+        }
+    }
+
+    fn now(&self) -> SystemTime {
+        self.state.clock.now()
+    }
+
+    fn track_busy_time(&self) -> bool {
+        self.state.track_busy_time
+    }
+
+    fn event_enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        #[cfg(feature = "use_tracing_reporter")]
+        if crate::tracing_reporter::is_reporting() {
+            return false;
+        }
+
+        // more verbose levels compare greater
+        self.state
+            .min_level
+            .is_none_or(|min_level| *metadata.level() <= min_level)
     }
 
     fn report_span(&self, span: Span<Self::Visitor, Self::SpanId, Self::TraceId>) {
-        if self.should_report(&span.trace_id) {
-            let (data, timestamp) = span_to_values(span);
-            self.report_data(data, timestamp);
+        // as with events, see `event_enabled`
+        #[cfg(feature = "use_tracing_reporter")]
+        if crate::tracing_reporter::is_reporting() {
+            return;
         }
+
+        self.start_sweeper();
+        self.state.report_span(span);
     }
 
     fn report_event(&self, event: Event<Self::Visitor, Self::SpanId, Self::TraceId>) {
-        if self.should_report(&event.trace_id) {
-            let (data, timestamp) = event_to_values(event);
-            self.report_data(data, timestamp);
+        self.start_sweeper();
+        self.state.sample_event(event, false);
+    }
+
+    // the event carries the fields of the root span, as of when the start was reported
+    fn report_trace_start(
+        &self,
+        event: Event<Self::Visitor, Self::SpanId, Self::TraceId>,
+        root: &Self::Visitor,
+    ) {
+        if self.state.trace_start {
+            let mut event = event;
+            event.values = root.clone();
+            self.start_sweeper();
+            self.state.sample_event(event, true);
+        }
+    }
+
+    fn root_key(&self, root: &Self::Visitor) -> Option<String> {
+        let field = self.state.sampler.key_field()?;
+        match root.values.get(field)? {
+            libhoney::Value::String(value) => Some(value.clone()),
+            value => Some(value.to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reporter::TestReporter;
+    use libhoney::json;
+    use std::str::FromStr;
+    use tracing_distributed::TelemetryLayer;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn force_sample_field_keeps_trace() {
+        let reporter = TestReporter::new();
+        let telemetry = HoneycombTelemetry::new(reporter.clone(), Box::new(|_: &TraceId| false))
+            .with_force_sample_field(Some("force_sample"));
+        let layer = TelemetryLayer::new("test", telemetry, |tracing_id| SpanId { tracing_id });
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!("dropped");
+            tracing::info!(force_sample = true, "forced");
+            tracing::info!("kept");
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 3);
+        assert_eq!(recorded[0].get("force_sample"), Some(&json!(true)));
+        assert_eq!(recorded[2].get("name"), Some(&json!("root")));
+    }
+
+    #[test]
+    fn service_name_field_overrides_layer_service_name() {
+        let reporter = TestReporter::new();
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("default", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info_span!("charge", service.name = "billing").in_scope(|| {
+                tracing::info!("inherits nothing");
+            });
+        });
+
+        let service_names: Vec<_> = reporter
+            .recorded()
+            .iter()
+            .map(|data| data["service_name"].clone())
+            .collect();
+        assert_eq!(
+            service_names,
+            vec![json!("default"), json!("billing"), json!("default")]
+        );
+    }
+
+    #[test]
+    fn reports_parent_span_id() {
+        let reporter = TestReporter::new();
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("parent");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info_span!("child").in_scope(|| {});
+        });
+
+        let recorded = reporter.recorded();
+        let (child, parent) = (&recorded[0], &recorded[1]);
+        assert_eq!(child["name"], json!("child"));
+        assert_eq!(child["trace.parent_id"], parent["trace.span_id"]);
+        assert_eq!(parent.get("trace.parent_id"), None);
+    }
+
+    #[test]
+    fn timestamps_spans_with_clock() {
+        use crate::ManualClock;
+        use std::time::Duration;
+
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000));
+        let reporter = TestReporter::new();
+        let telemetry = HoneycombTelemetry::new(reporter.clone(), Box::new(crate::AlwaysSampler))
+            .with_clock(Box::new(clock.clone()));
+        let layer = TelemetryLayer::new("test", telemetry, |tracing_id| SpanId { tracing_id });
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            clock.advance(Duration::from_millis(250));
+        });
+
+        assert_eq!(reporter.recorded()[0]["duration_ms"], json!(250.0));
+    }
+
+    #[test]
+    fn reports_timestamp_field() {
+        use crate::{ManualClock, TimestampFormat};
+        use std::time::Duration;
+
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_millis(1_000_250));
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_clock(clock)
+            .with_timestamp_field("time_ms", TimestampFormat::EpochMillis)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", time_ms = "recorded");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!("event");
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 2);
+        for data in &recorded {
+            assert_eq!(data["time_ms"], json!(1_000_250));
+        }
+    }
+
+    #[test]
+    fn merges_global_fields() {
+        let reporter = TestReporter::new();
+        let mut global_fields = HashMap::new();
+        global_fields.insert("region".to_string(), json!("eu-north-1"));
+        global_fields.insert("host".to_string(), json!("global"));
+        let telemetry = HoneycombTelemetry::new(reporter.clone(), Box::new(crate::AlwaysSampler))
+            .with_global_fields(global_fields);
+        let layer = TelemetryLayer::new("test", telemetry, |tracing_id| SpanId { tracing_id });
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", host = "span");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+        });
+
+        let span = &reporter.recorded()[0];
+        assert_eq!(span["region"], json!("eu-north-1"));
+        assert_eq!(span["host"], json!("span"));
+    }
+
+    #[test]
+    fn marks_events_as_span_events() {
+        let reporter = TestReporter::new();
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!("event");
+        });
+
+        let recorded = reporter.recorded();
+        let (event, span) = (&recorded[0], &recorded[1]);
+        assert_eq!(event["meta.annotation_type"], json!("span_event"));
+        assert_eq!(span.get("meta.annotation_type"), None);
+    }
+
+    #[test]
+    fn builds_layer_for_any_reporter() {
+        let reporter = TestReporter::new();
+        let mut global_fields = HashMap::new();
+        global_fields.insert("region".to_string(), json!("eu-north-1"));
+        let layer = crate::Builder::new("builder", reporter.clone())
+            .with_global_fields(global_fields)
+            .with_redacted_fields(vec!["password"])
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", password = "hunter2");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+        });
+
+        let span = &reporter.recorded()[0];
+        assert_eq!(span["service_name"], json!("builder"));
+        assert_eq!(span["region"], json!("eu-north-1"));
+        assert_eq!(span["password"], json!(crate::REDACTED));
+    }
+
+    #[test]
+    fn caps_recorded_fields() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_max_fields(2)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span =
+                tracing::info_span!("root", d = 4, b = 2, service.name = "svc", a = 1, c = 3);
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!(a = 1);
+        });
+
+        let recorded = reporter.recorded();
+        let (event, span) = (&recorded[0], &recorded[1]);
+        assert_eq!(event.get("meta.fields_dropped"), None);
+        assert_eq!(span["a"], json!(1));
+        assert_eq!(span["b"], json!(2));
+        assert_eq!(span.get("c"), None);
+        assert_eq!(span.get("d"), None);
+        assert_eq!(span["service_name"], json!("svc"));
+        assert_eq!(span["name"], json!("root"));
+        assert_eq!(span["meta.fields_dropped"], json!(2));
+    }
+
+    #[test]
+    fn converts_debug_recorded_fields() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_field_converter("elapsed", crate::duration_as_ms)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            let elapsed = std::time::Duration::from_millis(1500);
+            tracing::info!(elapsed = ?elapsed, other = ?elapsed);
+        });
+
+        let event = &reporter.recorded()[0];
+        assert_eq!(event["elapsed"], json!(1500.0));
+        assert_eq!(event["other"], json!("1.5s"));
+    }
+
+    #[test]
+    fn coerces_fields_to_configured_types() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_field_type("user_id", crate::FieldType::String)
+            .with_field_type("retries", crate::FieldType::Int)
+            .with_field_type("password", crate::FieldType::Int)
+            .with_redacted_fields(vec!["password"])
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!(user_id = 42, retries = "3", password = "hunter2");
+            tracing::info!(user_id = "abc", retries = "many");
+        });
+
+        let recorded = reporter.recorded();
+        let (first, second) = (&recorded[0], &recorded[1]);
+        assert_eq!(first["user_id"], json!("42"));
+        assert_eq!(first["retries"], json!(3));
+        assert_eq!(first["password"], json!(crate::REDACTED));
+        assert!(!first.contains_key("password.coercion_error"));
+        assert_eq!(second["user_id"], json!("abc"));
+        assert_eq!(second["retries"], json!("many"));
+        assert_eq!(
+            second["retries.coercion_error"],
+            json!("cannot coerce \"many\" to Int")
+        );
+    }
+
+    #[test]
+    fn counts_sampled_spans_and_events() {
+        let reporter = TestReporter::new();
+        let kept = TraceId::new();
+        let sampler = {
+            let kept = kept.clone();
+            move |trace_id: &TraceId| *trace_id == kept
+        };
+        let stats = crate::SamplingStats::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_sampler(sampler)
+            .with_sampling_stats(stats.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for trace_id in [kept, TraceId::new(), TraceId::new()] {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(trace_id, None).unwrap();
+                tracing::info!("event");
+            }
+        });
+
+        assert_eq!(reporter.recorded().len(), 2);
+        assert_eq!(stats.spans_sampled_in(), 1);
+        assert_eq!(stats.spans_sampled_out(), 2);
+        assert_eq!(stats.events_sampled_in(), 1);
+        assert_eq!(stats.events_sampled_out(), 2);
+    }
+
+    #[test]
+    fn samples_on_root_field() {
+        // at a rate of 2, the key "trace-1" is kept and this trace id dropped, see
+        // deterministic_sampler
+        let dropped = TraceId::from("80f198ee56343ba864fe8b2a57d3eff7");
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_sampler(crate::FieldSampler::new("customer_id", 2))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for key in [Some("trace-1"), None] {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(dropped.clone(), None).unwrap();
+                if let Some(key) = key {
+                    crate::set_baggage("customer_id", key).unwrap();
+                }
+                tracing::info_span!("child").in_scope(|| tracing::info!("event"));
+            }
+        });
+
+        // only the first trace is kept; baggage is reported on its spans, not its event
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 3);
+        let span_keys = recorded
+            .iter()
+            .filter(|data| data.contains_key(fields::DURATION_MS))
+            .map(|data| data.get("customer_id"))
+            .collect::<Vec<_>>();
+        assert_eq!(span_keys, [Some(&json!("trace-1")); 2]);
+
+        // a field recorded on the root span by the time it is registered keeps the whole trace,
+        // without baggage
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_sampler(crate::FieldSampler::new("customer_id", 2))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", customer_id = "trace-1");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(dropped.clone(), None).unwrap();
+            tracing::info_span!("child").in_scope(|| tracing::info!("event"));
+        });
+
+        assert_eq!(reporter.recorded().len(), 3);
+
+        // with tail sampling, a field recorded on the root span after registering it is used too
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_sampler(crate::FieldSampler::new("customer_id", 2))
+            .with_tail_sampling(std::time::Duration::from_secs(60), 16)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", customer_id = tracing::field::Empty);
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(dropped.clone(), None).unwrap();
+            span.record("customer_id", "trace-1");
+            tracing::info!("event");
+        });
+
+        assert_eq!(reporter.recorded().len(), 2);
+    }
+
+    #[test]
+    fn reports_span_status() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_status_mapping(crate::StatusMapping::StatusCode)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info_span!("failed").in_scope(|| tracing::error!("failed"));
+            tracing::info_span!("ok").in_scope(|| tracing::warn!("not an error"));
+            tracing::info_span!("error_field", error = "timed out").in_scope(|| {});
+        });
+
+        let recorded = reporter.recorded();
+        let span = |name: &str| {
+            recorded
+                .iter()
+                .find(|data| {
+                    data[fields::NAME] == json!(name) && data.contains_key(fields::SPAN_ID)
+                })
+                .unwrap()
+        };
+        assert_eq!(span("failed")[fields::ERROR], json!(true));
+        assert_eq!(span("failed")[fields::STATUS_CODE], json!(2));
+        assert!(!span("ok").contains_key(fields::ERROR));
+        assert_eq!(span("ok")[fields::STATUS_CODE], json!(0));
+        assert_eq!(span("error_field")[fields::ERROR], json!("timed out"));
+        assert_eq!(span("error_field")[fields::STATUS_CODE], json!(2));
+        // only events directly within a span count
+        assert!(!span("root").contains_key(fields::ERROR));
+        assert_eq!(span("root")[fields::STATUS_CODE], json!(0));
+    }
+
+    #[test]
+    fn reports_span_status_of_renamed_error_field() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_status_mapping(crate::StatusMapping::StatusCode)
+            .with_key_mapping(|key| format!("app.{}", key))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", error = "timed out");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+        });
+
+        let recorded = reporter.recorded();
+        let span = recorded
+            .iter()
+            .find(|data| data.contains_key(fields::SPAN_ID))
+            .unwrap();
+        assert_eq!(span["app.error"], json!("timed out"));
+        assert_eq!(span[fields::ERROR], json!(true));
+        assert_eq!(span[fields::STATUS_CODE], json!(2));
+    }
+
+    #[test]
+    fn routes_by_dataset_field_under_key_mapping() {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = sent.clone();
+        let reporter = crate::BatchJsonReporter::new(1, move |dataset: Option<&str>, body| {
+            let body: libhoney::Value = serde_json::from_str(&body).unwrap();
+            sink.lock()
+                .unwrap()
+                .push((dataset.map(str::to_string), body));
+            Ok(())
+        });
+        let layer = crate::Builder::new("test", reporter)
+            .with_key_mapping(|key| format!("app.{}", key))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", honeycomb.dataset = "tenant-a", user = "alice");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+        });
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0.as_deref(), Some("tenant-a"));
+        let data = &sent[0].1[0]["data"];
+        assert_eq!(data["app.user"], json!("alice"));
+        assert!(data.get(crate::DATASET_FIELD).is_none());
+        assert!(data.get("app.honeycomb.dataset").is_none());
+    }
+
+    #[test]
+    fn tail_samples_trace_with_renamed_error_field() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_sampler(|_: &TraceId| false)
+            .with_tail_sampling(Duration::from_secs(60), 16)
+            .with_key_mapping(|key| format!("app.{}", key))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!(error = true, "failed");
+        });
+
+        assert_eq!(reporter.recorded().len(), 2);
+    }
+
+    #[test]
+    fn decides_expired_traces_without_new_data() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_sampler(|_: &TraceId| false)
+            .with_tail_sampling(Duration::from_millis(50), 16)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::error!("failed");
+
+            // the root span is still open, and nothing else is recorded meanwhile
+            std::thread::sleep(Duration::from_millis(500));
+            assert_eq!(reporter.recorded().len(), 1);
+        });
+    }
+
+    #[test]
+    fn flush_decides_pending_traces() {
+        type Layer = TelemetryLayer<HoneycombTelemetry<TestReporter>, SpanId, TraceId>;
+
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_sampler(|_: &TraceId| false)
+            .with_tail_sampling(Duration::from_secs(60), 16)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::error!("failed");
+            assert!(reporter.recorded().is_empty());
+
+            tracing::dispatcher::get_default(|dispatch| {
+                let layer = dispatch.downcast_ref::<Layer>().unwrap();
+                layer.telemetry().flush().unwrap();
+            });
+            assert_eq!(reporter.recorded().len(), 1);
+        });
+    }
+
+    #[test]
+    fn flush_guard_decides_pending_traces() {
+        let reporter = TestReporter::new();
+        let (layer, guard) = crate::Builder::new("test", reporter.clone())
+            .with_sampler(|_: &TraceId| false)
+            .with_tail_sampling(Duration::from_secs(60), 16)
+            .build_with_flush_guard();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::error!("failed");
+            assert!(reporter.recorded().is_empty());
+
+            // the root span is still open, so the trace is only decided by the guard
+            guard.flush().unwrap();
+            assert_eq!(reporter.recorded().len(), 1);
+        });
+    }
+
+    #[test]
+    fn drops_events_below_min_level() {
+        let reporter = TestReporter::new();
+        let stats = crate::SamplingStats::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_min_level(tracing::Level::INFO)
+            .with_sampling_stats(stats.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::trace!("trace");
+            tracing::debug!("debug");
+            tracing::info!("info");
+            tracing::error!("error");
+        });
+
+        let recorded = reporter.recorded();
+        let levels: Vec<_> = recorded
+            .iter()
+            .map(|data| data[fields::LEVEL].as_str().unwrap())
+            .collect();
+        assert_eq!(levels, vec!["INFO", "ERROR", "DEBUG"]);
+        assert_eq!(stats.events_sampled_in(), 2);
+        assert_eq!(stats.events_sampled_out(), 0);
+    }
+
+    #[test]
+    fn reports_event_message() {
+        let builder = |reporter: &TestReporter| crate::Builder::new("test", reporter.clone());
+        let (reporter, renamed) = (TestReporter::new(), TestReporter::new());
+        let layers = [
+            (builder(&reporter).build(), &reporter, fields::MESSAGE),
+            (
+                builder(&renamed).with_message_field("body").build(),
+                &renamed,
+                "body",
+            ),
+        ];
+
+        for (layer, reporter, message_field) in layers {
+            let subscriber = tracing_subscriber::registry().with(layer);
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+                let x = 42;
+                tracing::info!("hello {}", x);
+            });
+
+            let event = &reporter.recorded()[0];
+            assert_eq!(event[message_field], json!("hello 42"));
+        }
+        assert!(!renamed.recorded()[0].contains_key(fields::MESSAGE));
+    }
+
+    #[test]
+    fn reports_source_location() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_source_location(true)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        let mut lines = Vec::new();
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            lines.push(line!() - 1);
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!("event");
+            lines.push(line!() - 1);
+        });
+
+        let recorded = reporter.recorded();
+        let (event, root) = (&recorded[0], &recorded[1]);
+        for (data, line) in [(event, lines[1]), (root, lines[0])] {
+            assert_eq!(data[fields::CODE_FILEPATH], json!(file!()));
+            assert_eq!(data[fields::CODE_LINENO], json!(line));
+            assert_eq!(data[fields::CODE_NAMESPACE], json!(module_path!()));
+        }
+    }
+
+    #[cfg(feature = "deterministic_span_ids")]
+    #[test]
+    fn reports_deterministic_span_ids() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_deterministic_span_ids(true)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..2 {
+                let root = tracing::info_span!("root");
+                let _guard = root.enter();
+                crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+                tracing::info_span!("child").in_scope(|| {});
+            }
+        });
+
+        let ids: Vec<_> = reporter
+            .recorded()
+            .into_iter()
+            .map(|data| {
+                (
+                    data[fields::SPAN_ID].clone(),
+                    data.get(fields::PARENT_ID).cloned(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                (json!("2"), Some(json!("1"))),
+                (json!("1"), None),
+                (json!("4"), Some(json!("3"))),
+                (json!("3"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_trace_start_events() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_trace_start_events(true)
+            .with_min_level(tracing::Level::WARN)
+            .with_sampler(|trace_id: &TraceId| trace_id.to_string() != "dropped")
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", user = "alice");
+            let _guard = span.enter();
+            let route = vec![("http.route".to_string(), json!("/users"))];
+            crate::register_dist_tracing_root_with_fields(
+                TraceId::from("kept"),
+                None,
+                route.into_iter().collect(),
+            )
+            .unwrap();
+
+            let span = tracing::info_span!("request");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::from("dropped"), None).unwrap();
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 2);
+        let start = &recorded[0];
+        assert_eq!(start[fields::ANNOTATION_TYPE], json!(fields::TRACE_START));
+        assert_eq!(start[fields::NAME], json!("request"));
+        assert_eq!(start[fields::TRACE_ID], json!("kept"));
+        assert_eq!(start["user"], json!("alice"));
+        assert_eq!(start["http.route"], json!("/users"));
+        assert_eq!(start[fields::TARGET], json!(TRACE_START_TARGET));
+        assert!(recorded[1].contains_key(fields::SPAN_ID));
+    }
+
+    #[test]
+    fn tees_to_independently_sampled_telemetry() {
+        let (new, old) = (TestReporter::new(), TestReporter::new());
+        let layer = crate::new_honeycomb_telemetry_layer_with_tee(
+            "test",
+            crate::Builder::new("test", new.clone()).build_telemetry(),
+            crate::Builder::new("test", old.clone())
+                .with_sampler(|trace_id: &TraceId| trace_id.to_string() == "kept")
+                .with_min_level(tracing::Level::WARN)
+                .build_telemetry(),
+        );
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for trace_id in ["kept", "dropped"].iter() {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(TraceId::from(*trace_id), None).unwrap();
+                tracing::info!("info");
+                tracing::warn!("warn");
+            }
+        });
+
+        assert_eq!(new.recorded().len(), 6);
+        let old = old.recorded();
+        assert_eq!(old.len(), 2);
+        assert_eq!(old[0][fields::LEVEL], json!("WARN"));
+        assert_eq!(old[1][fields::TRACE_ID], json!("kept"));
+    }
+
+    #[test]
+    fn drops_spans_shorter_than_min_duration() {
+        let reporter = TestReporter::new();
+        let clock = crate::ManualClock::new(SystemTime::UNIX_EPOCH);
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_clock(clock.clone())
+            .with_min_span_duration(Duration::from_millis(1))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::info_span!("root");
+            let _guard = root.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info_span!("short").in_scope(|| {
+                clock.advance(Duration::from_micros(999));
+                tracing::info!("event");
+            });
+            tracing::info_span!("exact").in_scope(|| clock.advance(Duration::from_millis(1)));
+        });
+
+        let names: Vec<_> = reporter
+            .recorded()
+            .into_iter()
+            .map(|data| data[fields::NAME].clone())
+            .collect();
+        assert_eq!(names.len(), 3);
+        assert!(names[0].as_str().unwrap().starts_with("event "));
+        assert_eq!(names[1..], [json!("exact"), json!("root")]);
+    }
+
+    #[test]
+    fn reports_busy_and_idle_time_if_enabled() {
+        for track_busy_time in [false, true] {
+            let reporter = TestReporter::new();
+            let clock = crate::ManualClock::new(SystemTime::UNIX_EPOCH);
+            let layer = crate::Builder::new("test", reporter.clone())
+                .with_clock(clock.clone())
+                .with_busy_time(track_busy_time)
+                .build();
+            let subscriber = tracing_subscriber::registry().with(layer);
+
+            tracing::subscriber::with_default(subscriber, || {
+                let root = tracing::info_span!("root");
+                let _guard = root.enter();
+                crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+
+                let work = tracing::info_span!("work", busy_ms = "recorded");
+                clock.advance(Duration::from_millis(5));
+                work.in_scope(|| clock.advance(Duration::from_millis(2)));
+                clock.advance(Duration::from_millis(3));
+                // re-entered while entered, counted once
+                let _outer = work.enter();
+                let _inner = work.enter();
+                clock.advance(Duration::from_millis(1));
+            });
+
+            let work = &reporter.recorded()[0];
+            assert_eq!(work[fields::NAME], json!("work"));
+            assert_eq!(work[fields::DURATION_MS], json!(11.0));
+            if track_busy_time {
+                assert_eq!(work[fields::BUSY_MS], json!(3.0));
+                assert_eq!(work[fields::IDLE_MS], json!(8.0));
+            } else {
+                // not reserved, so kept as recorded
+                assert_eq!(work[fields::BUSY_MS], json!("recorded"));
+                assert!(!work.contains_key(fields::IDLE_MS));
+            }
+        }
+    }
+
+    #[test]
+    fn with_client_passes_reporter_client() {
+        let config = libhoney::Config {
+            options: libhoney::client::Options {
+                api_host: "http://127.0.0.1:1".to_string(),
+                ..libhoney::client::Options::default()
+            },
+            transmission_options: libhoney::transmission::Options::default(),
+        };
+        let reporter = Arc::new(crate::new_libhoney_reporter(config));
+        let layer = crate::Builder::new("test", reporter.clone()).build();
+
+        let passed = layer
+            .telemetry()
+            .with_client(|client| client as *const libhoney::Client<_>);
+        #[cfg(not(feature = "use_parking_lot"))]
+        let client = reporter.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let client = reporter.lock();
+        assert!(std::ptr::eq(passed, &*client));
+    }
+
+    #[test]
+    fn shares_reporter_between_layers() {
+        let inner = TestReporter::new();
+        let reporter: Arc<dyn Reporter + Send + Sync> = Arc::new(inner.clone());
+        let (spans, guard) =
+            crate::Builder::new("spans", reporter.clone()).build_with_flush_guard();
+        let logs = crate::Builder::new("logs", reporter).build();
+
+        let root = || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+        };
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(spans), root);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(logs), root);
+        guard.flush().unwrap();
+
+        let services: Vec<_> = inner
+            .recorded()
+            .into_iter()
+            .map(|data| data[fields::SERVICE_NAME].clone())
+            .collect();
+        assert_eq!(services, vec![json!("spans"), json!("logs")]);
+    }
+
+    #[test]
+    fn reports_library_name_and_version() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone()).build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!("event");
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 2);
+        for data in recorded {
+            assert_eq!(data[fields::LIBRARY_NAME], json!("tracing-honeycomb"));
+            assert_eq!(
+                data[fields::LIBRARY_VERSION],
+                json!(env!("CARGO_PKG_VERSION"))
+            );
+        }
+
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_library("my-wrapper", "1.2.3")
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", library.name = "user");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded[0][fields::LIBRARY_NAME], json!("my-wrapper"));
+        assert_eq!(recorded[0][fields::LIBRARY_VERSION], json!("1.2.3"));
+        assert_eq!(recorded[0]["tracing.library.name"], json!("user"));
+    }
+
+    #[test]
+    fn reports_fields_recorded_after_span_creation() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone()).build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span =
+                tracing::info_span!("root", user_id = tracing::field::Empty, status = "pending");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            span.record("user_id", 42);
+            span.record("status", "done");
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0]["user_id"], json!(42));
+        assert_eq!(recorded[0]["status"], json!("done"));
+    }
+
+    #[test]
+    fn registers_root_with_fields() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_redacted_fields(vec!["authorization"])
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            let root_fields = vec![
+                ("http.method".to_string(), json!("GET")),
+                ("authorization".to_string(), json!("secret")),
+                (fields::NAME.to_string(), json!("request")),
+            ];
+            crate::register_dist_tracing_root_with_fields(
+                TraceId::new(),
+                None,
+                root_fields.into_iter().collect(),
+            )
+            .unwrap();
+            tracing::info_span!("child").in_scope(|| {});
+        });
+
+        let recorded = reporter.recorded();
+        let (child, root) = (&recorded[0], &recorded[1]);
+        assert_eq!(root["http.method"], json!("GET"));
+        assert_eq!(root["authorization"], json!(crate::REDACTED));
+        assert_eq!(root["tracing.name"], json!("request"));
+        assert_eq!(root[fields::NAME], json!("root"));
+        assert!(!child.contains_key("http.method"));
+    }
+
+    #[test]
+    fn reports_whether_current_trace_is_sampled() {
+        assert_eq!(crate::is_current_trace_sampled(), None);
+
+        // "trace-1" is kept at a rate of 2 and dropped at a rate of 10, see
+        // `deterministic_sampler::test::samples_fixed_trace_ids`
+        for (sample_rate, sampled) in [(2, true), (10, false)] {
+            let layer = crate::Builder::new("test", TestReporter::new())
+                .with_trace_sampling(sample_rate)
+                .build();
+            let subscriber = tracing_subscriber::registry().with(layer);
+
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                assert_eq!(crate::is_current_trace_sampled(), None);
+                crate::register_dist_tracing_root(TraceId::from("trace-1"), None).unwrap();
+                assert_eq!(crate::is_current_trace_sampled(), Some(sampled));
+            });
+        }
+    }
+
+    #[test]
+    fn reports_all_traces_at_rate_0_or_1_or_without_sampling() {
+        // "trace-1" is dropped at a rate of 10, see
+        // `deterministic_sampler::test::samples_fixed_trace_ids`
+        for sample_rate in [None, Some(0), Some(1)] {
+            let reporter = TestReporter::new();
+            let mut builder = crate::Builder::new("test", reporter.clone());
+            if let Some(sample_rate) = sample_rate {
+                builder = builder.with_trace_sampling(sample_rate);
+            }
+            let subscriber = tracing_subscriber::registry().with(builder.build());
+
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(TraceId::from("trace-1"), None).unwrap();
+                assert_eq!(crate::is_current_trace_sampled(), Some(true));
+                tracing::info!("event");
+            });
+
+            assert_eq!(
+                reporter.recorded().len(),
+                2,
+                "sample rate {:?}",
+                sample_rate
+            );
+        }
+    }
+
+    #[test]
+    fn omits_trace_id_fields_if_disabled() {
+        // "trace-1" is kept at a rate of 2 and dropped at a rate of 10, see
+        // `deterministic_sampler::test::samples_fixed_trace_ids`
+        for (sample_rate, sampled) in [(2, true), (10, false)] {
+            let reporter = TestReporter::new();
+            let layer = crate::Builder::new("test", reporter.clone())
+                .with_trace_id_fields(false)
+                .with_trace_sampling(sample_rate)
+                .build();
+            let subscriber = tracing_subscriber::registry().with(layer);
+
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(TraceId::from("trace-1"), None).unwrap();
+                tracing::info_span!("child").in_scope(|| tracing::info!("event"));
+            });
+
+            let recorded = reporter.recorded();
+            assert_eq!(recorded.len(), if sampled { 3 } else { 0 });
+            for data in recorded {
+                assert!(!data.contains_key(fields::TRACE_ID));
+                assert!(!data.contains_key(fields::SPAN_ID));
+                assert!(!data.contains_key(fields::PARENT_ID));
+                assert!(data.contains_key(fields::NAME));
+            }
+        }
+    }
+
+    #[test]
+    fn marks_root_span_type() {
+        let reporter = TestReporter::new();
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            // continuing a remote trace, so the root span has a parent
+            crate::register_dist_tracing_root(TraceId::new(), "1".parse().ok()).unwrap();
+            tracing::info_span!("child").in_scope(|| tracing::info!("event"));
+        });
+
+        let recorded = reporter.recorded();
+        let (event, child, root) = (&recorded[0], &recorded[1], &recorded[2]);
+        assert_eq!(root[fields::SPAN_TYPE], json!(fields::ROOT_SPAN));
+        assert!(!child.contains_key(fields::SPAN_TYPE));
+        assert!(!event.contains_key(fields::SPAN_TYPE));
+    }
+
+    #[test]
+    fn reports_span_kind() {
+        let reporter = TestReporter::new();
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", otel.kind = "SERVER");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info_span!("unknown", otel.kind = "sideways").in_scope(|| {});
+            tracing::info_span!("child").in_scope(|| tracing::info!("event"));
+        });
+
+        let recorded = reporter.recorded();
+        let (unknown, event, child, root) =
+            (&recorded[0], &recorded[1], &recorded[2], &recorded[3]);
+        assert_eq!(root[fields::SPAN_KIND], json!("server"));
+        assert!(!root.contains_key(fields::OTEL_KIND));
+        assert_eq!(unknown[fields::SPAN_KIND], json!("internal"));
+        assert_eq!(unknown[fields::OTEL_KIND], json!("sideways"));
+        assert_eq!(child[fields::SPAN_KIND], json!("internal"));
+        assert!(!event.contains_key(fields::SPAN_KIND));
+    }
+
+    #[test]
+    fn reports_baggage_as_span_fields() {
+        let reporter = TestReporter::new();
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", region = "recorded");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            crate::set_baggage("tenant_id", "42").unwrap();
+            crate::set_baggage("region", "baggage").unwrap();
+            crate::set_baggage(fields::NAME, "baggage").unwrap();
+            tracing::info_span!("child").in_scope(|| {});
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 2);
+        let (child, root) = (&recorded[0], &recorded[1]);
+        assert_eq!(child["tenant_id"], json!("42"));
+        assert_eq!(child["region"], json!("baggage"));
+        assert_eq!(child[fields::NAME], json!("child"));
+        assert_eq!(root["tenant_id"], json!("42"));
+        assert_eq!(root["region"], json!("recorded"));
+        assert_eq!(child["tracing.name"], json!("baggage"));
+    }
+
+    #[test]
+    fn caps_and_maps_baggage_as_recorded_fields() {
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_max_fields(2)
+            .with_key_mapping(|key| format!("app.{}", key))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", b = 1);
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            crate::set_baggage("a", "baggage").unwrap();
+            crate::set_baggage("c", "baggage").unwrap();
+            tracing::info_span!("child", b = 2).in_scope(|| {});
+        });
+
+        // the first two fields by name are kept
+        let child = &reporter.recorded()[0];
+        assert_eq!(child["app.a"], json!("baggage"));
+        assert_eq!(child["app.b"], json!(2));
+        assert!(!child.contains_key("app.c") && !child.contains_key("c"));
+        assert_eq!(child[fields::FIELDS_DROPPED], json!(1));
+    }
+
+    #[test]
+    fn reports_follows_from_links() {
+        let reporter = TestReporter::new();
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let (producer_trace, consumer_trace) = (TraceId::new(), TraceId::new());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let producer = tracing::info_span!("producer");
+            producer.in_scope(|| {
+                crate::register_dist_tracing_root(producer_trace.clone(), None).unwrap();
+            });
+            let consumer = tracing::info_span!("consumer");
+            consumer.in_scope(|| {
+                crate::register_dist_tracing_root(consumer_trace.clone(), None).unwrap();
+            });
+            consumer.follows_from(&producer);
+        });
+
+        let recorded = reporter.recorded();
+        let (link, consumer, producer) = (&recorded[0], &recorded[1], &recorded[2]);
+        assert_eq!(consumer["name"], json!("consumer"));
+        assert_eq!(link["meta.annotation_type"], json!("link"));
+        assert_eq!(link["trace.trace_id"], json!(consumer_trace.to_string()));
+        assert_eq!(link["trace.parent_id"], consumer["trace.span_id"]);
+        assert_eq!(
+            link["trace.link.trace_id"],
+            json!(producer_trace.to_string())
+        );
+        assert_eq!(link["trace.link.span_id"], producer["trace.span_id"]);
+    }
+
+    // rejects everything reported to it
+    struct FailingReporter;
+
+    impl Reporter for FailingReporter {
+        fn report_data(
+            &self,
+            _: HashMap<String, libhoney::Value>,
+            _: DateTime<Utc>,
+        ) -> Result<(), ReportError> {
+            Err(ReportError::Closed)
+        }
+    }
+
+    fn report_failing_trace(error_handling: Option<ErrorHandling>) {
+        let mut builder = crate::Builder::new("test", FailingReporter);
+        if let Some(error_handling) = error_handling {
+            builder = builder.with_error_handling(error_handling);
+        }
+        let subscriber = tracing_subscriber::registry().with(builder.build());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!("event");
+        });
+    }
+
+    #[test]
+    fn handles_reporter_errors_as_configured() {
+        assert!(matches!(ErrorHandling::default(), ErrorHandling::Log));
+        report_failing_trace(None);
+        report_failing_trace(Some(ErrorHandling::Ignore));
+
+        // one event and one span
+        let count = Arc::new(AtomicUsize::new(0));
+        report_failing_trace(Some(ErrorHandling::Count(count.clone())));
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+
+        let handled = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let errors = handled.clone();
+        report_failing_trace(Some(ErrorHandling::handler(move |err| {
+            errors.lock().unwrap().push(err.to_string())
+        })));
+        let handled = handled.lock().unwrap();
+        assert_eq!(handled.len(), 2);
+        assert_eq!(handled[0], ReportError::Closed.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "error sending event to honeycomb")]
+    fn panics_on_reporter_errors_if_configured() {
+        let layer = crate::Builder::new("test", FailingReporter)
+            .with_error_handling(ErrorHandling::Panic)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        // only a span, as panicking while unwinding from a failed event would abort
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+        });
+    }
+
+    #[test]
+    fn sample_rate_control_changes_rate_of_layer() {
+        // see deterministic_sampler for this decision at a rate of 2
+        let dropped = TraceId::from("80f198ee56343ba864fe8b2a57d3eff7");
+        let control = crate::SampleRateControl::new(0);
+        let reporter = TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone())
+            .with_sampler(control.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for sample_rate in [0, 2] {
+                control.set_rate(sample_rate);
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(dropped.clone(), None).unwrap();
+            }
+        });
+
+        assert_eq!(reporter.recorded().len(), 1);
+    }
+
+    #[test]
+    fn forces_only_the_trace_under_the_same_local_root() {
+        let reporter = TestReporter::new();
+        let telemetry = HoneycombTelemetry::new(reporter.clone(), Box::new(|_: &TraceId| false))
+            .with_force_sample_field(Some("force_sample"));
+        let layer = TelemetryLayer::new("test", telemetry, |tracing_id| SpanId { tracing_id });
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let trace_id = TraceId::new();
+        let remote_parent = Some(SpanId::from_str("1").unwrap());
+
+        tracing::subscriber::with_default(subscriber, || {
+            // two requests of the same trace, each handled under its own local root
+            for (request, force) in [("forced", true), ("other", false)] {
+                let span = tracing::info_span!("root", request);
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(trace_id.clone(), remote_parent.clone()).unwrap();
+                tracing::info!(force_sample = force, "event");
+            }
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 2);
+        assert!(recorded
+            .iter()
+            .all(|data| data.get("request") != Some(&json!("other"))));
+    }
+}
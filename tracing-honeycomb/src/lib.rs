@@ -11,19 +11,73 @@
 //!
 //! As a tracing layer, `TelemetryLayer` can be composed with other layers to provide stdout logging, filtering, etc.
 
+mod clock;
+pub mod fields;
 mod honeycomb;
+#[cfg(feature = "use_log")]
+mod log_reporter;
+#[cfg(feature = "use_otlp")]
+mod otlp_reporter;
+pub mod propagation;
 mod reporter;
+mod rich_reporter;
+mod sampler;
+mod sink;
 mod span_id;
+mod tail_sampling;
+#[cfg(feature = "use_tokio")]
+mod tokio_reporter;
 mod trace_id;
+mod trace_start;
+mod traceparent;
+#[cfg(feature = "use_tracing_reporter")]
+mod tracing_reporter;
 mod visitor;
 
-pub use honeycomb::HoneycombTelemetry;
-pub use reporter::{LibhoneyReporter, Reporter, StdoutReporter};
-pub use span_id::SpanId;
-pub use trace_id::TraceId;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use honeycomb::{ErrorHandling, HoneycombTelemetry};
+#[cfg(feature = "use_log")]
+pub use log_reporter::{LogReporter, LOG_REPORTER_TARGET};
+#[cfg(feature = "use_otlp")]
+pub use otlp_reporter::OtlpReporter;
+pub use reporter::{
+    BatchJsonReporter, BatchingReporter, BufferFullPolicy, EventSamplingReporter, FileReporter,
+    FlushGuard, HealthHandle, HealthReporter, InitError, LibhoneyReporter, MetricsReporter,
+    MultiReporter, OverflowPolicy, PrettyStdoutReporter, RateLimitReporter, ReloadableReporter,
+    ReportError, Reporter, ReporterHandle, ReporterHealth, RetryPolicy, RetryReporter,
+    RoutingReporter, SpoolingReporter, StdoutReporter, TestReporter, DATASET_FIELD,
+    DEFAULT_HEALTH_WINDOW, SAMPLE_RATE_FIELD,
+};
+pub use rich_reporter::{Flattening, HoneycombEvent, HoneycombSpan, RichReporter};
+pub use sampler::{
+    AlwaysSampler, FieldSampler, ModuloSampler, NameBasedSampler, SampleRateControl, Sampler,
+    SamplingStats,
+};
+pub use sink::ChannelReporter;
+pub use span_id::{ParseSpanIdError, SpanId};
+#[cfg(feature = "use_tokio")]
+pub use tokio_reporter::{AsyncReporter, InlineReporter, ReportFuture, TokioReporter};
+pub use trace_id::{TraceId, TraceIdLengthError};
+pub use trace_start::TRACE_START_TARGET;
+pub use traceparent::{TraceParent, TraceParentError};
 #[doc(no_inline)]
-pub use tracing_distributed::{TelemetryLayer, TraceCtxError};
-pub use visitor::HoneycombVisitor;
+pub use tracing_distributed::{
+    CountingTelemetry, TeeTelemetry, TelemetryLayer, TraceCtxError, MAX_BAGGAGE_BYTES,
+    MAX_BAGGAGE_ENTRIES,
+};
+#[cfg(feature = "use_tracing_reporter")]
+pub use tracing_reporter::{TracingReporter, TRACING_REPORTER_TARGET};
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+pub use visitor::DEFAULT_MAX_FLATTEN_DEPTH;
+pub use visitor::{
+    duration_as_ms, FieldConverter, FieldType, HoneycombVisitor, KeyMapping, RedactedFields,
+    StatusMapping, TimestampFormat, DEFAULT_MAX_ERROR_SOURCES, DEFAULT_MAX_FIELD_LEN, REDACTED,
+    SERVICE_NAME_FIELD,
+};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tail_sampling::TailSampling;
 
 pub(crate) mod deterministic_sampler;
 
@@ -34,12 +88,49 @@ use std::sync::Mutex;
 
 /// Register the current span as the local root of a distributed trace.
 ///
+/// If enabled via [`Builder::with_trace_start_events`], also reports an event marking the start
+/// of the trace.
+///
 /// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
 pub fn register_dist_tracing_root(
     trace_id: TraceId,
     remote_parent_span: Option<SpanId>,
 ) -> Result<(), TraceCtxError> {
-    tracing_distributed::register_dist_tracing_root(trace_id, remote_parent_span)
+    tracing_distributed::register_dist_tracing_root(trace_id, remote_parent_span)?;
+    trace_start::report();
+    Ok(())
+}
+
+/// Register the current span as the local root of a distributed trace, as
+/// [`register_dist_tracing_root`], and record the given fields on it, e.g. `http.method` and
+/// `http.route` for an incoming request.
+///
+/// The fields are reported on the root span as though it had recorded them itself: they are
+/// redacted as configured via [`Builder::with_redacted_fields`], prefixed with `tracing.` if
+/// their name is [reserved](fields::RESERVED), and renamed by any [`KeyMapping`]. Their values
+/// are reported as given, so [`FieldConverter`]s do not apply to them. A field of the same name
+/// recorded on the span later replaces the given value. Unlike baggage, the fields are not
+/// inherited by other spans.
+///
+/// Requires the layer to be constructed by this crate, with the [`HoneycombVisitor`], and fails
+/// with `TraceCtxError::VisitorTypeMismatch` otherwise, without registering the span.
+pub fn register_dist_tracing_root_with_fields(
+    trace_id: TraceId,
+    remote_parent_span: Option<SpanId>,
+    fields: HashMap<String, libhoney::Value>,
+) -> Result<(), TraceCtxError> {
+    tracing_distributed::register_dist_tracing_root_with_visitor::<_, _, HoneycombVisitor, _>(
+        trace_id,
+        remote_parent_span,
+        |visitor| {
+            for (name, value) in fields {
+                visitor.record_named(&name, || value);
+            }
+        },
+    )?;
+    // once the fields are recorded, so that the event carries them
+    trace_start::report();
+    Ok(())
 }
 
 /// Retrieve the distributed trace context associated with the current span.
@@ -52,6 +143,74 @@ pub fn current_dist_trace_ctx() -> Result<(TraceId, SpanId), TraceCtxError> {
     tracing_distributed::current_dist_trace_ctx()
 }
 
+/// Returns whether the sampler of the current subscriber's layer keeps the trace the current span
+/// is part of, e.g. to skip expensive enrichment of traces which will be dropped.
+///
+/// Returns `None` if the current span is not part of a trace, or if the subscriber has no
+/// [`HoneycombTelemetry`] layer. The decision reflects the sampler as configured at the time of
+/// the call, e.g. the current rate of a [`SampleRateControl`]. With tail sampling, traces which
+/// fail or are forced via [`Builder::with_force_sample_field`] are kept regardless.
+///
+/// Costs about as much as [`current_dist_trace_ctx`], but allocates nothing once the trace
+/// context of the current span has been looked up, as it is by this call.
+pub fn is_current_trace_sampled() -> Option<bool> {
+    tracing_distributed::with_current_trace::<SpanId, TraceId, HoneycombVisitor, _>(
+        |trace_id, root_name, root_key, visitor, baggage| {
+            let sampler = visitor.sampler.as_ref()?;
+            let key = sampler.key_field().and_then(|field| {
+                root_key.or_else(|| {
+                    baggage
+                        .iter()
+                        .find(|(key, _)| key == field)
+                        .map(|(_, value)| value.as_str())
+                })
+            });
+            Some(sampler.should_sample_keyed(trace_id, root_name, key))
+        },
+    )
+    .ok()
+    .flatten()
+}
+
+/// Retrieve the distributed trace context associated with the current span, along with the ids
+/// of the spans from the local root of the trace down to the current span, root-first.
+///
+/// The remote parent span is not included. The ids are a snapshot of the span tree at the time
+/// of the call, and are allocated in proportion to the depth of the current span.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn current_dist_trace_ctx_with_ancestry() -> Result<(TraceId, Vec<SpanId>), TraceCtxError> {
+    tracing_distributed::current_dist_trace_ctx_with_ancestry()
+}
+
+/// Sets a baggage entry on the current span, replacing any entry with the same key.
+///
+/// Spans created within the current span, after this call, inherit its baggage, which is
+/// reported as fields on each of them, e.g. to carry a `tenant_id` through a whole trace.
+/// Fields recorded on a span take precedence over baggage entries with the same key, and
+/// otherwise baggage is reported as recorded fields are, subject to
+/// [`Builder::with_key_mapping`] and [`Builder::with_max_fields`]. Baggage is propagated to
+/// other processes by [`propagation::inject`], and can be restored from
+/// [`propagation::extract_baggage`].
+///
+/// Fails if the current span is not part of a distributed trace, or if setting the entry would
+/// exceed [`MAX_BAGGAGE_ENTRIES`] or [`MAX_BAGGAGE_BYTES`].
+pub fn set_baggage(key: impl Into<String>, value: impl Into<String>) -> Result<(), TraceCtxError> {
+    tracing_distributed::set_baggage::<SpanId, TraceId>(key, value)
+}
+
+/// Returns the value of the baggage entry with the given key in effect for the current span, if
+/// any. See [`set_baggage`].
+pub fn get_baggage(key: &str) -> Result<Option<String>, TraceCtxError> {
+    tracing_distributed::get_baggage::<SpanId, TraceId>(key)
+}
+
+/// Returns all baggage entries in effect for the current span, in the order they were first
+/// set. See [`set_baggage`].
+pub fn current_baggage() -> Result<Vec<(String, String)>, TraceCtxError> {
+    tracing_distributed::current_baggage::<SpanId, TraceId>()
+}
+
 /// Construct a TelemetryLayer that does not publish telemetry to any backend.
 ///
 /// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
@@ -64,39 +223,310 @@ pub fn new_blackhole_telemetry_layer(
     )
 }
 
+type CountingLayer = TelemetryLayer<CountingTelemetry<SpanId, TraceId>, SpanId, TraceId>;
+
+/// Construct a TelemetryLayer that does not publish telemetry to any backend, but counts the
+/// spans and events it would have reported.
+///
+/// The returned `CountingTelemetry` shares its counters with the layer, exposing them via
+/// `span_count` and `event_count`.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_counting_telemetry_layer() -> (CountingLayer, CountingTelemetry<SpanId, TraceId>) {
+    let telemetry = CountingTelemetry::default();
+    let layer = TelemetryLayer::new(
+        "honeycomb_counting_tracing_layer",
+        telemetry.clone(),
+        move |tracing_id| SpanId { tracing_id },
+    );
+
+    (layer, telemetry)
+}
+
 /// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided honeycomb config.
 ///
+/// The config is not validated, as with the other libhoney constructors, so a config with e.g.
+/// an empty API key results in every event being rejected once sent. Use
+/// [`try_new_honeycomb_telemetry_layer`] to check the config up front.
+///
 /// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
 pub fn new_honeycomb_telemetry_layer(
     service_name: &'static str,
     honeycomb_config: libhoney::Config,
 ) -> TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId> {
-    let reporter = libhoney::init(honeycomb_config);
-    // publishing requires &mut so just mutex-wrap it
-    // FIXME: may not be performant, investigate options (eg mpsc)
-    let reporter = Mutex::new(reporter);
+    Builder::new_libhoney(service_name, honeycomb_config).build()
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, failing if the config has an empty API key or dataset, or an API host
+/// which is not an http(s) URL.
+///
+/// The config is checked before libhoney is initialized, rather than having every event
+/// rejected once the layer is in use. Whether the host is reachable and the key valid is
+/// only known once events are sent.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn try_new_honeycomb_telemetry_layer(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+) -> Result<TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId>, InitError> {
+    Ok(Builder::try_new_libhoney(service_name, honeycomb_config)?.build())
+}
 
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, along with a [`FlushGuard`] which flushes the libhoney client when dropped.
+///
+/// Useful for short-lived processes, such as CLIs, whose last spans would otherwise remain
+/// buffered on exit. See [`FlushGuard`] for when to drop the guard.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_flush_guard(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+) -> (
+    TelemetryLayer<HoneycombTelemetry<Arc<LibhoneyReporter>>, SpanId, TraceId>,
+    FlushGuard,
+) {
+    Builder::new_libhoney(service_name, honeycomb_config).build_with_flush_guard()
+}
+
+/// Construct a TelemetryLayer that prints telemetry to stdout as one line of JSON per span or
+/// event, e.g. to try the layer out without a Honeycomb API key.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_stdout_telemetry_layer(
+    service_name: &'static str,
+) -> TelemetryLayer<HoneycombTelemetry<StdoutReporter>, SpanId, TraceId> {
+    Builder::new_stdout(service_name).build()
+}
+
+/// Construct a TelemetryLayer that prints telemetry to stdout as indented JSON, colorized if
+/// stdout is a terminal. See [`PrettyStdoutReporter`].
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_stdout_telemetry_layer_pretty(
+    service_name: &'static str,
+) -> TelemetryLayer<HoneycombTelemetry<PrettyStdoutReporter>, SpanId, TraceId> {
+    Builder::new(service_name, PrettyStdoutReporter::new()).build()
+}
+
+/// Construct a TelemetryLayer that appends telemetry to the file at `path` as newline-delimited
+/// JSON, creating the file if needed.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_file(
+    service_name: &'static str,
+    path: impl Into<std::path::PathBuf>,
+) -> std::io::Result<TelemetryLayer<HoneycombTelemetry<FileReporter>, SpanId, TraceId>> {
+    let reporter = FileReporter::new(path)?;
+
+    Ok(Builder::new(service_name, reporter).build())
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to the provided reporter, e.g. a
+/// [`TestReporter`] to assert on reported data in tests.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_reporter<R: Reporter>(
+    service_name: &'static str,
+    reporter: R,
+) -> TelemetryLayer<HoneycombTelemetry<R>, SpanId, TraceId> {
+    Builder::new(service_name, reporter).build()
+}
+
+/// Construct a TelemetryLayer that hands spans and events to the provided [`RichReporter`] as
+/// gathered, rather than flattened into Honeycomb's columns.
+///
+/// Use [`Builder::new`] to configure sampling, redaction and the like, which apply as for any
+/// other reporter.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_rich_reporter<R: RichReporter>(
+    service_name: &'static str,
+    reporter: R,
+) -> TelemetryLayer<HoneycombTelemetry<R>, SpanId, TraceId> {
+    Builder::new(service_name, reporter).build()
+}
+
+/// Construct a TelemetryLayer that reports every span and event to both of the provided
+/// telemetry capabilities, each with its own sampler, e.g. to write all traces to a new
+/// dataset while keeping 1% of them in an old one during a migration.
+///
+/// Unlike with a [`MultiReporter`], which reports the data sampled by a single sampler to
+/// each of its reporters, each capability samples independently. Build them via
+/// [`Builder::build_telemetry`]. Fields are recorded as configured on `first`, see
+/// [`TeeTelemetry`], and each span and event kept by both is converted and serialized twice.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_tee<A: Reporter, B: Reporter>(
+    service_name: &'static str,
+    first: HoneycombTelemetry<A>,
+    second: HoneycombTelemetry<B>,
+) -> TelemetryLayer<TeeTelemetry<HoneycombTelemetry<A>, HoneycombTelemetry<B>>, SpanId, TraceId> {
     TelemetryLayer::new(
         service_name,
-        HoneycombTelemetry::new(reporter, None),
+        TeeTelemetry::new(first, second),
         move |tracing_id| SpanId { tracing_id },
     )
 }
 
+/// Construct a TelemetryLayer that publishes telemetry to each of the provided reporters.
+///
+/// See [`MultiReporter`] for the cost of reporting to several backends.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_reporters(
+    service_name: &'static str,
+    reporters: Vec<Box<dyn Reporter + Send + Sync>>,
+) -> TelemetryLayer<HoneycombTelemetry<MultiReporter>, SpanId, TraceId> {
+    Builder::new(service_name, MultiReporter::new(reporters)).build()
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to the provided reporter, along with a
+/// [`ReporterHandle`] through which the reporter can be replaced later, e.g. by a
+/// [`LibhoneyReporter`] once configuration has been loaded.
+///
+/// See [`ReloadableReporter`] for the cost of the indirection.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_reloadable(
+    service_name: &'static str,
+    reporter: Box<dyn Reporter + Send + Sync>,
+) -> (
+    TelemetryLayer<HoneycombTelemetry<ReloadableReporter>, SpanId, TraceId>,
+    ReporterHandle,
+) {
+    let reporter = ReloadableReporter::new(reporter);
+    let handle = reporter.handle();
+
+    (Builder::new(service_name, reporter).build(), handle)
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, along with a [`HealthHandle`] tracking whether recent sends to Honeycomb
+/// succeeded, e.g. to serve from a readiness probe.
+///
+/// libhoney sends events in the background, so the handle records the outcome of each event
+/// once libhoney learns it: an event counts as failed if it could not be sent, was rejected
+/// by Honeycomb, or was dropped because libhoney's buffer was full. Health is judged from
+/// these outcomes as by [`HealthHandle::default`]; use [`Builder::new_libhoney_with_health`]
+/// to judge it otherwise.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_health(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+) -> (
+    TelemetryLayer<HoneycombTelemetry<HealthReporter<LibhoneyReporter>>, SpanId, TraceId>,
+    HealthHandle,
+) {
+    let health = HealthHandle::default();
+    let builder = Builder::new_libhoney_with_health(service_name, honeycomb_config, health.clone());
+
+    (builder.build(), health)
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, handing data to the libhoney client in batches.
+///
+/// Data is forwarded once `batch_size` spans and events have been buffered or the oldest
+/// buffered one is `max_latency` old, whichever comes first.
+///
+/// # Panics
+///
+/// Panics if `batch_size` is 0 or `max_latency` is zero, as [`BatchingReporter::new`] does.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_batched(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+    batch_size: usize,
+    max_latency: std::time::Duration,
+) -> TelemetryLayer<HoneycombTelemetry<BatchingReporter<LibhoneyReporter>>, SpanId, TraceId> {
+    let reporter =
+        BatchingReporter::new(libhoney_reporter(honeycomb_config), batch_size, max_latency);
+
+    Builder::new(service_name, reporter).build()
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, without blocking the traced code path on the libhoney client.
+///
+/// Reported data is queued on a bounded channel of the given `capacity`, which is drained
+/// into the libhoney client by a task spawned on the current `tokio` runtime. `overflow`
+/// selects whether reporting drops data or blocks while the channel is full, which it only
+/// does outside of a `tokio` runtime, see [`TokioReporter`].
+///
+/// # Panics
+///
+/// Panics if called outside of a `tokio` runtime, or if `capacity` is 0.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+#[cfg(feature = "use_tokio")]
+pub fn new_honeycomb_telemetry_layer_async(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+    capacity: usize,
+    overflow: OverflowPolicy,
+) -> TelemetryLayer<HoneycombTelemetry<TokioReporter>, SpanId, TraceId> {
+    let reporter = TokioReporter::spawn(
+        InlineReporter::new(libhoney_reporter(honeycomb_config)),
+        capacity,
+        overflow,
+    );
+
+    Builder::new(service_name, reporter).build()
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, adding the provided fields to every span and event.
+///
+/// Fields recorded on a span or event take precedence over global fields of the same name,
+/// see [`Builder::with_global_fields`].
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_fields(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+    global_fields: HashMap<String, libhoney::Value>,
+) -> TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId> {
+    Builder::new_libhoney(service_name, honeycomb_config)
+        .with_global_fields(global_fields)
+        .build()
+}
+
+/// Construct a TelemetryLayer that exports telemetry to the OpenTelemetry collector at the
+/// provided OTLP/gRPC `endpoint`, e.g. `http://localhost:4317`.
+///
+/// See [`OtlpReporter`] for how spans and events are translated. To export in batches, pass a
+/// [`BatchingReporter`] wrapping an `OtlpReporter` to
+/// `new_honeycomb_telemetry_layer_with_reporter` instead.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+#[cfg(feature = "use_otlp")]
+pub fn new_honeycomb_telemetry_layer_with_otlp(
+    service_name: &'static str,
+    endpoint: impl Into<String>,
+) -> Result<TelemetryLayer<HoneycombTelemetry<OtlpReporter>, SpanId, TraceId>, ReportError> {
+    let reporter = OtlpReporter::connect(endpoint)?;
+
+    Ok(Builder::new(service_name, reporter).build())
+}
+
 /// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the
 /// provided honeycomb config, and sample rate.
 ///
-/// This function differs from `new_honeycomb_telemetry_layer` and the `sample_rate`
-/// on the `libhoney::Config` there in an important way. `libhoney` samples `Event`
+/// This function differs from the `sample_rate` on the `libhoney::Config` in an important
+/// way. `libhoney` samples `Event`
 /// data, which is individual spans on each trace. This means that using the
 /// sampling logic in libhoney may result in missing event data or incomplete
 /// traces. Calling this function provides trace-level sampling, meaning sampling
 /// decisions are based on a modulo of the traceID, and events in a single trace
 /// will not be sampled differently. If the trace is sampled, then all spans
 /// under it will be sent to honeycomb. If a trace is not sampled, no spans or
-/// events under it will be sent. When using this trace-level sampling, the
-/// `sample_rate` parameter on the `libhoney::Config` should be set to 1, which
-/// is the default.
+/// events under it will be sent. So that both don't sample at once, the `sample_rate`
+/// parameter on the `libhoney::Config` is reset to 1, the default, as it is by every
+/// constructor initializing libhoney.
 ///
 /// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
 pub fn new_honeycomb_telemetry_layer_with_trace_sampling(
@@ -104,16 +534,143 @@ pub fn new_honeycomb_telemetry_layer_with_trace_sampling(
     honeycomb_config: libhoney::Config,
     sample_rate: u32,
 ) -> TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId> {
-    let reporter = libhoney::init(honeycomb_config);
-    // publishing requires &mut so just mutex-wrap it
-    // FIXME: may not be performant, investigate options (eg mpsc)
-    let reporter = Mutex::new(reporter);
+    Builder::new_libhoney(service_name, honeycomb_config)
+        .with_trace_sampling(sample_rate)
+        .build()
+}
 
-    TelemetryLayer::new(
+/// Environment variable from which [`new_honeycomb_telemetry_layer_with_env_sampling`] reads
+/// the sample rate.
+pub const SAMPLE_RATE_ENV_VAR: &str = "HONEYCOMB_SAMPLE_RATE";
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, with trace-level sampling at the rate set in the
+/// [`HONEYCOMB_SAMPLE_RATE`](SAMPLE_RATE_ENV_VAR) environment variable, e.g. to `10` to keep
+/// one in ten traces, so that the rate can be tuned per deployment without code changes.
+///
+/// The variable is read once, when this is called. If it is not set, every trace is kept. If
+/// it is not a non-negative integer, this fails with [`InitError::InvalidSampleRate`]. A
+/// rate of 0 keeps every trace, as with [`Builder::with_trace_sampling`]. Sampling
+/// otherwise behaves as in [`new_honeycomb_telemetry_layer_with_trace_sampling`], including
+/// resetting the `sample_rate` of the `libhoney::Config`.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_env_sampling(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+) -> Result<TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId>, InitError> {
+    let sample_rate = parse_sample_rate(std::env::var_os(SAMPLE_RATE_ENV_VAR))?;
+    Ok(new_honeycomb_telemetry_layer_with_trace_sampling(
         service_name,
-        HoneycombTelemetry::new(reporter, Some(sample_rate)),
-        move |tracing_id| SpanId { tracing_id },
-    )
+        honeycomb_config,
+        sample_rate,
+    ))
+}
+
+// the sample rate set in `SAMPLE_RATE_ENV_VAR`, 1 if unset
+fn parse_sample_rate(value: Option<std::ffi::OsString>) -> Result<u32, InitError> {
+    let value = match value {
+        Some(value) => value.to_string_lossy().into_owned(),
+        None => return Ok(1),
+    };
+    value
+        .trim()
+        .parse()
+        .map_err(|_| InitError::InvalidSampleRate(value))
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, with trace-level sampling at a rate which can be adjusted at runtime.
+///
+/// Returns the layer along with a [`SampleRateControl`] handle used to adjust the rate.
+/// Sampling otherwise behaves as in `new_honeycomb_telemetry_layer_with_trace_sampling`,
+/// except that a rate of 0 disables sampling.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_sample_rate_control(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+    sample_rate: u32,
+) -> (
+    TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId>,
+    SampleRateControl,
+) {
+    let control = SampleRateControl::new(sample_rate);
+    let layer =
+        new_honeycomb_telemetry_layer_with_sampler(service_name, honeycomb_config, control.clone());
+
+    (layer, control)
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, reporting only the traces selected by the provided `Sampler`.
+///
+/// As with `new_honeycomb_telemetry_layer_with_trace_sampling`, the `sample_rate` parameter
+/// on the `libhoney::Config` is reset to 1.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_sampler<S: Sampler + 'static>(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+    sampler: S,
+) -> TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId> {
+    Builder::new_libhoney(service_name, honeycomb_config)
+        .with_sampler(sampler)
+        .build()
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, sampling each trace at the rate configured for the name of its local root
+/// span (see `register_dist_tracing_root`), or at `default_rate` for other names.
+///
+/// See [`NameBasedSampler`] for how decisions stay consistent within a trace.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_name_based_sampling(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+    rates: HashMap<&'static str, u32>,
+    default_rate: u32,
+) -> TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId> {
+    let sampler = NameBasedSampler::new(rates, default_rate);
+    new_honeycomb_telemetry_layer_with_sampler(service_name, honeycomb_config, sampler)
+}
+
+/// Construct a TelemetryLayer that publishes telemetry to honeycomb.io using the provided
+/// honeycomb config, with trace-level sampling that always keeps traces containing errors.
+///
+/// Spans and events are buffered per trace until the trace's local root span (see
+/// `register_dist_tracing_root`) closes. The whole trace is then reported if any of its spans
+/// or events recorded `error = true` or was at the `ERROR` level, and is otherwise sampled as in
+/// `new_honeycomb_telemetry_layer_with_trace_sampling`.
+///
+/// Every span and event of up to `max_traces` in-flight traces is held in memory, so this
+/// trades memory for the guarantee of keeping error traces. A trace is decided early, using
+/// only the data seen so far, once it has been pending for `timeout` or when more than
+/// `max_traces` traces are pending (oldest first). Later data for an early-decided trace is
+/// buffered and decided separately. Traces without a local root are only decided early.
+/// Expired traces are decided by a background thread, without waiting for new data, and
+/// `HoneycombTelemetry::flush` and `shutdown` decide all pending traces first.
+///
+/// Specialized to the honeycomb.io-specific SpanId and TraceId provided by this crate.
+pub fn new_honeycomb_telemetry_layer_with_tail_sampling(
+    service_name: &'static str,
+    honeycomb_config: libhoney::Config,
+    sample_rate: u32,
+    timeout: std::time::Duration,
+    max_traces: usize,
+) -> TelemetryLayer<HoneycombTelemetry<LibhoneyReporter>, SpanId, TraceId> {
+    Builder::new_libhoney(service_name, honeycomb_config)
+        .with_trace_sampling(sample_rate)
+        .with_tail_sampling(timeout, max_traces)
+        .build()
+}
+
+// libhoney samples individual spans and events, which would drop parts of the traces kept by
+// the layer's trace-level sampling, so it must not sample as well
+fn without_libhoney_sampling(mut honeycomb_config: libhoney::Config) -> libhoney::Config {
+    honeycomb_config.options.sample_rate = 1;
+    honeycomb_config
 }
 
 /// Builds Honeycomb Telemetry with custom configuration values.
@@ -121,67 +678,226 @@ pub fn new_honeycomb_telemetry_layer_with_trace_sampling(
 /// Methods can be chained in order to set the configuration values. The
 /// TelemetryLayer is constructed by calling [`build`].
 ///
-/// New instances of `Builder` are obtained via [`Builder::new_libhoney`]
-/// or [`Builder::new_stdout`].
+/// New instances of `Builder` are obtained via [`Builder::new_libhoney`],
+/// [`Builder::new_stdout`], or [`Builder::new`] for any other [`Reporter`].
 ///
 /// [`Builder::new_stdout`] is useful when instrumenting e.g. AWS Lambda functions.
 /// See more at [AWS Lambda Instrumentation]. For almost all other use cases you are probably
 /// looking for [`Builder::new_libhoney`].
 ///
 /// [`build`]: method@Self::build
+/// [`Builder::new`]: method@Builder::new
 /// [`Builder::new_stdout`]: method@Builder::<StdoutReporter>::new_stdout
 /// [`Builder::new_libhoney`]: method@Builder::<LibhoneyReporter>::new_libhoney
 /// [AWS Lambda Instrumentation]: https://docs.honeycomb.io/getting-data-in/integrations/aws/aws-lambda/
 #[derive(Debug)]
 pub struct Builder<R> {
     reporter: R,
-    sample_rate: Option<u32>,
+    sampler: Box<dyn Sampler>,
+    error_handling: ErrorHandling,
+    tail_sampling: Option<TailSampling>,
+    redacted_fields: Option<RedactedFields>,
+    key_mapping: Option<KeyMapping>,
+    max_field_len: usize,
+    escape_control_chars: bool,
+    max_error_sources: usize,
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    max_flatten_depth: usize,
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    nest_values: bool,
+    force_sample_field: Option<&'static str>,
+    clock: Box<dyn Clock>,
+    global_fields: HashMap<String, libhoney::Value>,
+    max_fields: Option<usize>,
+    field_converters: HashMap<String, FieldConverter>,
+    field_types: HashMap<String, FieldType>,
+    sampling_stats: SamplingStats,
+    timestamp_field: Option<(String, TimestampFormat)>,
+    min_level: Option<tracing::Level>,
+    min_span_duration: Option<std::time::Duration>,
+    track_busy_time: bool,
+    status_mapping: Option<StatusMapping>,
+    message_field: Option<String>,
+    source_location: bool,
+    library: (String, String),
+    trace_id_fields: bool,
+    trace_start: bool,
+    #[cfg(feature = "deterministic_span_ids")]
+    deterministic_span_ids: bool,
     service_name: &'static str,
 }
 
 impl Builder<StdoutReporter> {
     /// Returns a new `Builder` that reports data to stdout
     pub fn new_stdout(service_name: &'static str) -> Self {
-        Self {
-            reporter: StdoutReporter,
-            sample_rate: None,
-            service_name,
-        }
+        Self::new(service_name, StdoutReporter)
     }
 }
 
 impl Builder<LibhoneyReporter> {
     /// Returns a new `Builder` that reports data to a [`libhoney::Client`]
+    ///
+    /// Traces are sampled by the layer, see [`with_trace_sampling`](Builder::with_trace_sampling),
+    /// so the `sample_rate` of the config is reset to 1, for libhoney not to drop spans out of
+    /// the traces the layer keeps.
     pub fn new_libhoney(service_name: &'static str, config: libhoney::Config) -> Self {
-        let reporter = libhoney::init(config);
-
-        // Handle the libhoney response channel by consuming and ignoring messages. This prevents a
-        // deadlock because the responses() channel is bounded and gains an item for every event
-        // emitted.
-        let responses = reporter.responses();
-        std::thread::spawn(move || {
-            loop {
-                if responses.recv().is_err() {
-                    // If we receive an error, the channel is empty & disconnected. No need to keep
-                    // this thread around.
-                    break;
-                }
+        Self::new(service_name, libhoney_reporter(config))
+    }
+
+    /// Returns a new `Builder` that reports data to a [`libhoney::Client`], or an error if
+    /// the config is invalid, see [`try_new_honeycomb_telemetry_layer`].
+    pub fn try_new_libhoney(
+        service_name: &'static str,
+        config: libhoney::Config,
+    ) -> Result<Self, InitError> {
+        reporter::validate_libhoney_config(&config)?;
+        Ok(Self::new_libhoney(service_name, config))
+    }
+
+    /// Returns a new `Builder` that reports data to a [`libhoney::Client`], handling events
+    /// libhoney drops because its buffer is full as configured by `policy`, rather than by
+    /// logging them to stderr.
+    pub fn new_libhoney_with_buffer_full_policy(
+        service_name: &'static str,
+        config: libhoney::Config,
+        policy: BufferFullPolicy,
+    ) -> Self {
+        Self::new(
+            service_name,
+            libhoney_reporter_with_buffer_full_policy(config, policy, None),
+        )
+    }
+}
+
+impl Builder<HealthReporter<LibhoneyReporter>> {
+    /// Returns a new `Builder` that reports data to a [`libhoney::Client`], recording the
+    /// outcome of each event libhoney sends in `health`, as described for
+    /// [`new_honeycomb_telemetry_layer_with_health`]. The reporter's
+    /// [`health`](Reporter::health) is that judged by `health`.
+    pub fn new_libhoney_with_health(
+        service_name: &'static str,
+        config: libhoney::Config,
+        health: HealthHandle,
+    ) -> Self {
+        let reporter = libhoney_reporter_with_buffer_full_policy(
+            config,
+            BufferFullPolicy::default(),
+            Some(health.clone()),
+        );
+        Self::new(service_name, HealthReporter::untracked(reporter, health))
+    }
+}
+
+/// Returns a new [`LibhoneyReporter`], initializing a libhoney client from `config`, as used by
+/// [`Builder::new_libhoney`], which also describes how the config's `sample_rate` is reset.
+///
+/// To share one client between several layers, e.g. one for spans and one for logs, wrap the
+/// reporter in an [`Arc`], which is itself a [`Reporter`], and pass a clone to each
+/// [`Builder`]. This initializes libhoney once, rather than once per layer, each with its own
+/// transmission threads and buffer. The layers then share the client's buffer as well:
+/// flushing either layer's reporter, e.g. via its [`FlushGuard`], transmits the data reported
+/// by both. A single guard therefore suffices, held until all the layers have stopped being
+/// used.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use tracing_honeycomb::{new_libhoney_reporter, Builder};
+///
+/// # let config = libhoney::Config {
+/// #     options: libhoney::client::Options::default(),
+/// #     transmission_options: libhoney::transmission::Options::default(),
+/// # };
+/// let reporter = Arc::new(new_libhoney_reporter(config));
+/// let (spans, _guard) = Builder::new("my-service", reporter.clone()).build_with_flush_guard();
+/// let logs = Builder::new("my-service", reporter).build();
+/// ```
+pub fn new_libhoney_reporter(config: libhoney::Config) -> LibhoneyReporter {
+    libhoney_reporter(config)
+}
+
+fn libhoney_reporter(config: libhoney::Config) -> LibhoneyReporter {
+    libhoney_reporter_with_buffer_full_policy(config, BufferFullPolicy::default(), None)
+}
+
+// records the outcome of each event libhoney sends in `health`, if given
+fn libhoney_reporter_with_buffer_full_policy(
+    config: libhoney::Config,
+    policy: BufferFullPolicy,
+    health: Option<HealthHandle>,
+) -> LibhoneyReporter {
+    let reporter = libhoney::init(without_libhoney_sampling(config));
+
+    // Handle the libhoney response channel by consuming messages, recording their outcome if
+    // tracking health, and otherwise only acting on those for events dropped because the
+    // buffer is full. This prevents a deadlock because the
+    // responses() channel is bounded and gains an item for every event emitted.
+    let responses = reporter.responses();
+    // Once we receive an error, the channel is empty & disconnected. No need to keep this
+    // thread around.
+    std::thread::spawn(move || {
+        while let Ok(response) = responses.recv() {
+            if let Some(health) = &health {
+                let sent = response.error.is_none()
+                    && response.status_code.is_none_or(|code| code.is_success());
+                health.record(sent);
             }
-        });
+            policy.handle(response.error.as_deref());
+        }
+    });
 
-        // publishing requires &mut so just mutex-wrap it
-        // FIXME: may not be performant, investigate options (eg mpsc)
-        let reporter = Mutex::new(reporter);
+    // publishing requires &mut so just mutex-wrap it
+    // FIXME: may not be performant, investigate options (eg mpsc)
+    Mutex::new(reporter)
+}
 
+impl<R: RichReporter> Builder<R> {
+    /// Returns a new `Builder` that reports data to the provided reporter, e.g. a
+    /// [`BatchingReporter`] or a [`TestReporter`], or hands spans and events to the provided
+    /// [`RichReporter`].
+    ///
+    /// The reporter may be shared with other layers by passing an `Arc` of it, or an
+    /// `Arc<dyn Reporter + Send + Sync>`, see [`new_libhoney_reporter`].
+    pub fn new(service_name: &'static str, reporter: R) -> Self {
         Self {
             reporter,
-            sample_rate: None,
+            sampler: Box::new(AlwaysSampler),
+            error_handling: ErrorHandling::default(),
+            tail_sampling: None,
+            redacted_fields: None,
+            key_mapping: None,
+            max_field_len: DEFAULT_MAX_FIELD_LEN,
+            escape_control_chars: false,
+            max_error_sources: DEFAULT_MAX_ERROR_SOURCES,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            max_flatten_depth: DEFAULT_MAX_FLATTEN_DEPTH,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            nest_values: false,
+            force_sample_field: None,
+            clock: Box::new(SystemClock),
+            global_fields: HashMap::new(),
+            max_fields: None,
+            field_converters: HashMap::new(),
+            field_types: HashMap::new(),
+            sampling_stats: SamplingStats::new(),
+            timestamp_field: None,
+            min_level: None,
+            min_span_duration: None,
+            track_busy_time: false,
+            status_mapping: None,
+            message_field: None,
+            source_location: false,
+            library: (
+                fields::DEFAULT_LIBRARY_NAME.to_string(),
+                fields::DEFAULT_LIBRARY_VERSION.to_string(),
+            ),
+            trace_id_fields: true,
+            trace_start: false,
+            #[cfg(feature = "deterministic_span_ids")]
+            deterministic_span_ids: false,
             service_name,
         }
     }
-}
 
-impl<R: Reporter> Builder<R> {
     /// Enables sampling for the telemetry layer.
     ///
     /// The `sample_rate` on the `libhoney::Config` is different from this in an important way.
@@ -192,20 +908,557 @@ impl<R: Reporter> Builder<R> {
     /// decisions are based on a modulo of the traceID, and events in a single trace
     /// will not be sampled differently. If the trace is sampled, then all spans
     /// under it will be sent to honeycomb. If a trace is not sampled, no spans or
-    /// events under it will be sent. When using this trace-level sampling,
-    /// when using a [`LibhoneyReporter`] the `sample_rate` parameter on the
-    /// [`libhoney::Config`] should be set to 1, which is the default.
+    /// events under it will be sent. So that both don't sample at once, a
+    /// [`LibhoneyReporter`] constructed by this crate, e.g. via [`Builder::new_libhoney`],
+    /// resets the `sample_rate` parameter on the [`libhoney::Config`] to 1, the default.
+    ///
+    /// A `sample_rate` of 1 or 0 keeps every trace, as does a `Builder` on which no sampling
+    /// is configured.
     pub fn with_trace_sampling(mut self, sample_rate: u32) -> Self {
-        self.sample_rate.replace(sample_rate);
+        self.sampler = Box::new(ModuloSampler::new(sample_rate));
+        self
+    }
+
+    /// Enables sampling for the telemetry layer using a custom [`Sampler`].
+    ///
+    /// Replaces any sampling set by [`with_trace_sampling`](Self::with_trace_sampling).
+    pub fn with_sampler<S: Sampler + 'static>(mut self, sampler: S) -> Self {
+        self.sampler = Box::new(sampler);
+        self
+    }
+
+    /// Counts the spans and events kept and dropped by sampling in the provided
+    /// [`SamplingStats`], a clone of which can be kept to read the counts.
+    ///
+    /// The counts are also available via [`HoneycombTelemetry::sampling_stats`], whether or not
+    /// this is called.
+    pub fn with_sampling_stats(mut self, sampling_stats: SamplingStats) -> Self {
+        self.sampling_stats = sampling_stats;
+        self
+    }
+
+    /// Sets how errors returned by the reporter are handled.
+    ///
+    /// Defaults to [`ErrorHandling::Log`].
+    pub fn with_error_handling(mut self, error_handling: ErrorHandling) -> Self {
+        self.error_handling = error_handling;
+        self
+    }
+
+    /// Enables tail-based sampling, which always keeps traces containing errors.
+    ///
+    /// Spans and events are buffered per trace until its local root span closes, and are
+    /// then reported if any recorded `error = true` or was at the `ERROR` level. Other traces
+    /// are sampled by the configured sampler. See
+    /// [`new_honeycomb_telemetry_layer_with_tail_sampling`] for the memory tradeoff and the
+    /// meaning of `timeout` and `max_traces`.
+    pub fn with_tail_sampling(mut self, timeout: std::time::Duration, max_traces: usize) -> Self {
+        self.tail_sampling = Some(TailSampling::new(timeout, max_traces));
+        self
+    }
+
+    /// Forces traces to be kept, regardless of sampling, once any of their spans or events
+    /// records the given field as `true`, e.g. `tracing::info!(force_sample = true)`.
+    ///
+    /// Only applies to spans and events reported after the field was seen, so earlier spans
+    /// and events in the trace may already have been dropped. Combine with
+    /// [`with_tail_sampling`](Self::with_tail_sampling) to keep the whole trace.
+    ///
+    /// Forced traces are remembered until their local root span (see
+    /// `register_dist_tracing_root`) closes, or their tail sampling decision is made.
+    pub fn with_force_sample_field(mut self, field: &'static str) -> Self {
+        self.force_sample_field = Some(field);
+        self
+    }
+
+    /// Sets the [`Clock`] used to timestamp spans and events, and so to compute span durations.
+    ///
+    /// Defaults to [`SystemClock`]. A [`ManualClock`] makes timestamps and `duration_ms`
+    /// reproducible, e.g. for golden-file tests.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Adds the given fields to every reported span and event, e.g. the deployed version or
+    /// region.
+    ///
+    /// Fields recorded on a span or event, and those set by this crate such as `name`, take
+    /// precedence over global fields of the same name.
+    pub fn with_global_fields(mut self, global_fields: HashMap<String, libhoney::Value>) -> Self {
+        self.global_fields = global_fields;
+        self
+    }
+
+    /// Redacts the values of the given fields, replacing them with [`REDACTED`] before they
+    /// are reported.
+    ///
+    /// Names are matched case-insensitively, and a name ending in `*` matches every field
+    /// starting with the rest of it. See [`RedactedFields`].
+    pub fn with_redacted_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.redacted_fields = Some(RedactedFields::new(fields));
+        self
+    }
+
+    /// Maps the names of recorded fields to the keys they are reported under, e.g. to prefix
+    /// them with `app.` to namespace them away from Honeycomb's `trace.*` columns.
+    ///
+    /// By default field names are reported unchanged. The keys set by this crate, such as
+    /// the span and trace ids, `name` and `duration_ms`, are exempt, as is [`DATASET_FIELD`];
+    /// see [`KeyMapping`].
+    pub fn with_key_mapping<F>(mut self, key_mapping: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.key_mapping = Some(KeyMapping::new(key_mapping));
+        self
+    }
+
+    /// Sets the maximum length, in bytes, of recorded string field values.
+    ///
+    /// Longer values are truncated on a char boundary and suffixed with `...`, which counts
+    /// towards the maximum, and a sibling `<name>.truncated = true` field is added. Defaults
+    /// to [`DEFAULT_MAX_FIELD_LEN`].
+    pub fn with_max_field_len(mut self, max_field_len: usize) -> Self {
+        self.max_field_len = max_field_len;
+        self
+    }
+
+    /// Escapes control characters in recorded string field values, other than tab and
+    /// newline, e.g. NUL bytes or the ANSI escape sequences of colored output from untrusted
+    /// input, which can break Honeycomb's ingestion or log viewers.
+    ///
+    /// Each such character is replaced with its Rust escape, e.g. `\u{1b}`, before the value
+    /// is truncated. Values are always valid UTF-8, as `tracing` records them as `str`, so
+    /// need no further scrubbing. Fields recorded as `Debug` are escaped too, but not strings
+    /// nested in [`valuable`](https://docs.rs/valuable) values reported as nested JSON, nor
+    /// global fields. Disabled by default, so that values are reported unchanged.
+    pub fn with_escape_control_chars(mut self, escape_control_chars: bool) -> Self {
+        self.escape_control_chars = escape_control_chars;
+        self
+    }
+
+    /// Sets the maximum number of sources recorded for error field values.
+    ///
+    /// Fields recorded as a `&dyn std::error::Error` are reported as `<name>` and
+    /// `<name>.message`, along with up to this many messages from their `source` chain as
+    /// `<name>.source.0`, `<name>.source.1`, etc. Defaults to [`DEFAULT_MAX_ERROR_SOURCES`].
+    pub fn with_max_error_sources(mut self, max_error_sources: usize) -> Self {
+        self.max_error_sources = max_error_sources;
+        self
+    }
+
+    /// Caps the number of recorded fields reported per span or event, e.g. to limit the cost of
+    /// over-instrumented third-party spans.
+    ///
+    /// The first `max_fields` fields, sorted by name, are kept and the rest are dropped, with
+    /// their number reported as [`fields::FIELDS_DROPPED`]. Baggage reported as fields is
+    /// counted alike. The fields set by this crate, listed in [`fields`], as well as
+    /// [`SERVICE_NAME_FIELD`], [`DATASET_FIELD`] and global fields, are neither counted nor
+    /// dropped. Unlimited by default.
+    pub fn with_max_fields(mut self, max_fields: usize) -> Self {
+        self.max_fields = Some(max_fields);
+        self
+    }
+
+    /// Converts the values of fields with the given name before they are reported, e.g. with
+    /// [`duration_as_ms`] to report `tracing::info!(elapsed = ?duration)` as a number of
+    /// milliseconds rather than a string such as `1.5s`.
+    ///
+    /// Only applies to values recorded via their `Debug` implementation, see
+    /// [`FieldConverter`]. Names are matched exactly, and redaction takes precedence.
+    pub fn with_field_converter<F>(mut self, name: impl Into<String>, converter: F) -> Self
+    where
+        F: Fn(&dyn std::fmt::Debug) -> libhoney::Value + Send + Sync + 'static,
+    {
+        self.field_converters
+            .insert(name.into(), FieldConverter::new(converter));
+        self
+    }
+
+    /// Coerces the values of fields with the given name to `field_type` before they are
+    /// reported, e.g. so that a field sometimes recorded as a number and sometimes as a string
+    /// always lands in a column of the same type. See [`FieldType`] for the coercion rules.
+    ///
+    /// Names are matched exactly, and apply to the value recorded for the field itself, after
+    /// any [`FieldConverter`], not to the derived fields of errors or structured values.
+    /// Redaction takes precedence.
+    pub fn with_field_type(mut self, name: impl Into<String>, field_type: FieldType) -> Self {
+        self.field_types.insert(name.into(), field_type);
+        self
+    }
+
+    /// Coerces the values of each named field to its [`FieldType`], as
+    /// [`with_field_type`](Self::with_field_type) does for a single field.
+    pub fn with_field_types(mut self, field_types: HashMap<String, FieldType>) -> Self {
+        self.field_types.extend(field_types);
+        self
+    }
+
+    /// Drops spans which closed sooner than `min_span_duration` after they were opened, e.g.
+    /// sub-millisecond spans adding volume but little insight. Spans lasting exactly
+    /// `min_span_duration` are kept.
+    ///
+    /// Unlike sampling, this drops individual spans rather than whole traces, and the spans
+    /// are dropped before being passed to the sampler, nor counted in [`SamplingStats`]. The
+    /// local root span of a trace is always kept. Events are reported as they occur, before
+    /// the span they occurred in closes, so the events of a dropped span are still reported,
+    /// as are its child spans if long enough, each with a `trace.parent_id` which Honeycomb
+    /// shows as a missing span. Spans close after their children, so those are usually
+    /// dropped too.
+    pub fn with_min_span_duration(mut self, min_span_duration: std::time::Duration) -> Self {
+        self.min_span_duration = Some(min_span_duration);
+        self
+    }
+
+    /// Sets whether spans report how long they were entered for as [`fields::BUSY_MS`], and
+    /// the rest of their [`fields::DURATION_MS`] as [`fields::IDLE_MS`], e.g. to tell time
+    /// spent working in a future from time spent awaiting. Disabled by default.
+    ///
+    /// Tracking reads the clock and updates the span each time it is entered or exited, which
+    /// for futures is on every poll, so it adds overhead proportional to how often spans are
+    /// entered rather than to how many there are.
+    pub fn with_busy_time(mut self, track_busy_time: bool) -> Self {
+        self.track_busy_time = track_busy_time;
         self
     }
 
+    /// Drops events less severe than `min_level`, e.g. `tracing::Level::INFO` to only report
+    /// `INFO`, `WARN` and `ERROR` events, independently of any filter applied to the subscriber
+    /// as a whole, such as one enabling `DEBUG` logs to the console.
+    ///
+    /// Events are filtered before their fields are recorded, and so before sampling: dropped
+    /// events are not passed to the sampler, nor counted in [`SamplingStats`]. Spans are
+    /// reported regardless of their level, so that the traces of reported events stay connected.
+    pub fn with_min_level(mut self, min_level: tracing::Level) -> Self {
+        self.min_level = Some(min_level);
+        self
+    }
+
+    /// Additionally reports each span and event's timestamp as a field with the given name and
+    /// format, e.g. `with_timestamp_field("timestamp_ms", TimestampFormat::EpochMillis)` for
+    /// pipelines expecting epoch milliseconds.
+    ///
+    /// Not needed for Honeycomb, to which reporters send the timestamp as its `time` column in
+    /// RFC 3339, so no field is set by default. The field takes precedence over a span or
+    /// event field or global field of the same name.
+    pub fn with_timestamp_field(
+        mut self,
+        name: impl Into<String>,
+        format: TimestampFormat,
+    ) -> Self {
+        self.timestamp_field = Some((name.into(), format));
+        self
+    }
+
+    /// Reports the status of each span as configured by `status_mapping`, e.g.
+    /// `StatusMapping::ErrorField` to set `error = true` on spans in which `tracing::error!`
+    /// was called. See [`StatusMapping`] for when a span is considered to have failed.
+    ///
+    /// No status is reported by default, so spans only carry the fields they record.
+    pub fn with_status_mapping(mut self, status_mapping: StatusMapping) -> Self {
+        self.status_mapping = Some(status_mapping);
+        self
+    }
+
+    /// Reports the message of each event, formatted from the format string passed to a macro
+    /// such as `tracing::info!("hello {}", name)`, under the given name rather than
+    /// [`message`](fields::MESSAGE), e.g. `body` as in OpenTelemetry's log data model.
+    ///
+    /// Takes precedence over an event field of the same name. Spans, which have no message,
+    /// are unaffected.
+    pub fn with_message_field(mut self, name: impl Into<String>) -> Self {
+        self.message_field = Some(name.into());
+        self
+    }
+
+    /// Reports the source location of the callsite of each span and event, as
+    /// [`code.filepath`](fields::CODE_FILEPATH), [`code.lineno`](fields::CODE_LINENO) and
+    /// [`code.namespace`](fields::CODE_NAMESPACE), following OpenTelemetry's conventions.
+    ///
+    /// Disabled by default, as every callsite adds distinct values to these columns.
+    pub fn with_source_location(mut self, source_location: bool) -> Self {
+        self.source_location = source_location;
+        self
+    }
+
+    /// Sets the library name and version reported on each span and event as
+    /// [`library.name`](fields::LIBRARY_NAME) and [`library.version`](fields::LIBRARY_VERSION).
+    ///
+    /// Defaults to `tracing-honeycomb` and the version of this crate. Useful when wrapping this
+    /// crate in another, so that the data can be traced back to it.
+    pub fn with_library(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.library = (name.into(), version.into());
+        self
+    }
+
+    /// Sets whether spans and events are reported with their [`trace.trace_id`](fields::TRACE_ID),
+    /// [`trace.span_id`](fields::SPAN_ID) and [`trace.parent_id`](fields::PARENT_ID), e.g. to
+    /// omit them when using a [`StdoutReporter`] as a plain structured logger. Enabled by default.
+    ///
+    /// Traces are still tracked and sampled by their ids, which are only left out of the
+    /// reported data. Without them, Honeycomb cannot assemble the spans into traces.
+    pub fn with_trace_id_fields(mut self, trace_id_fields: bool) -> Self {
+        self.trace_id_fields = trace_id_fields;
+        self
+    }
+
+    /// Sets the maximum depth to which structured field values are flattened.
+    ///
+    /// Fields recorded as a [`valuable::Valuable`] struct, map, list or enum are reported as
+    /// one field per leaf value, keyed by its dotted path, e.g. `user.id` and `user.role`.
+    /// Values nested deeper than this are reported as their `Debug` representation instead.
+    /// Defaults to [`DEFAULT_MAX_FLATTEN_DEPTH`].
+    ///
+    /// Requires the `use_valuable` feature, and building with `--cfg tracing_unstable`.
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    pub fn with_max_flatten_depth(mut self, max_flatten_depth: usize) -> Self {
+        self.max_flatten_depth = max_flatten_depth;
+        self
+    }
+
+    /// Reports fields recorded as a [`valuable::Valuable`] struct, map, list or enum as a single
+    /// field holding nested JSON, e.g. `user = {"id": 1, "role": "admin"}`, rather than
+    /// flattening them into one field per leaf value.
+    ///
+    /// Structs and maps are reported as objects, lists and tuples as arrays, and enums as
+    /// their variant name, or as an object with their variant name as the only key if the
+    /// variant has fields, e.g. `{"Member": {"team": "core"}}`. Values nested deeper than
+    /// [`with_max_flatten_depth`](Self::with_max_flatten_depth) are reported as their `Debug`
+    /// representation. Honeycomb stores nested JSON as a string column unless the dataset is
+    /// set to unpack nested JSON.
+    ///
+    /// Requires the `use_valuable` feature, and building with `--cfg tracing_unstable`.
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    pub fn with_nested_values(mut self, nest_values: bool) -> Self {
+        self.nest_values = nest_values;
+        self
+    }
+
+    /// Reports an event marking the start of each trace, as its root span is registered via
+    /// [`register_dist_tracing_root`], e.g. to alert on incoming requests as they arrive
+    /// rather than once their root span closes.
+    ///
+    /// The event is reported with [`meta.annotation_type`](fields::ANNOTATION_TYPE) set to
+    /// [`trace_start`](fields::TRACE_START), named after the root span and carrying the fields
+    /// recorded on it so far, including those passed to
+    /// [`register_dist_tracing_root_with_fields`]. It is sampled along with the rest of its
+    /// trace, so not reported for dropped traces, and counts as an event in
+    /// [`SamplingStats`].
+    ///
+    /// Disabled by default, as it adds an event to every trace, i.e. one more event per
+    /// request for a service whose traces each handle a request. The event is reported
+    /// directly, with the [`TRACE_START_TARGET`] target and the level of the root span, rather
+    /// than emitted as a `tracing` event, so neither other layers nor filters see it, and
+    /// [`Builder::with_min_level`] does not apply to it.
+    pub fn with_trace_start_events(mut self, trace_start: bool) -> Self {
+        self.trace_start = trace_start;
+        self
+    }
+
+    /// Reports span ids assigned from a counter, starting at `1` in the order the layer first
+    /// sees each span, rather than the ids assigned by the `tracing_subscriber::Registry`,
+    /// which vary between runs, e.g. with the threads spans are opened on. Spans opened in the
+    /// same order are then reported with the same [`trace.span_id`](fields::SPAN_ID) and
+    /// [`trace.parent_id`](fields::PARENT_ID) values on every run, for golden-file tests
+    /// together with a [`ManualClock`].
+    ///
+    /// For tests only: the layer remembers every span it has seen for as long as it lives, and
+    /// ids are only predictable if spans are opened, entered and closed in a fixed order, so
+    /// not across threads racing each other. A span seen first as the parent of an event or
+    /// another span is numbered then, rather than when it was opened. Ids are only assigned
+    /// by this layer, so are unrelated to those of another process or layer.
+    ///
+    /// Requires the `deterministic_span_ids` feature.
+    #[cfg(feature = "deterministic_span_ids")]
+    pub fn with_deterministic_span_ids(mut self, deterministic_span_ids: bool) -> Self {
+        self.deterministic_span_ids = deterministic_span_ids;
+        self
+    }
+
+    /// Constructs the configured `TelemetryLayer`, along with a [`FlushGuard`] which flushes
+    /// its reporter when dropped.
+    ///
+    /// See [`FlushGuard`] for when to drop the guard.
+    pub fn build_with_flush_guard(
+        self,
+    ) -> (
+        TelemetryLayer<HoneycombTelemetry<Arc<R>>, SpanId, TraceId>,
+        FlushGuard,
+    )
+    where
+        R: Reporter + Send + Sync + 'static,
+    {
+        let reporter = Arc::new(self.reporter);
+        let guard = FlushGuard::new(reporter.clone());
+        let builder = Builder {
+            reporter,
+            sampler: self.sampler,
+            error_handling: self.error_handling,
+            tail_sampling: self.tail_sampling,
+            redacted_fields: self.redacted_fields,
+            key_mapping: self.key_mapping,
+            max_field_len: self.max_field_len,
+            escape_control_chars: self.escape_control_chars,
+            max_error_sources: self.max_error_sources,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            max_flatten_depth: self.max_flatten_depth,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            nest_values: self.nest_values,
+            force_sample_field: self.force_sample_field,
+            clock: self.clock,
+            global_fields: self.global_fields,
+            max_fields: self.max_fields,
+            field_converters: self.field_converters,
+            field_types: self.field_types,
+            sampling_stats: self.sampling_stats,
+            timestamp_field: self.timestamp_field,
+            min_level: self.min_level,
+            min_span_duration: self.min_span_duration,
+            track_busy_time: self.track_busy_time,
+            status_mapping: self.status_mapping,
+            message_field: self.message_field,
+            source_location: self.source_location,
+            library: self.library,
+            trace_id_fields: self.trace_id_fields,
+            trace_start: self.trace_start,
+            #[cfg(feature = "deterministic_span_ids")]
+            deterministic_span_ids: self.deterministic_span_ids,
+            service_name: self.service_name,
+        };
+
+        let layer = builder.build();
+        let guard = guard.with_decide_pending(layer.telemetry().pending_decider());
+        (layer, guard)
+    }
+
     /// Constructs the configured `TelemetryLayer`
     pub fn build(self) -> TelemetryLayer<HoneycombTelemetry<R>, SpanId, TraceId> {
-        TelemetryLayer::new(
-            self.service_name,
-            HoneycombTelemetry::new(self.reporter, self.sample_rate),
-            move |tracing_id| SpanId { tracing_id },
-        )
+        let service_name = self.service_name;
+        #[cfg(feature = "deterministic_span_ids")]
+        let deterministic_span_ids = self.deterministic_span_ids;
+        let telemetry = self.build_telemetry();
+
+        #[cfg(feature = "deterministic_span_ids")]
+        {
+            if deterministic_span_ids {
+                let ids = span_id::DeterministicSpanIds::default();
+                return TelemetryLayer::new(service_name, telemetry, move |tracing_id| {
+                    ids.promote(tracing_id)
+                });
+            }
+        }
+
+        TelemetryLayer::new(service_name, telemetry, move |tracing_id| SpanId {
+            tracing_id,
+        })
+    }
+
+    /// Constructs the configured [`HoneycombTelemetry`] on its own, e.g. to combine two in a
+    /// [`TeeTelemetry`] via [`new_honeycomb_telemetry_layer_with_tee`].
+    ///
+    /// The service name and options applying to the layer, such as
+    /// [`with_deterministic_span_ids`](Self::with_deterministic_span_ids), are left to the
+    /// layer the telemetry is used in.
+    // only returned as is without the `use_valuable` options
+    #[allow(clippy::let_and_return)]
+    pub fn build_telemetry(self) -> HoneycombTelemetry<R> {
+        let telemetry = HoneycombTelemetry::new(self.reporter, self.sampler)
+            .with_error_handling(self.error_handling)
+            .with_tail_sampling(self.tail_sampling)
+            .with_redacted_fields(self.redacted_fields)
+            .with_key_mapping(self.key_mapping)
+            .with_max_field_len(self.max_field_len)
+            .with_escape_control_chars(self.escape_control_chars)
+            .with_max_error_sources(self.max_error_sources)
+            .with_force_sample_field(self.force_sample_field)
+            .with_clock(self.clock)
+            .with_global_fields(self.global_fields)
+            .with_max_fields(self.max_fields)
+            .with_field_converters(self.field_converters)
+            .with_field_types(self.field_types)
+            .with_sampling_stats(self.sampling_stats)
+            .with_timestamp_field(self.timestamp_field)
+            .with_min_level(self.min_level)
+            .with_min_span_duration(self.min_span_duration)
+            .with_busy_time(self.track_busy_time)
+            .with_status_mapping(self.status_mapping)
+            .with_message_field(self.message_field)
+            .with_source_location(self.source_location)
+            .with_library(self.library)
+            .with_trace_id_fields(self.trace_id_fields)
+            .with_trace_start(self.trace_start);
+        #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+        let telemetry = telemetry
+            .with_max_flatten_depth(self.max_flatten_depth)
+            .with_nested_values(self.nest_values);
+
+        telemetry
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn new_honeycomb_telemetry_layer_with_sampler_uses_sampler() {
+        // nothing listens on this port, so sent data is dropped
+        let config = libhoney::Config {
+            options: libhoney::client::Options {
+                api_key: "key".to_string(),
+                dataset: "dataset".to_string(),
+                api_host: "http://127.0.0.1:1".to_string(),
+                ..libhoney::client::Options::default()
+            },
+            transmission_options: libhoney::transmission::Options::default(),
+        };
+        let kept = TraceId::from("kept");
+        let sampler = {
+            let kept = kept.clone();
+            move |trace_id: &TraceId| *trace_id == kept
+        };
+        let layer = new_honeycomb_telemetry_layer_with_sampler("test", config, sampler);
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for (trace_id, sampled) in [(kept, true), (TraceId::from("dropped"), false)] {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                register_dist_tracing_root(trace_id, None).unwrap();
+                assert_eq!(is_current_trace_sampled(), Some(sampled));
+            }
+        });
+    }
+
+    #[test]
+    fn disables_libhoney_sampling() {
+        let mut config = libhoney::Config {
+            options: libhoney::client::Options::default(),
+            transmission_options: libhoney::transmission::Options::default(),
+        };
+        config.options.sample_rate = 10;
+        let config = super::without_libhoney_sampling(config);
+        assert_eq!(config.options.sample_rate, 1);
+    }
+
+    #[test]
+    fn parses_sample_rate_from_env_var() {
+        let parse = |value: &str| super::parse_sample_rate(Some(value.into()));
+        assert_eq!(super::parse_sample_rate(None), Ok(1));
+        assert_eq!(parse("10"), Ok(10));
+        assert_eq!(parse(" 20\n"), Ok(20));
+        assert_eq!(parse("0"), Ok(0));
+        for invalid in ["", "-1", "0.5", "ten", "4294967296"].iter() {
+            assert_eq!(
+                parse(invalid),
+                Err(InitError::InvalidSampleRate(invalid.to_string()))
+            );
+        }
     }
 }
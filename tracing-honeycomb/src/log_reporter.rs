@@ -0,0 +1,235 @@
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::fields;
+use crate::reporter::{ReportError, Reporter, DATASET_FIELD};
+
+/// Target of the records logged by [`LogReporter`].
+pub const LOG_REPORTER_TARGET: &str = "tracing_honeycomb::logged";
+
+/// Reporter that logs each span and event as a record via the `log` crate, e.g. to keep an
+/// existing `log` based pipeline fed while migrating to `tracing`.
+///
+/// Records are logged with the [`LOG_REPORTER_TARGET`] target, at the `log` level matching the
+/// reported [`LEVEL`](crate::fields::LEVEL) field, or `Info` if it is missing or not a
+/// `tracing` level. The record's message is the reported [`MESSAGE`](crate::fields::MESSAGE)
+/// field, or the [`NAME`](crate::fields::NAME) of the span or event if it has no message. All
+/// other fields are attached as structured key-values, strings, booleans and numbers as such
+/// and other values as JSON. For loggers which do not support key-values,
+/// [`with_inline_fields`](Self::with_inline_fields) appends the fields to the message instead.
+///
+/// If the application also converts `log` records to `tracing` events, e.g. with
+/// `tracing_log::LogTracer`, exclude the [`LOG_REPORTER_TARGET`] target from conversion, as the
+/// `TelemetryLayer` would otherwise report each logged record again.
+///
+/// Requires the `use_log` feature.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogReporter {
+    inline_fields: bool,
+}
+
+impl LogReporter {
+    /// Returns a new `LogReporter`, attaching fields as structured key-values.
+    pub fn new() -> Self {
+        LogReporter::default()
+    }
+
+    /// Sets whether the fields are also appended to the message, as space separated
+    /// `name=value` pairs sorted by name, for loggers which ignore structured key-values.
+    pub fn with_inline_fields(mut self, inline_fields: bool) -> Self {
+        self.inline_fields = inline_fields;
+        self
+    }
+}
+
+impl Reporter for LogReporter {
+    fn report_data(
+        &self,
+        mut data: HashMap<String, libhoney::Value>,
+        _timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        let level = log_level(data.get(fields::LEVEL));
+        if level > log::max_level() {
+            return Ok(());
+        }
+
+        // honeycomb-specific, only used to route data to a dataset
+        data.remove(DATASET_FIELD);
+
+        let mut message = match data.remove(fields::MESSAGE) {
+            Some(libhoney::Value::String(message)) => message,
+            Some(message) => message.to_string(),
+            None => match data.get(fields::NAME) {
+                Some(libhoney::Value::String(name)) => name.clone(),
+                _ => String::new(),
+            },
+        };
+        if self.inline_fields {
+            for (name, value) in data.iter().collect::<BTreeMap<_, _>>() {
+                match value {
+                    libhoney::Value::String(value) => write!(message, " {}={:?}", name, value),
+                    value => write!(message, " {}={}", name, value),
+                }
+                .expect("writing to a String cannot fail");
+            }
+        }
+
+        log::logger().log(
+            &log::Record::builder()
+                .level(level)
+                .target(LOG_REPORTER_TARGET)
+                .args(format_args!("{}", message))
+                .key_values(&KeyValues(&data))
+                .build(),
+        );
+        Ok(())
+    }
+}
+
+// maps a reported `tracing` level to the matching `log` level
+fn log_level(level: Option<&libhoney::Value>) -> log::Level {
+    match level.and_then(libhoney::Value::as_str) {
+        Some("ERROR") => log::Level::Error,
+        Some("WARN") => log::Level::Warn,
+        Some("DEBUG") => log::Level::Debug,
+        Some("TRACE") => log::Level::Trace,
+        _ => log::Level::Info,
+    }
+}
+
+// the reported fields, as the key-values of a `log` record
+struct KeyValues<'a>(&'a HashMap<String, libhoney::Value>);
+
+impl<'a> log::kv::Source for KeyValues<'a> {
+    fn visit<'kvs>(
+        &'kvs self,
+        visitor: &mut dyn log::kv::VisitSource<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        for (name, value) in self.0 {
+            let value = match value {
+                libhoney::Value::String(value) => log::kv::Value::from(value.as_str()),
+                libhoney::Value::Bool(value) => log::kv::Value::from(*value),
+                libhoney::Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+                    (Some(value), _) => log::kv::Value::from(value),
+                    (None, Some(value)) => log::kv::Value::from(value),
+                    _ => log::kv::Value::from(number.as_f64().unwrap_or(f64::NAN)),
+                },
+                value => log::kv::Value::from_display(value),
+            };
+            visitor.visit_pair(log::kv::Key::from_str(name), value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, OnceLock};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // the message, level and `user_id` key-value of each record logged by a `LogReporter`
+    type Logged = Arc<std::sync::Mutex<Vec<(String, log::Level, Option<String>)>>>;
+
+    struct TestLogger(Logged);
+
+    impl log::Log for TestLogger {
+        fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            if record.target() == LOG_REPORTER_TARGET {
+                let user_id = record
+                    .key_values()
+                    .get(log::kv::Key::from_str("user_id"))
+                    .map(|value| value.to_string());
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((record.args().to_string(), record.level(), user_id));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    // the `log` logger is global, so it is set once, and each test filters by message
+    fn logged() -> Logged {
+        static LOGGED: OnceLock<Logged> = OnceLock::new();
+        LOGGED
+            .get_or_init(|| {
+                let logged = Logged::default();
+                log::set_boxed_logger(Box::new(TestLogger(logged.clone()))).unwrap();
+                log::set_max_level(log::LevelFilter::Trace);
+                logged
+            })
+            .clone()
+    }
+
+    #[test]
+    fn logs_with_matching_level_and_key_values() {
+        let logged = logged();
+        let layer = crate::Builder::new("test", LogReporter::new()).build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::warn_span!("log_reporter_root", user_id = 42);
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(crate::TraceId::new(), None).unwrap();
+            tracing::error!(user_id = "abc", "log_reporter_event");
+        });
+
+        let logged: Vec<_> = logged
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(message, ..)| {
+                message == "log_reporter_event" || message == "log_reporter_root"
+            })
+            .cloned()
+            .collect();
+        assert_eq!(
+            logged,
+            vec![
+                (
+                    "log_reporter_event".to_string(),
+                    log::Level::Error,
+                    Some("abc".to_string())
+                ),
+                (
+                    "log_reporter_root".to_string(),
+                    log::Level::Warn,
+                    Some("42".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn inlines_fields_into_message() {
+        let logged = logged();
+        let reporter = LogReporter::new().with_inline_fields(true);
+        let data: HashMap<_, _> = vec![
+            (fields::NAME.to_string(), json!("log_reporter_inline")),
+            (fields::LEVEL.to_string(), json!("DEBUG")),
+            ("user_id".to_string(), json!(42)),
+        ]
+        .into_iter()
+        .collect();
+        reporter.report_data(data, Utc::now()).unwrap();
+
+        let logged = logged.lock().unwrap();
+        let (message, level, _) = logged
+            .iter()
+            .find(|(message, ..)| message.starts_with("log_reporter_inline"))
+            .unwrap();
+        assert_eq!(
+            message,
+            "log_reporter_inline level=\"DEBUG\" name=\"log_reporter_inline\" user_id=42"
+        );
+        assert_eq!(*level, log::Level::Debug);
+    }
+}
@@ -0,0 +1,630 @@
+use chrono::{DateTime, Utc};
+use libhoney::{json, Value};
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    logs_service_client::LogsServiceClient, ExportLogsServiceRequest,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    trace_service_client::TraceServiceClient, ExportTraceServiceRequest,
+};
+use opentelemetry_proto::tonic::common::v1::{
+    any_value, AnyValue, ArrayValue, InstrumentationScope, KeyValue, KeyValueList,
+};
+use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs, SeverityNumber};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use opentelemetry_proto::tonic::trace::v1::{
+    span::SpanKind, status::StatusCode, ResourceSpans, ScopeSpans, Span, Status,
+};
+use otlp_tokio::runtime::Runtime;
+use otlp_tokio::task::JoinHandle;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use tonic::transport::{Channel, Endpoint};
+
+#[cfg(feature = "use_parking_lot")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "use_parking_lot"))]
+use std::sync::{Mutex, PoisonError};
+
+use crate::reporter::{ReportError, Reporter, DATASET_FIELD};
+use crate::visitor::is_link;
+use crate::{fields, TraceId};
+
+type Data = (HashMap<String, Value>, DateTime<Utc>);
+
+const NANOS_PER_MILLI: f64 = 1_000_000_f64;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Reporter that exports spans and events to an OpenTelemetry collector over OTLP/gRPC
+///
+/// Spans are exported as OTLP spans, and events as OTLP log records attached to their parent
+/// span. The fields set by this crate are mapped onto their OTLP counterparts:
+///
+/// - `trace.trace_id`, `trace.span_id` and `trace.parent_id` become the trace, span and parent
+///   span ids. Trace ids which are not 128-bit hex or UUID values are zero-extended if they are
+///   64-bit hex values, as with B3, and are otherwise replaced by a hash of the id.
+/// - `duration_ms` becomes the span's end time, relative to its start time.
+/// - `service_name` becomes the `service.name` resource attribute.
+/// - `name` becomes the span name.
+/// - `level` becomes the log record's severity. Spans at the `ERROR` level, or recording
+///   `error = true`, get an error status.
+/// - `message` becomes the log record's body.
+///
+/// All other fields, including `level` on spans, become attributes. Span links, reported
+/// separately from the linking span, are not exported.
+///
+/// Exports run in the background, on a runtime owned by the reporter, so reporting does not
+/// block on the network. Export failures are logged to stderr. Each report is exported in its
+/// own request, so wrapping this in a [`BatchingReporter`](crate::BatchingReporter) is
+/// recommended.
+///
+/// Exports still in flight when the reporter is dropped are waited for before its runtime is
+/// shut down. If it is dropped from within an asynchronous execution context, where blocking
+/// would panic, they are waited for on a separate thread instead.
+///
+/// Requires the `use_otlp` feature.
+#[derive(Debug)]
+pub struct OtlpReporter {
+    traces: TraceServiceClient<Channel>,
+    logs: LogsServiceClient<Channel>,
+    // only `None` once dropped
+    runtime: Option<Runtime>,
+    in_flight: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl OtlpReporter {
+    /// Returns a new `OtlpReporter` exporting to the OTLP/gRPC collector at `endpoint`, e.g.
+    /// `http://localhost:4317`.
+    ///
+    /// The connection is made lazily, on the first export.
+    pub fn connect(endpoint: impl Into<String>) -> Result<Self, ReportError> {
+        let endpoint = Endpoint::from_shared(endpoint.into())?;
+        let runtime = otlp_tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("tracing-honeycomb-otlp")
+            .enable_all()
+            .build()?;
+
+        // the channel's worker task is spawned onto the current runtime
+        let channel = {
+            let _guard = runtime.enter();
+            endpoint.connect_lazy()
+        };
+
+        Ok(OtlpReporter {
+            traces: TraceServiceClient::new(channel.clone()),
+            logs: LogsServiceClient::new(channel),
+            runtime: Some(runtime),
+            in_flight: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn runtime(&self) -> &Runtime {
+        self.runtime
+            .as_ref()
+            .expect("runtime is only taken on drop")
+    }
+
+    fn take_in_flight(&self) -> Vec<JoinHandle<()>> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut in_flight = self.in_flight.lock();
+
+        std::mem::take(&mut *in_flight)
+    }
+
+    fn export(&self, batch: Vec<Data>) {
+        // grouped by service name, which may be overridden per span or event
+        let mut spans: HashMap<String, Vec<Span>> = HashMap::new();
+        let mut log_records: HashMap<String, Vec<LogRecord>> = HashMap::new();
+        for (mut data, timestamp) in batch {
+            if is_link(&data) {
+                continue;
+            }
+
+            let service_name = take_string(&mut data, fields::SERVICE_NAME);
+            if data.contains_key(fields::SPAN_ID) {
+                let span = to_span(data, timestamp);
+                spans.entry(service_name).or_default().push(span);
+            } else {
+                let log_record = to_log_record(data, timestamp);
+                log_records
+                    .entry(service_name)
+                    .or_default()
+                    .push(log_record);
+            }
+        }
+
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut in_flight = self.in_flight.lock();
+
+        in_flight.retain(|handle| !handle.is_finished());
+
+        if !spans.is_empty() {
+            let request = ExportTraceServiceRequest {
+                resource_spans: spans
+                    .into_iter()
+                    .map(|(service_name, spans)| ResourceSpans {
+                        resource: Some(resource(service_name)),
+                        scope_spans: vec![ScopeSpans {
+                            scope: Some(scope()),
+                            spans,
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    })
+                    .collect(),
+            };
+            let mut client = self.traces.clone();
+            in_flight.push(self.runtime().spawn(async move {
+                if let Err(status) = client.export(request).await {
+                    // no caller to hand the error back to, so log msg to stderr
+                    eprintln!("error exporting spans to OTLP collector, {}", status);
+                }
+            }));
+        }
+
+        if !log_records.is_empty() {
+            let request = ExportLogsServiceRequest {
+                resource_logs: log_records
+                    .into_iter()
+                    .map(|(service_name, log_records)| ResourceLogs {
+                        resource: Some(resource(service_name)),
+                        scope_logs: vec![ScopeLogs {
+                            scope: Some(scope()),
+                            log_records,
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    })
+                    .collect(),
+            };
+            let mut client = self.logs.clone();
+            in_flight.push(self.runtime().spawn(async move {
+                if let Err(status) = client.export(request).await {
+                    // no caller to hand the error back to, so log msg to stderr
+                    eprintln!("error exporting events to OTLP collector, {}", status);
+                }
+            }));
+        }
+    }
+}
+
+impl Reporter for OtlpReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        self.export(vec![(data, timestamp)]);
+        Ok(())
+    }
+
+    /// Exports the whole batch in at most two requests, one for spans and one for events.
+    fn report_batch(&self, batch: Vec<Data>) -> Result<(), ReportError> {
+        self.export(batch);
+        Ok(())
+    }
+
+    /// Blocks until all exports started so far have completed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within an asynchronous execution context.
+    fn flush(&self) -> Result<(), ReportError> {
+        drain(self.runtime(), self.take_in_flight());
+        Ok(())
+    }
+}
+
+impl Drop for OtlpReporter {
+    fn drop(&mut self) {
+        let in_flight = self.take_in_flight();
+        let runtime = match self.runtime.take() {
+            Some(runtime) => runtime,
+            None => return,
+        };
+
+        // dropping or blocking on a runtime from within an asynchronous execution context
+        // panics, so finish up on another thread there
+        let finish = move || {
+            drain(&runtime, in_flight);
+            runtime.shutdown_background();
+        };
+        if otlp_tokio::runtime::Handle::try_current().is_ok() {
+            std::thread::spawn(finish);
+        } else {
+            finish();
+        }
+    }
+}
+
+// waits for the given exports to complete
+fn drain(runtime: &Runtime, in_flight: Vec<JoinHandle<()>>) {
+    for handle in in_flight {
+        // export errors are logged by the task itself
+        let _ = runtime.block_on(handle);
+    }
+}
+
+// whether the span recorded `error = true` or was at the `ERROR` level
+fn is_error(data: &HashMap<String, Value>) -> bool {
+    data.get(fields::ERROR) == Some(&json!(true))
+        || data.get(fields::LEVEL) == Some(&json!("ERROR"))
+}
+
+fn to_span(mut data: HashMap<String, Value>, timestamp: DateTime<Utc>) -> Span {
+    let trace_id = take_trace_id(&mut data);
+    let span_id = take_span_id(&mut data, fields::SPAN_ID);
+    let parent_span_id = take_span_id(&mut data, fields::PARENT_ID);
+    let name = take_string(&mut data, fields::NAME);
+    // honeycomb-specific, implied by the span's place in the trace
+    data.remove(fields::SPAN_TYPE);
+    // honeycomb-specific, only used to route data to a dataset
+    data.remove(DATASET_FIELD);
+    let kind = match data
+        .remove(fields::SPAN_KIND)
+        .as_ref()
+        .and_then(Value::as_str)
+    {
+        Some("server") => SpanKind::Server,
+        Some("client") => SpanKind::Client,
+        Some("producer") => SpanKind::Producer,
+        Some("consumer") => SpanKind::Consumer,
+        _ => SpanKind::Internal,
+    };
+    let duration_ms = data.remove(fields::DURATION_MS).and_then(|d| d.as_f64());
+    let status_code = if is_error(&data) {
+        StatusCode::Error
+    } else {
+        StatusCode::Unset
+    };
+
+    let start_time_unix_nano = unix_nanos(timestamp);
+    let duration_nanos = (duration_ms.unwrap_or_default() * NANOS_PER_MILLI) as u64;
+
+    Span {
+        trace_id,
+        span_id,
+        parent_span_id,
+        name,
+        kind: kind as i32,
+        start_time_unix_nano,
+        end_time_unix_nano: start_time_unix_nano + duration_nanos,
+        attributes: attributes(data),
+        status: Some(Status {
+            code: status_code as i32,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn to_log_record(mut data: HashMap<String, Value>, timestamp: DateTime<Utc>) -> LogRecord {
+    let trace_id = take_trace_id(&mut data);
+    let span_id = take_span_id(&mut data, fields::PARENT_ID);
+    // honeycomb-specific, implied by the event being a log record
+    data.remove(fields::ANNOTATION_TYPE);
+    // honeycomb-specific, only used to route data to a dataset
+    data.remove(DATASET_FIELD);
+    let severity_text = take_string(&mut data, fields::LEVEL);
+    let severity_number = match severity_text.as_str() {
+        "TRACE" => SeverityNumber::Trace,
+        "DEBUG" => SeverityNumber::Debug,
+        "INFO" => SeverityNumber::Info,
+        "WARN" => SeverityNumber::Warn,
+        "ERROR" => SeverityNumber::Error,
+        _ => SeverityNumber::Unspecified,
+    };
+    let body = data.remove("message").map(any_value);
+
+    LogRecord {
+        time_unix_nano: unix_nanos(timestamp),
+        observed_time_unix_nano: unix_nanos(timestamp),
+        severity_number: severity_number as i32,
+        severity_text,
+        body,
+        attributes: attributes(data),
+        trace_id,
+        span_id,
+        ..Default::default()
+    }
+}
+
+fn take_string(data: &mut HashMap<String, Value>, key: &str) -> String {
+    match data.remove(key) {
+        Some(Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+fn take_trace_id(data: &mut HashMap<String, Value>) -> Vec<u8> {
+    match data.remove(fields::TRACE_ID) {
+        Some(Value::String(trace_id)) => otlp_trace_id(trace_id).to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+// 16 byte OTLP trace id for a trace id of any form, see `OtlpReporter`
+fn otlp_trace_id(trace_id: String) -> [u8; 16] {
+    let trace_id = TraceId::from(trace_id);
+    if let Ok(trace_id) = u128::try_from(trace_id.clone()) {
+        return trace_id.to_be_bytes();
+    }
+
+    let trace_id = trace_id.to_string();
+    if trace_id.len() == 16 && trace_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        if let Ok(trace_id) = u64::from_str_radix(&trace_id, 16) {
+            return u128::from(trace_id).to_be_bytes();
+        }
+    }
+
+    let digest = Sha1::digest(trace_id.as_bytes());
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    bytes
+}
+
+// span ids are reported as hex, see `SpanId`'s `Display`
+fn take_span_id(data: &mut HashMap<String, Value>, key: &str) -> Vec<u8> {
+    match data.remove(key) {
+        Some(Value::String(span_id)) => u64::from_str_radix(&span_id, 16)
+            .map(|span_id| span_id.to_be_bytes().to_vec())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+fn unix_nanos(timestamp: DateTime<Utc>) -> u64 {
+    let secs = u64::try_from(timestamp.timestamp()).unwrap_or_default();
+    secs * NANOS_PER_SECOND + u64::from(timestamp.timestamp_subsec_nanos())
+}
+
+fn attributes(values: impl IntoIterator<Item = (String, Value)>) -> Vec<KeyValue> {
+    values
+        .into_iter()
+        .map(|(key, value)| KeyValue {
+            key,
+            value: Some(any_value(value)),
+        })
+        .collect()
+}
+
+fn any_value(value: Value) -> AnyValue {
+    let value = match value {
+        Value::Null => None,
+        Value::Bool(b) => Some(any_value::Value::BoolValue(b)),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Some(any_value::Value::IntValue(i)),
+            None => n.as_f64().map(any_value::Value::DoubleValue),
+        },
+        Value::String(s) => Some(any_value::Value::StringValue(s)),
+        Value::Array(values) => Some(any_value::Value::ArrayValue(ArrayValue {
+            values: values.into_iter().map(any_value).collect(),
+        })),
+        Value::Object(values) => Some(any_value::Value::KvlistValue(KeyValueList {
+            values: attributes(values),
+        })),
+    };
+
+    AnyValue { value }
+}
+
+fn resource(service_name: String) -> Resource {
+    Resource {
+        attributes: attributes(vec![(
+            "service.name".to_string(),
+            Value::String(service_name),
+        )]),
+        ..Default::default()
+    }
+}
+
+fn scope() -> InstrumentationScope {
+    InstrumentationScope {
+        name: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use libhoney::json;
+    use opentelemetry_proto::tonic::collector::logs::v1::{
+        logs_service_server::{LogsService, LogsServiceServer},
+        ExportLogsServiceResponse,
+    };
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        trace_service_server::{TraceService, TraceServiceServer},
+        ExportTraceServiceResponse,
+    };
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    fn data(pairs: &[(&str, Value)]) -> HashMap<String, Value> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn maps_span_fields() {
+        let timestamp = DateTime::parse_from_rfc3339("2021-06-28T12:00:00.5Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let span = to_span(
+            data(&[
+                ("trace.trace_id", json!("4bf92f3577b34da6a3ce929d0e0e4736")),
+                ("trace.span_id", json!("f067aa0ba902b7")),
+                ("name", json!("request")),
+                ("span.kind", json!("server")),
+                ("level", json!("ERROR")),
+                ("duration_ms", json!(1.5)),
+                ("user", json!("alice")),
+            ]),
+            timestamp,
+        );
+
+        assert_eq!(
+            span.trace_id,
+            0x4bf92f3577b34da6a3ce929d0e0e4736_u128
+                .to_be_bytes()
+                .to_vec()
+        );
+        assert_eq!(span.span_id, 0xf067aa0ba902b7_u64.to_be_bytes().to_vec());
+        assert!(span.parent_span_id.is_empty());
+        assert_eq!(span.name, "request");
+        assert_eq!(span.kind, SpanKind::Server as i32);
+        assert_eq!(span.start_time_unix_nano, 1_624_881_600_500_000_000);
+        assert_eq!(
+            span.end_time_unix_nano - span.start_time_unix_nano,
+            1_500_000
+        );
+        assert_eq!(span.status.unwrap().code, StatusCode::Error as i32);
+
+        let mut keys: Vec<_> = span.attributes.iter().map(|kv| kv.key.as_str()).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec!["level", "user"]);
+    }
+
+    #[test]
+    fn maps_event_fields() {
+        let log_record = to_log_record(
+            data(&[
+                ("trace.trace_id", json!("463ac35c9f6413ad")),
+                ("trace.parent_id", json!("f067aa0ba902b7")),
+                ("level", json!("WARN")),
+                ("message", json!("slow")),
+            ]),
+            Utc::now(),
+        );
+
+        assert_eq!(
+            log_record.trace_id,
+            0x463ac35c9f6413ad_u128.to_be_bytes().to_vec()
+        );
+        assert_eq!(
+            log_record.span_id,
+            0xf067aa0ba902b7_u64.to_be_bytes().to_vec()
+        );
+        assert_eq!(log_record.severity_number, SeverityNumber::Warn as i32);
+        assert_eq!(log_record.severity_text, "WARN");
+        assert_eq!(
+            log_record.body,
+            Some(AnyValue {
+                value: Some(any_value::Value::StringValue("slow".to_string()))
+            })
+        );
+        assert!(log_record.attributes.is_empty());
+    }
+
+    // OTLP/gRPC collector recording the requests it receives
+    #[derive(Clone, Default)]
+    struct Collector {
+        spans: Arc<std::sync::Mutex<Vec<ExportTraceServiceRequest>>>,
+        logs: Arc<std::sync::Mutex<Vec<ExportLogsServiceRequest>>>,
+    }
+
+    #[tonic::async_trait]
+    impl TraceService for Collector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            self.spans.lock().unwrap().push(request.into_inner());
+            Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl LogsService for Collector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportLogsServiceRequest>,
+        ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+            self.logs.lock().unwrap().push(request.into_inner());
+            Ok(tonic::Response::new(ExportLogsServiceResponse::default()))
+        }
+    }
+
+    // serves `collector` on a local port on `runtime`, returning its endpoint once it accepts
+    // connections
+    fn serve(runtime: &Runtime, collector: Collector) -> String {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        runtime.spawn(
+            tonic::transport::Server::builder()
+                .add_service(TraceServiceServer::new(collector.clone()))
+                .add_service(LogsServiceServer::new(collector))
+                .serve(addr),
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while std::net::TcpStream::connect(addr).is_err() {
+            assert!(Instant::now() < deadline, "collector did not start");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn exports_to_collector_before_dropping() {
+        let runtime = otlp_tokio::runtime::Runtime::new().unwrap();
+        let collector = Collector::default();
+        let endpoint = serve(&runtime, collector.clone());
+
+        let reporter = OtlpReporter::connect(endpoint.clone()).unwrap();
+        let span = data(&[
+            ("trace.trace_id", json!("4bf92f3577b34da6a3ce929d0e0e4736")),
+            ("trace.span_id", json!("f067aa0ba902b7")),
+            ("service_name", json!("test")),
+            ("name", json!("request")),
+        ]);
+        let event = data(&[
+            ("trace.trace_id", json!("4bf92f3577b34da6a3ce929d0e0e4736")),
+            ("trace.parent_id", json!("f067aa0ba902b7")),
+            ("service_name", json!("test")),
+            ("message", json!("done")),
+        ]);
+        reporter
+            .report_batch(vec![(span, Utc::now()), (event, Utc::now())])
+            .unwrap();
+
+        // in-flight exports complete on drop, without flushing first
+        drop(reporter);
+        let spans = collector.spans.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        let exported = &spans[0].resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(exported.name, "request");
+        let logs = collector.logs.lock().unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].resource_logs[0].scope_logs[0].log_records.len(), 1);
+        drop((spans, logs));
+
+        // dropping from within a runtime neither blocks on nor drops the runtime in place
+        runtime.block_on(async move {
+            drop(OtlpReporter::connect(endpoint).unwrap());
+        });
+    }
+
+    #[test]
+    fn hashes_arbitrary_trace_ids() {
+        let trace_id = otlp_trace_id("a string".to_string());
+        assert_eq!(trace_id, otlp_trace_id("a string".to_string()));
+        assert_ne!(trace_id, [0; 16]);
+    }
+}
@@ -0,0 +1,645 @@
+//! Helpers for propagating distributed trace context in request headers.
+//!
+//! [`extract`] and [`inject`] handle both W3C Trace Context `traceparent` headers and B3
+//! headers, for any header carrier implementing [`Extractor`] or [`Injector`]. These are
+//! implemented for `HashMap<String, String>`, and for `http::HeaderMap` with the `use_http`
+//! feature, so that middleware for most HTTP frameworks only needs to call them. With the
+//! `use_reqwest` feature, [`inject_current_context`] does the same for outgoing `reqwest`
+//! requests.
+//!
+//! Continuing a trace from an incoming request's headers:
+//!
+//! ```no_run
+//! # use std::collections::HashMap;
+//! # use tracing_honeycomb::{propagation, register_dist_tracing_root};
+//! # let headers: HashMap<String, String> = HashMap::new();
+//! let (trace_id, parent_span) = propagation::extract(&headers).unwrap();
+//! register_dist_tracing_root(trace_id, parent_span).unwrap();
+//! ```
+//!
+//! or, equivalently, [`register_dist_tracing_root_from_headers`].
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::{current_baggage, current_dist_trace_ctx, SpanId, TraceCtxError, TraceId, TraceParent};
+
+/// W3C Trace Context header name.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+/// W3C Baggage header name, of the form `{key}={value},{key}={value}`.
+pub const BAGGAGE_HEADER: &str = "baggage";
+/// Single header name, of the form `{trace_id}-{span_id}-{sampled}-{parent_span_id}`.
+pub const B3_SINGLE_HEADER: &str = "b3";
+/// Multi-header trace id header name.
+pub const B3_TRACE_ID_HEADER: &str = "X-B3-TraceId";
+/// Multi-header span id header name.
+pub const B3_SPAN_ID_HEADER: &str = "X-B3-SpanId";
+/// Multi-header sampling decision header name.
+pub const B3_SAMPLED_HEADER: &str = "X-B3-Sampled";
+
+/// Error extracting B3 trace context from headers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PropagationError {
+    /// Neither a `b3` header nor an `X-B3-TraceId` header carrying a trace id was found.
+    MissingTraceId,
+    /// The trace id is not 16 or 32 hex characters, or is all zeroes.
+    InvalidTraceId,
+    /// The span id is not 16 hex characters, or is all zeroes.
+    InvalidSpanId,
+}
+
+impl Display for PropagationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTraceId => write!(f, "no B3 trace id found in headers"),
+            Self::InvalidTraceId => write!(f, "invalid B3 trace id"),
+            Self::InvalidSpanId => write!(f, "invalid B3 span id"),
+        }
+    }
+}
+
+impl std::error::Error for PropagationError {}
+
+/// Error continuing a trace from request headers via
+/// [`register_dist_tracing_root_from_headers`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RegisterFromHeadersError {
+    /// Neither a valid `traceparent` header nor valid B3 headers were found.
+    MissingContext,
+    /// The current span could not be registered as the root of the trace.
+    Register(TraceCtxError),
+}
+
+impl Display for RegisterFromHeadersError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingContext => write!(f, "no valid trace context found in headers"),
+            Self::Register(e) => write!(f, "failed to register trace root: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for RegisterFromHeadersError {}
+
+/// B3 header encodings.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum B3Format {
+    /// A single `b3` header.
+    Single,
+    /// Separate `X-B3-TraceId`, `X-B3-SpanId` and `X-B3-Sampled` headers.
+    Multi,
+}
+
+/// Read access to the headers of a request, or any other carrier of string keys and values
+pub trait Extractor {
+    /// Returns the value of the header named `key`, matched case-insensitively.
+    fn get(&self, key: &str) -> Option<&str>;
+}
+
+/// Write access to the headers of a request, or any other carrier of string keys and values
+pub trait Injector {
+    /// Sets the header named `key` to `value`, replacing any previous value.
+    fn set(&mut self, key: &str, value: String);
+}
+
+impl Extractor for HashMap<String, String> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl Injector for HashMap<String, String> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_string(), value);
+    }
+}
+
+/// Headers whose values are not visible ASCII are ignored.
+#[cfg(feature = "use_http")]
+impl Extractor for http::HeaderMap {
+    fn get(&self, key: &str) -> Option<&str> {
+        http::HeaderMap::get(self, key).and_then(|v| v.to_str().ok())
+    }
+}
+
+/// Headers with invalid names or values are not set.
+#[cfg(feature = "use_http")]
+impl Injector for http::HeaderMap {
+    fn set(&mut self, key: &str, value: String) {
+        let name = http::header::HeaderName::from_bytes(key.as_bytes());
+        let value = http::header::HeaderValue::from_str(&value);
+        if let (Ok(name), Ok(value)) = (name, value) {
+            self.insert(name, value);
+        }
+    }
+}
+
+/// Extracts the trace id and, if present, the parent span id from a `traceparent` header, or
+/// failing that from B3 headers.
+///
+/// Returns `None` if neither carries a valid trace context. The returned values can be passed
+/// directly to `register_dist_tracing_root`.
+pub fn extract<C: Extractor + ?Sized>(carrier: &C) -> Option<(TraceId, Option<SpanId>)> {
+    let traceparent =
+        header(carrier, TRACEPARENT_HEADER).and_then(|v| v.parse::<TraceParent>().ok());
+    match traceparent {
+        Some(traceparent) => Some((
+            traceparent.trace_id().clone(),
+            Some(traceparent.span_id().clone()),
+        )),
+        None => extract_b3(carrier).ok(),
+    }
+}
+
+/// Registers the current span as the local root of the trace carried by `carrier`, with the
+/// span which sent it as its remote parent.
+///
+/// Equivalent to passing the result of [`extract`] to `register_dist_tracing_root`: a valid
+/// `traceparent` header takes precedence over B3 headers, which are used if it is absent or
+/// invalid, the single `b3` header in turn taking precedence over `X-B3-*` headers. Fails
+/// with [`RegisterFromHeadersError::MissingContext`] if neither carries a valid trace context,
+/// in which case no span is registered, e.g. so a new trace can be started instead.
+pub fn register_dist_tracing_root_from_headers<C: Extractor + ?Sized>(
+    carrier: &C,
+) -> Result<(), RegisterFromHeadersError> {
+    let (trace_id, parent_span) =
+        extract(carrier).ok_or(RegisterFromHeadersError::MissingContext)?;
+    crate::register_dist_tracing_root(trace_id, parent_span)
+        .map_err(RegisterFromHeadersError::Register)
+}
+
+/// Injects the current span's distributed trace context into `carrier`.
+///
+/// Sets a `traceparent` header, or a single `b3` header if the current trace id is not a
+/// 128-bit hex or UUID value, and so can't be sent as a `traceparent`. The current span is
+/// sent as the parent span id, so that the receiver's spans are its children. The current
+/// span's baggage, if any, is sent in a `baggage` header.
+pub fn inject<C: Injector + ?Sized>(carrier: &mut C) -> Result<(), TraceCtxError> {
+    let (trace_id, span_id) = current_dist_trace_ctx()?;
+    match trace_id.to_traceparent(&span_id, true) {
+        Ok(traceparent) => carrier.set(TRACEPARENT_HEADER, traceparent),
+        Err(_) => inject_b3(carrier, B3Format::Single)?,
+    }
+
+    let baggage = current_baggage()?;
+    if !baggage.is_empty() {
+        let baggage: Vec<String> = baggage
+            .iter()
+            .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+            .collect();
+        carrier.set(BAGGAGE_HEADER, baggage.join(","));
+    }
+    Ok(())
+}
+
+/// Injects the current span's distributed trace context into an outgoing `reqwest` request, as
+/// with [`inject`], e.g. `inject_current_context(client.get(url)).send()`.
+///
+/// A no-op, returning the builder unchanged, if the current span is not part of a distributed
+/// trace, or no layer is registered, so it can be called unconditionally.
+///
+/// Requires the `use_reqwest` feature.
+#[cfg(feature = "use_reqwest")]
+pub fn inject_current_context(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let mut headers = HashMap::new();
+    if inject(&mut headers).is_err() {
+        return builder;
+    }
+    headers
+        .into_iter()
+        .fold(builder, |builder, (key, value)| builder.header(key, value))
+}
+
+/// Extracts baggage entries from a `baggage` header, in order.
+///
+/// Entries which are malformed, or not valid UTF-8 once decoded, are skipped, as are any
+/// properties following an entry's value. Pass the entries to
+/// [`set_baggage`](crate::set_baggage) after registering the trace root to restore them:
+///
+/// ```no_run
+/// # use std::collections::HashMap;
+/// # use tracing_honeycomb::{propagation, register_dist_tracing_root, set_baggage};
+/// # let headers: HashMap<String, String> = HashMap::new();
+/// let (trace_id, parent_span) = propagation::extract(&headers).unwrap();
+/// register_dist_tracing_root(trace_id, parent_span).unwrap();
+/// for (key, value) in propagation::extract_baggage(&headers) {
+///     set_baggage(key, value).unwrap();
+/// }
+/// ```
+pub fn extract_baggage<C: Extractor + ?Sized>(carrier: &C) -> Vec<(String, String)> {
+    let baggage = match header(carrier, BAGGAGE_HEADER) {
+        Some(baggage) => baggage,
+        None => return Vec::new(),
+    };
+
+    baggage
+        .split(',')
+        .filter_map(|entry| {
+            let entry = entry.split(';').next().unwrap_or_default();
+            let (key, value) = entry.split_once('=')?;
+            let key = percent_decode(key.trim())?;
+            let value = percent_decode(value.trim())?;
+            if key.is_empty() {
+                None
+            } else {
+                Some((key, value))
+            }
+        })
+        .collect()
+}
+
+// encodes all but unreserved URI characters
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            decoded.push(b);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Extracts the trace id and, if present, the parent span id from B3 headers.
+///
+/// Accepts both the single `b3` header and the `X-B3-*` multi-header form, preferring the
+/// single header if both are present. Header names are matched case-insensitively.
+/// The returned values can be passed directly to `register_dist_tracing_root`.
+pub fn extract_b3<C: Extractor + ?Sized>(
+    headers: &C,
+) -> Result<(TraceId, Option<SpanId>), PropagationError> {
+    if let Some(b3) = header(headers, B3_SINGLE_HEADER) {
+        let mut parts = b3.split('-');
+        let trace_id = parts.next().unwrap_or_default();
+        // a lone sampling decision (`0`, `1` or `d`) carries no trace context
+        if trace_id.len() > 1 {
+            let trace_id = parse_trace_id(trace_id)?;
+            let span_id = parts.next().map(parse_span_id).transpose()?;
+            return Ok((trace_id, span_id));
+        }
+    }
+
+    let trace_id = header(headers, B3_TRACE_ID_HEADER).ok_or(PropagationError::MissingTraceId)?;
+    let trace_id = parse_trace_id(trace_id)?;
+    let span_id = header(headers, B3_SPAN_ID_HEADER)
+        .map(parse_span_id)
+        .transpose()?;
+
+    Ok((trace_id, span_id))
+}
+
+/// Injects the current span's distributed trace context into `headers` in the given B3 format.
+///
+/// The current span is sent as the span id, so that the receiver's spans are its children.
+/// Trace ids which are not 16 or 32 hex characters are sent as-is.
+pub fn inject_b3<C: Injector + ?Sized>(
+    headers: &mut C,
+    format: B3Format,
+) -> Result<(), TraceCtxError> {
+    let (trace_id, span_id) = current_dist_trace_ctx()?;
+    let span_id = format!("{:016x}", span_id.tracing_id.into_u64());
+
+    match format {
+        B3Format::Single => {
+            headers.set(B3_SINGLE_HEADER, format!("{}-{}-1", trace_id, span_id));
+        }
+        B3Format::Multi => {
+            headers.set(B3_TRACE_ID_HEADER, trace_id.to_string());
+            headers.set(B3_SPAN_ID_HEADER, span_id);
+            headers.set(B3_SAMPLED_HEADER, "1".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+fn header<'a, C: Extractor + ?Sized>(headers: &'a C, name: &str) -> Option<&'a str> {
+    headers.get(name).map(str::trim)
+}
+
+fn is_hex_id(s: &str, lens: &[usize]) -> bool {
+    lens.contains(&s.len())
+        && s.bytes().all(|b| b.is_ascii_hexdigit())
+        && s.bytes().any(|b| b != b'0')
+}
+
+fn parse_trace_id(s: &str) -> Result<TraceId, PropagationError> {
+    if is_hex_id(s, &[16, 32]) {
+        Ok(TraceId::from(s.to_ascii_lowercase()))
+    } else {
+        Err(PropagationError::InvalidTraceId)
+    }
+}
+
+fn parse_span_id(s: &str) -> Result<SpanId, PropagationError> {
+    if is_hex_id(s, &[16]) {
+        SpanId::from_str(s).map_err(|_| PropagationError::InvalidSpanId)
+    } else {
+        Err(PropagationError::InvalidSpanId)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TRACE_ID: &str = "80f198ee56343ba864fe8b2a57d3eff7";
+    const SPAN_ID: &str = "e457b5a2e4d86bd1";
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn extracts_single_header() {
+        let headers = headers(&[(
+            "b3",
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90",
+        )]);
+        let (trace_id, span_id) = extract_b3(&headers).unwrap();
+        assert_eq!(trace_id, TraceId::from(TRACE_ID));
+        assert_eq!(span_id, Some(SpanId::from_str(SPAN_ID).unwrap()));
+    }
+
+    #[test]
+    fn extracts_multi_header() {
+        let headers = headers(&[
+            ("x-b3-traceid", "463ac35c9f6413ad"),
+            ("X-B3-SpanId", SPAN_ID),
+            ("X-B3-Sampled", "1"),
+        ]);
+        let (trace_id, span_id) = extract_b3(&headers).unwrap();
+        assert_eq!(trace_id, TraceId::from("463ac35c9f6413ad"));
+        assert_eq!(span_id, Some(SpanId::from_str(SPAN_ID).unwrap()));
+    }
+
+    #[test]
+    fn extracts_trace_id_without_span_id() {
+        let headers = headers(&[("X-B3-TraceId", TRACE_ID)]);
+        assert_eq!(extract_b3(&headers), Ok((TraceId::from(TRACE_ID), None)));
+    }
+
+    #[test]
+    fn falls_back_to_multi_header_for_sampling_only_single_header() {
+        let headers = headers(&[("b3", "0"), ("X-B3-TraceId", TRACE_ID)]);
+        assert_eq!(extract_b3(&headers), Ok((TraceId::from(TRACE_ID), None)));
+    }
+
+    #[test]
+    fn rejects_missing_or_invalid_ids() {
+        assert_eq!(
+            extract_b3(&headers(&[("b3", "1")])),
+            Err(PropagationError::MissingTraceId)
+        );
+        assert_eq!(
+            extract_b3(&headers(&[("X-B3-TraceId", "abc")])),
+            Err(PropagationError::InvalidTraceId)
+        );
+        assert_eq!(
+            extract_b3(&headers(&[("b3", "0000000000000000-e457b5a2e4d86bd1")])),
+            Err(PropagationError::InvalidTraceId)
+        );
+        assert_eq!(
+            extract_b3(&headers(&[
+                ("X-B3-TraceId", TRACE_ID),
+                ("X-B3-SpanId", "xyz")
+            ])),
+            Err(PropagationError::InvalidSpanId)
+        );
+    }
+
+    #[test]
+    fn injects_current_context() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber =
+            tracing_subscriber::registry().with(crate::new_blackhole_telemetry_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::from(TRACE_ID), None).unwrap();
+            let (_, current_span_id) = current_dist_trace_ctx().unwrap();
+
+            for format in &[B3Format::Single, B3Format::Multi] {
+                let mut headers = HashMap::new();
+                inject_b3(&mut headers, *format).unwrap();
+                assert_eq!(
+                    extract_b3(&headers),
+                    Ok((TraceId::from(TRACE_ID), Some(current_span_id.clone())))
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn registers_root_from_headers() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber =
+            tracing_subscriber::registry().with(crate::new_blackhole_telemetry_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            assert_eq!(
+                register_dist_tracing_root_from_headers(&headers(&[("b3", "garbage")])),
+                Err(RegisterFromHeadersError::MissingContext)
+            );
+            assert!(current_dist_trace_ctx().is_err());
+
+            let b3 = headers(&[("X-B3-TraceId", TRACE_ID), ("X-B3-SpanId", SPAN_ID)]);
+            register_dist_tracing_root_from_headers(&b3).unwrap();
+            let (trace_id, _) = current_dist_trace_ctx().unwrap();
+            assert_eq!(trace_id, TraceId::from(TRACE_ID));
+        });
+
+        let b3 = headers(&[("X-B3-TraceId", TRACE_ID)]);
+        assert_eq!(
+            register_dist_tracing_root_from_headers(&b3),
+            Err(RegisterFromHeadersError::Register(
+                TraceCtxError::NoEnabledSpan
+            ))
+        );
+    }
+
+    #[test]
+    fn extracts_traceparent_before_b3() {
+        let traceparent = headers(&[
+            (
+                "Traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+            ("b3", "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1"),
+        ]);
+        let (trace_id, span_id) = extract(&traceparent).unwrap();
+        assert_eq!(
+            trace_id,
+            TraceId::from(0x4bf92f3577b34da6a3ce929d0e0e4736_u128)
+        );
+        assert_eq!(span_id, Some(SpanId::from_str("00f067aa0ba902b7").unwrap()));
+
+        let fallback = headers(&[("traceparent", "garbage"), ("X-B3-TraceId", TRACE_ID)]);
+        assert_eq!(extract(&fallback), Some((TraceId::from(TRACE_ID), None)));
+        assert_eq!(extract(&HashMap::<String, String>::new()), None);
+    }
+
+    #[test]
+    #[cfg(feature = "use_reqwest")]
+    fn injects_current_context_into_reqwest_requests() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let client = reqwest::Client::new();
+        let request = |trace_id: Option<&TraceId>| {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            if let Some(trace_id) = trace_id {
+                crate::register_dist_tracing_root(trace_id.clone(), None).unwrap();
+            }
+            inject_current_context(client.get("http://localhost/"))
+                .build()
+                .unwrap()
+        };
+
+        let subscriber =
+            tracing_subscriber::registry().with(crate::new_blackhole_telemetry_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let trace_id = TraceId::new();
+            let request = request(Some(&trace_id));
+            let traceparent = request.headers()[TRACEPARENT_HEADER].to_str().unwrap();
+            assert_eq!(
+                traceparent.parse::<TraceParent>().unwrap().trace_id(),
+                &trace_id
+            );
+
+            // outside of a trace
+            assert!(request(None).headers().is_empty());
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "use_http")]
+    fn propagates_through_http_header_maps() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber =
+            tracing_subscriber::registry().with(crate::new_blackhole_telemetry_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::from(TRACE_ID), None).unwrap();
+            let (_, current_span_id) = current_dist_trace_ctx().unwrap();
+
+            let mut headers = http::HeaderMap::new();
+            inject(&mut headers).unwrap();
+            assert!(headers.contains_key(TRACEPARENT_HEADER));
+            assert_eq!(
+                extract(&headers),
+                Some((TraceId::from(TRACE_ID), Some(current_span_id)))
+            );
+        });
+
+        let mut headers = http::HeaderMap::new();
+        Injector::set(&mut headers, "X-B3-TraceId", TRACE_ID.to_string());
+        Injector::set(&mut headers, "X-B3-SpanId", SPAN_ID.to_string());
+        // invalid names and values are not set
+        Injector::set(&mut headers, "not a header", "value".to_string());
+        Injector::set(&mut headers, "x-invalid", "line\nbreak".to_string());
+        assert_eq!(headers.len(), 2);
+
+        assert_eq!(Extractor::get(&headers, "x-b3-traceid"), Some(TRACE_ID));
+        assert_eq!(
+            extract(&headers),
+            Some((
+                TraceId::from(TRACE_ID),
+                Some(SpanId::from_str(SPAN_ID).unwrap())
+            ))
+        );
+
+        // values which are not visible ASCII are ignored
+        headers.insert(
+            "x-b3-traceid",
+            http::HeaderValue::from_bytes(b"\xff").unwrap(),
+        );
+        assert_eq!(Extractor::get(&headers, "X-B3-TraceId"), None);
+        assert_eq!(extract(&headers), None);
+    }
+
+    #[test]
+    fn injects_traceparent_or_b3_for_other_trace_ids() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber =
+            tracing_subscriber::registry().with(crate::new_blackhole_telemetry_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            for trace_id in &[TraceId::new(), TraceId::from("463ac35c9f6413ad")] {
+                let span = tracing::info_span!("root");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(trace_id.clone(), None).unwrap();
+                let (_, current_span_id) = current_dist_trace_ctx().unwrap();
+
+                let mut headers = HashMap::new();
+                inject(&mut headers).unwrap();
+                assert_eq!(headers.len(), 1);
+                assert_eq!(
+                    extract(&headers),
+                    Some((trace_id.clone(), Some(current_span_id)))
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn propagates_baggage() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber =
+            tracing_subscriber::registry().with(crate::new_blackhole_telemetry_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+
+            let mut headers = HashMap::new();
+            inject(&mut headers).unwrap();
+            assert!(!headers.contains_key(BAGGAGE_HEADER));
+
+            crate::set_baggage("tenant_id", "42").unwrap();
+            crate::set_baggage("note", "a b,c=d;é").unwrap();
+            inject(&mut headers).unwrap();
+            assert_eq!(
+                headers[BAGGAGE_HEADER],
+                "tenant_id=42,note=a%20b%2Cc%3Dd%3B%C3%A9"
+            );
+            assert_eq!(extract_baggage(&headers), crate::current_baggage().unwrap());
+        });
+
+        let headers = headers(&[("Baggage", "k1=v1;prop=1, bad, =v, k2=%zz, k3 = v%203 ")]);
+        assert_eq!(
+            extract_baggage(&headers),
+            vec![
+                ("k1".to_string(), "v1".to_string()),
+                ("k3".to_string(), "v 3".to_string())
+            ]
+        );
+    }
+}
@@ -1,47 +1,3309 @@
-use chrono::{DateTime, Utc};
-use libhoney::FieldHolder;
-use std::collections::HashMap;
+use chrono::{DateTime, SecondsFormat, Utc};
+use libhoney::{FieldHolder, Sender};
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "use_parking_lot")]
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 #[cfg(not(feature = "use_parking_lot"))]
-use std::sync::Mutex;
+use std::sync::{Mutex, PoisonError, RwLock};
+
+use crate::sink::Worker;
+
+/// Errors that can occur while reporting data to some backend.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReportError {
+    /// The data could not be serialized.
+    Serialization(serde_json::Error),
+    /// The data could not be handed off to libhoney for transmission.
+    Transmission(libhoney::Error),
+    /// The data could not be written out.
+    Io(io::Error),
+    /// The reporter's buffer was full, so the data was dropped.
+    Overflow,
+    /// The reporter is no longer accepting data, e.g. because its background task has exited.
+    Closed,
+    /// The reporter could not be connected to its backend.
+    #[cfg(feature = "use_otlp")]
+    Connect(tonic::transport::Error),
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serialization(e) => write!(f, "failed to serialize data: {}", e),
+            Self::Transmission(e) => write!(f, "failed to send data: {}", e.message),
+            Self::Io(e) => write!(f, "failed to write data: {}", e),
+            Self::Overflow => write!(f, "reporter buffer full, data dropped"),
+            Self::Closed => write!(f, "reporter closed, data dropped"),
+            #[cfg(feature = "use_otlp")]
+            Self::Connect(e) => write!(f, "failed to connect: {}", e),
+        }
+    }
+}
+
+impl Error for ReportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Serialization(e) => Some(e),
+            Self::Transmission(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Overflow | Self::Closed => None,
+            #[cfg(feature = "use_otlp")]
+            Self::Connect(e) => Some(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ReportError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Serialization(err)
+    }
+}
+
+impl From<libhoney::Error> for ReportError {
+    fn from(err: libhoney::Error) -> Self {
+        Self::Transmission(err)
+    }
+}
+
+#[cfg(feature = "use_otlp")]
+impl From<tonic::transport::Error> for ReportError {
+    fn from(err: tonic::transport::Error) -> Self {
+        Self::Connect(err)
+    }
+}
+
+impl From<io::Error> for ReportError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
 
 /// Reports data to some backend
 pub trait Reporter {
     /// Reports data to the backend
-    fn report_data(&self, data: HashMap<String, libhoney::Value>, timestamp: DateTime<Utc>);
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError>;
+
+    /// Reports a batch of data to the backend, in order
+    ///
+    /// Defaults to calling `report_data` for each entry. Every entry is reported even if
+    /// some fail, in which case the first error is returned.
+    fn report_batch(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<(), ReportError> {
+        let mut res = Ok(());
+        for (data, timestamp) in batch {
+            let next = self.report_data(data, timestamp);
+            res = res.and(next);
+        }
+        res
+    }
+
+    /// Transmits any data buffered by the reporter
+    ///
+    /// Defaults to doing nothing.
+    fn flush(&self) -> Result<(), ReportError> {
+        Ok(())
+    }
+
+    /// Transmits any data buffered by the reporter, ahead of the process exiting
+    ///
+    /// Defaults to calling `flush`.
+    fn shutdown(&self) -> Result<(), ReportError> {
+        self.flush()
+    }
+
+    /// Health of the reporter, as judged from the outcome of its recent reports, e.g. for a
+    /// readiness probe
+    ///
+    /// Defaults to [`ReporterHealth::Healthy`]. Reporters wrapping others, including those
+    /// handing data off to a background thread or task, report the health of the reporters
+    /// they wrap, the worst of them if several. Other reporters, e.g. a [`BatchJsonReporter`]
+    /// or a [`LibhoneyReporter`], do not track the outcome of their reports, so are always
+    /// healthy unless wrapped in a [`HealthReporter`].
+    fn health(&self) -> ReporterHealth {
+        ReporterHealth::Healthy
+    }
+}
+
+impl<R: Reporter + ?Sized> Reporter for Arc<R> {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        (**self).report_data(data, timestamp)
+    }
+
+    fn report_batch(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<(), ReportError> {
+        (**self).report_batch(batch)
+    }
+
+    fn flush(&self) -> Result<(), ReportError> {
+        (**self).flush()
+    }
+
+    fn shutdown(&self) -> Result<(), ReportError> {
+        (**self).shutdown()
+    }
+
+    fn health(&self) -> ReporterHealth {
+        (**self).health()
+    }
+}
+
+/// Guard which flushes a reporter when dropped
+///
+/// Returned by [`Builder::build_with_flush_guard`](crate::Builder::build_with_flush_guard),
+/// for short-lived processes whose last spans would otherwise remain buffered on exit. With
+/// tail sampling, traces still pending are decided before each flush, from the data recorded
+/// so far, so that they are transmitted too.
+///
+/// Spans are only reported once they close, so the guard should be dropped after all spans
+/// have closed and the subscriber has stopped being used, e.g. held for the whole of `main`.
+/// A subscriber set via `tracing::subscriber::set_global_default` is never dropped, so the
+/// guard is the only point at which its data is flushed.
+#[must_use = "the reporter is flushed when the guard is dropped"]
+pub struct FlushGuard {
+    reporter: Option<Arc<dyn Reporter + Send + Sync>>,
+    // hands traces pending for tail sampling over to the reporter
+    decide_pending: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl FlushGuard {
+    pub(crate) fn new(reporter: Arc<dyn Reporter + Send + Sync>) -> Self {
+        FlushGuard {
+            reporter: Some(reporter),
+            decide_pending: None,
+        }
+    }
+
+    pub(crate) fn with_decide_pending(
+        mut self,
+        decide_pending: Arc<dyn Fn() + Send + Sync>,
+    ) -> Self {
+        self.decide_pending = Some(decide_pending);
+        self
+    }
+
+    fn decide_pending(&self) {
+        if let Some(decide_pending) = &self.decide_pending {
+            decide_pending();
+        }
+    }
+
+    /// Transmits any data buffered by the reporter
+    pub fn flush(&self) -> Result<(), ReportError> {
+        match &self.reporter {
+            Some(reporter) => {
+                self.decide_pending();
+                reporter.flush()
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Shuts the reporter down, transmitting any buffered data
+    pub fn shutdown(mut self) -> Result<(), ReportError> {
+        match self.reporter.take() {
+            Some(reporter) => {
+                self.decide_pending();
+                reporter.shutdown()
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Transmits any data buffered by the reporter, giving up after `timeout`, e.g. so that a
+    /// process exiting while the network is unreachable does not hang. Returns whether the
+    /// flush completed successfully within the deadline; errors are logged to stderr.
+    ///
+    /// The reporter is flushed on a background thread, which keeps running past the deadline,
+    /// since a blocking flush, such as libhoney's, cannot be cancelled. Until it completes,
+    /// the reporter may block further reports, flushes and the guard's own shutdown on drop,
+    /// so prefer [`shutdown_timeout`](Self::shutdown_timeout) when exiting. Data the flush has
+    /// not transmitted by the time the process exits is lost.
+    pub fn flush_timeout(&self, timeout: Duration) -> bool {
+        match &self.reporter {
+            Some(reporter) => {
+                let decide_pending = self.decide_pending.clone();
+                run_with_timeout(reporter.clone(), timeout, move |r| {
+                    if let Some(decide_pending) = decide_pending {
+                        decide_pending();
+                    }
+                    r.flush()
+                })
+            }
+            None => true,
+        }
+    }
+
+    /// Shuts the reporter down, transmitting any buffered data, giving up after `timeout`.
+    /// Returns whether the shutdown completed successfully within the deadline; errors are
+    /// logged to stderr.
+    ///
+    /// As with [`flush_timeout`](Self::flush_timeout), the shutdown continues on a background
+    /// thread past the deadline, and data it has not transmitted by the time the process exits
+    /// is lost. The guard is consumed, so that dropping it does not wait on the reporter again.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> bool {
+        match self.reporter.take() {
+            Some(reporter) => {
+                let decide_pending = self.decide_pending.take();
+                run_with_timeout(reporter, timeout, move |r| {
+                    if let Some(decide_pending) = decide_pending {
+                        decide_pending();
+                    }
+                    r.shutdown()
+                })
+            }
+            None => true,
+        }
+    }
+}
+
+fn run_with_timeout<F>(reporter: Arc<dyn Reporter + Send + Sync>, timeout: Duration, f: F) -> bool
+where
+    F: FnOnce(&(dyn Reporter + Send + Sync)) -> Result<(), ReportError> + Send + 'static,
+{
+    let (done_tx, done_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        // the receiver is gone if the deadline passed
+        let _ = done_tx.send(f(&*reporter));
+    });
+
+    match done_rx.recv_timeout(timeout) {
+        Ok(Ok(())) => true,
+        Ok(Err(err)) => {
+            eprintln!("error flushing events to honeycomb, {}", err);
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        if let Some(reporter) = self.reporter.take() {
+            self.decide_pending();
+            if let Err(err) = reporter.shutdown() {
+                // dropped on exit, so log msg to stderr
+                eprintln!("error flushing events to honeycomb, {}", err);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FlushGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlushGuard")
+            .field("active", &self.reporter.is_some())
+            .finish()
+    }
+}
+
+/// Field which, when recorded on a span or event as a string, routes it to the named Honeycomb
+/// dataset rather than the one set on the [`libhoney::Config`], e.g.
+/// `tracing::info_span!("request", honeycomb.dataset = "tenant-a")`.
+///
+/// Only honored by [`LibhoneyReporter`] and [`BatchJsonReporter`], which strip the field
+/// before sending so that it does not also appear as a column. The other reporters which
+/// write or forward data, such as [`StdoutReporter`], [`FileReporter`] and `OtlpReporter`,
+/// strip it too, while reporters wrapping another pass it on. The field is exempt from
+/// [`Builder::with_key_mapping`](crate::Builder::with_key_mapping). Spans and events without
+/// the field, or where it is not a string, are sent to the configured dataset. The field is
+/// not inherited by child spans or events, so to route a whole trace each of its spans and
+/// events must record it.
+pub const DATASET_FIELD: &str = "honeycomb.dataset";
+
+/// Field holding the rate at which a span or event was sampled by a reporter, e.g. `10` for
+/// one kept in ten, so that Honeycomb weights it accordingly in counts and sums.
+///
+/// Set by [`EventSamplingReporter`] on the events it keeps. [`LibhoneyReporter`] and
+/// [`BatchJsonReporter`] strip the field and send it as the sample rate of the span or event,
+/// multiplied by the configured sample rate in the case of `BatchJsonReporter`. Other
+/// reporters report it as a field.
+pub const SAMPLE_RATE_FIELD: &str = "honeycomb.sample_rate";
+
+// removes the sample rate set by a reporter, if any
+fn take_sample_rate(data: &mut HashMap<String, libhoney::Value>) -> Option<u64> {
+    data.remove(SAMPLE_RATE_FIELD)
+        .and_then(|sample_rate| sample_rate.as_u64())
 }
 
 /// Reporter that sends events and spans to a [`libhoney::Client`]
+///
+/// The target dataset can be overridden per span or event via [`DATASET_FIELD`].
 pub type LibhoneyReporter = Mutex<libhoney::Client<libhoney::transmission::Transmission>>;
 impl Reporter for LibhoneyReporter {
-    fn report_data(&self, data: HashMap<String, libhoney::Value>, timestamp: DateTime<Utc>) {
-        // succeed or die. failure is unrecoverable (mutex poisoned)
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        // a panic elsewhere while the lock was held leaves the client usable, so keep reporting
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut reporter = self.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut reporter = self.lock();
+
+        send_event(&mut reporter, data, timestamp)
+    }
+
+    fn report_batch(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<(), ReportError> {
+        // take the lock once for the whole batch
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut reporter = self.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut reporter = self.lock();
+
+        let mut res = Ok(());
+        for (data, timestamp) in batch {
+            let next = send_event(&mut reporter, data, timestamp);
+            res = res.and(next);
+        }
+        res
+    }
+
+    /// Blocks until libhoney has transmitted all events sent so far.
+    fn flush(&self) -> Result<(), ReportError> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut reporter = self.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut reporter = self.lock();
+
+        reporter.flush()?;
+        Ok(())
+    }
+
+    /// Blocks until libhoney has transmitted all events sent so far, then stops its
+    /// transmission threads, as `libhoney::Client::close` does. Data reported afterwards is
+    /// not sent.
+    fn shutdown(&self) -> Result<(), ReportError> {
         #[cfg(not(feature = "use_parking_lot"))]
-        let mut reporter = self.lock().unwrap();
+        let mut reporter = self.lock().unwrap_or_else(PoisonError::into_inner);
         #[cfg(feature = "use_parking_lot")]
         let mut reporter = self.lock();
 
-        let mut ev = reporter.new_event();
-        ev.add(data);
-        ev.set_timestamp(timestamp);
-        let res = ev.send(&mut reporter);
-        if let Err(err) = res {
-            // unable to report telemetry (buffer full) so log msg to stderr
-            // TODO: figure out strategy for handling this (eg report data loss event)
-            eprintln!("error sending event to honeycomb, {:?}", err);
+        // `close` takes the client by value, which the lock does not allow
+        reporter.transmission.stop()?;
+        Ok(())
+    }
+}
+
+/// Error validating a [`libhoney::Config`], as returned by
+/// [`try_new_honeycomb_telemetry_layer`](crate::try_new_honeycomb_telemetry_layer), or the
+/// sample rate read by
+/// [`new_honeycomb_telemetry_layer_with_env_sampling`](crate::new_honeycomb_telemetry_layer_with_env_sampling)
+///
+/// libhoney accepts any config, but one failing these checks has every event rejected, by
+/// Honeycomb or while sending, long after the layer was constructed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InitError {
+    /// The API key, i.e. the write key, is empty.
+    MissingApiKey,
+    /// The dataset name is empty.
+    MissingDataset,
+    /// The API host is not an `http` or `https` URL, e.g. `https://api.honeycomb.io`.
+    InvalidApiHost(String),
+    /// The [`HONEYCOMB_SAMPLE_RATE`](crate::SAMPLE_RATE_ENV_VAR) environment variable is set, but
+    /// not to a non-negative integer.
+    InvalidSampleRate(String),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::MissingApiKey => write!(f, "honeycomb API key is empty"),
+            InitError::MissingDataset => write!(f, "honeycomb dataset is empty"),
+            InitError::InvalidApiHost(host) => {
+                write!(f, "honeycomb API host {:?} is not an http(s) URL", host)
+            }
+            InitError::InvalidSampleRate(value) => write!(
+                f,
+                "{} {:?} is not a non-negative integer",
+                crate::SAMPLE_RATE_ENV_VAR,
+                value
+            ),
         }
     }
 }
 
-/// Reporter that sends events and spans to stdout
-#[derive(Debug, Clone, Copy)]
-pub struct StdoutReporter;
-impl Reporter for StdoutReporter {
-    fn report_data(&self, data: HashMap<String, libhoney::Value>, _timestamp: DateTime<Utc>) {
-        if let Ok(data) = serde_json::to_string(&data) {
-            println!("{}", data);
+impl Error for InitError {}
+
+/// Checks that `config` has an API key, a dataset and an http(s) API host, without
+/// initializing libhoney.
+pub(crate) fn validate_libhoney_config(config: &libhoney::Config) -> Result<(), InitError> {
+    let options = &config.options;
+    if options.api_key.trim().is_empty() {
+        return Err(InitError::MissingApiKey);
+    }
+    if options.dataset.trim().is_empty() {
+        return Err(InitError::MissingDataset);
+    }
+
+    let host = options
+        .api_host
+        .strip_prefix("https://")
+        .or_else(|| options.api_host.strip_prefix("http://"));
+    match host {
+        Some(host)
+            if !host.is_empty()
+                && !host.starts_with('/')
+                && !host.contains(char::is_whitespace) =>
+        {
+            Ok(())
+        }
+        _ => Err(InitError::InvalidApiHost(options.api_host.clone())),
+    }
+}
+
+// error of the response libhoney's `Transmission::send` queues, rather than returning an error
+// from `Event::send`, for an event dropped because its channel of pending work is full
+const QUEUE_OVERFLOW: &str = "queue overflow";
+
+/// What a [`LibhoneyReporter`] does when libhoney drops an event because its buffer of pending
+/// events is full, as configured via
+/// [`Builder::new_libhoney_with_buffer_full_policy`](method@crate::Builder::<LibhoneyReporter>::new_libhoney_with_buffer_full_policy).
+///
+/// libhoney does not report a full buffer as an error from `Event::send`, and so not to
+/// [`ErrorHandling`](crate::ErrorHandling), but on its channel of responses, which the reporter
+/// drains on a background thread. The policy is applied there, shortly after the event has
+/// been dropped. Blocking until the buffer has room is not possible, as libhoney drops the
+/// event without exposing how full its buffer is: for backpressure, use a
+/// [`TokioReporter`](crate::TokioReporter) with [`OverflowPolicy::Block`] instead.
+///
+/// To act on dropped events, e.g. to fail a CI run, track the reporter's health via
+/// [`Builder::new_libhoney_with_health`](method@crate::Builder::<HealthReporter<LibhoneyReporter>>::new_libhoney_with_health),
+/// which counts each of them as a failure.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BufferFullPolicy {
+    /// Silently drop the event.
+    Drop,
+    /// Log each dropped event to stderr. This is the default.
+    #[default]
+    Log,
+}
+
+impl BufferFullPolicy {
+    /// Applies the policy to a libhoney response with the given error, if it reports a full
+    /// buffer.
+    pub(crate) fn handle(self, error: Option<&str>) {
+        if error != Some(QUEUE_OVERFLOW) {
+            return;
+        }
+
+        match self {
+            BufferFullPolicy::Drop => {}
+            BufferFullPolicy::Log => eprintln!("honeycomb buffer full, event dropped"),
+        }
+    }
+}
+
+fn send_event(
+    reporter: &mut libhoney::Client<libhoney::transmission::Transmission>,
+    mut data: HashMap<String, libhoney::Value>,
+    timestamp: DateTime<Utc>,
+) -> Result<(), ReportError> {
+    let mut ev = match data.remove(DATASET_FIELD) {
+        // libhoney has no per-event dataset, but events take their dataset from the options
+        // of the builder they are created from, and are batched per dataset
+        Some(libhoney::Value::String(dataset)) => {
+            let mut builder = reporter.new_builder();
+            builder.options.dataset = dataset;
+            builder.new_event()
+        }
+        _ => reporter.new_event(),
+    };
+    let sample_rate = take_sample_rate(&mut data);
+    ev.add(data);
+    ev.set_timestamp(timestamp);
+    match sample_rate {
+        Some(sample_rate) => {
+            // already sampled, so only mark the event with the rate
+            ev.set_sample_rate(sample_rate as usize);
+            ev.send_presampled(reporter)?;
+        }
+        None => ev.send(reporter)?,
+    }
+    Ok(())
+}
+
+/// Reporter that fans data out to several other reporters
+///
+/// Each inner reporter receives its own copy of the data, so every report costs one clone of
+/// the data per inner reporter beyond the first.
+#[derive(Default)]
+pub struct MultiReporter {
+    reporters: Vec<Box<dyn Reporter + Send + Sync>>,
+}
+
+impl MultiReporter {
+    /// Returns a new `MultiReporter` fanning out to the given reporters
+    pub fn new(reporters: Vec<Box<dyn Reporter + Send + Sync>>) -> Self {
+        MultiReporter { reporters }
+    }
+
+    /// Adds a reporter to fan out to
+    pub fn with<R: Reporter + Send + Sync + 'static>(mut self, reporter: R) -> Self {
+        self.reporters.push(Box::new(reporter));
+        self
+    }
+}
+
+impl Reporter for MultiReporter {
+    /// Reports to every inner reporter even if some fail, in which case the first error is
+    /// returned.
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        let mut res = Ok(());
+        if let Some((last, rest)) = self.reporters.split_last() {
+            for reporter in rest {
+                let next = reporter.report_data(data.clone(), timestamp);
+                res = res.and(next);
+            }
+            res = res.and(last.report_data(data, timestamp));
+        }
+        res
+    }
+
+    /// Flushes every inner reporter even if some fail, in which case the first error is
+    /// returned.
+    fn flush(&self) -> Result<(), ReportError> {
+        let mut res = Ok(());
+        for reporter in &self.reporters {
+            res = res.and(reporter.flush());
+        }
+        res
+    }
+
+    /// Shuts down every inner reporter even if some fail, in which case the first error is
+    /// returned.
+    fn shutdown(&self) -> Result<(), ReportError> {
+        let mut res = Ok(());
+        for reporter in &self.reporters {
+            res = res.and(reporter.shutdown());
+        }
+        res
+    }
+
+    /// The worst health of the inner reporters.
+    fn health(&self) -> ReporterHealth {
+        self.reporters
+            .iter()
+            .map(|reporter| reporter.health())
+            .max()
+            .unwrap_or(ReporterHealth::Healthy)
+    }
+}
+
+impl fmt::Debug for MultiReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiReporter")
+            .field("reporters", &self.reporters.len())
+            .finish()
+    }
+}
+
+type DynReporter = Box<dyn Reporter + Send + Sync>;
+
+/// Reporter that forwards data to another reporter which can be replaced at runtime via a
+/// [`ReporterHandle`], e.g. to report to stdout until configuration for Honeycomb has been
+/// loaded asynchronously at startup, without reconstructing the subscriber.
+///
+/// The handle is all that is needed to swap the reporter: `tracing-subscriber`'s `reload`
+/// layer is only needed to also change the rest of the layer's configuration.
+///
+/// Reporting is thread-safe while the reporter is being replaced: each report goes wholly to
+/// either the old or the new reporter. The indirection costs a read lock, held only to clone
+/// an `Arc` of the current reporter, and a dynamic call per span and event, so reports neither
+/// block nor are blocked by a swap for longer than that.
+pub struct ReloadableReporter {
+    reporter: Arc<RwLock<Arc<DynReporter>>>,
+}
+
+impl ReloadableReporter {
+    /// Returns a new `ReloadableReporter` forwarding to the given reporter until replaced
+    pub fn new(reporter: DynReporter) -> Self {
+        ReloadableReporter {
+            reporter: Arc::new(RwLock::new(Arc::new(reporter))),
+        }
+    }
+
+    /// Returns a handle through which the reporter can be replaced
+    pub fn handle(&self) -> ReporterHandle {
+        ReporterHandle {
+            reporter: self.reporter.clone(),
+        }
+    }
+
+    fn with_reporter<T>(&self, f: impl FnOnce(&DynReporter) -> T) -> T {
+        // cloned out of the lock, so that a slow report does not hold up `set_reporter`
+        #[cfg(not(feature = "use_parking_lot"))]
+        let reporter = self
+            .reporter
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        #[cfg(feature = "use_parking_lot")]
+        let reporter = self.reporter.read().clone();
+
+        f(&reporter)
+    }
+}
+
+impl Reporter for ReloadableReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        self.with_reporter(|reporter| reporter.report_data(data, timestamp))
+    }
+
+    fn report_batch(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<(), ReportError> {
+        self.with_reporter(|reporter| reporter.report_batch(batch))
+    }
+
+    fn flush(&self) -> Result<(), ReportError> {
+        self.with_reporter(|reporter| reporter.flush())
+    }
+
+    fn shutdown(&self) -> Result<(), ReportError> {
+        self.with_reporter(|reporter| reporter.shutdown())
+    }
+
+    fn health(&self) -> ReporterHealth {
+        self.with_reporter(|reporter| reporter.health())
+    }
+}
+
+impl fmt::Debug for ReloadableReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadableReporter").finish()
+    }
+}
+
+/// Handle to replace the reporter of a [`ReloadableReporter`]. Clones share the same reporter.
+#[derive(Clone)]
+pub struct ReporterHandle {
+    reporter: Arc<RwLock<Arc<DynReporter>>>,
+}
+
+impl ReporterHandle {
+    /// Replaces the reporter, then shuts the previous one down so that any data it buffered is
+    /// transmitted, returning the error from doing so, if any.
+    ///
+    /// Data reported after the reporter is replaced goes to the new reporter, while reports
+    /// already in progress complete on the previous one, which is only shut down once they
+    /// have, so this waits for them.
+    pub fn set_reporter(&self, reporter: DynReporter) -> Result<(), ReportError> {
+        let reporter = Arc::new(reporter);
+        let previous = {
+            #[cfg(not(feature = "use_parking_lot"))]
+            let mut current = self
+                .reporter
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            #[cfg(feature = "use_parking_lot")]
+            let mut current = self.reporter.write();
+
+            std::mem::replace(&mut *current, reporter)
+        };
+        // outside the lock, so that reporting is not blocked on the previous reporter
+        while Arc::strong_count(&previous) > 1 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        previous.shutdown()
+    }
+}
+
+impl fmt::Debug for ReporterHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReporterHandle").finish()
+    }
+}
+
+/// Health of a reporter, as returned by [`Reporter::health`], from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReporterHealth {
+    /// Recent reports succeeded, or too few of them failed to matter
+    Healthy,
+    /// Some recent reports failed
+    Degraded,
+    /// Most recent reports failed
+    Unhealthy,
+}
+
+/// Default number of recent outcomes a [`HealthHandle`] judges health from.
+pub const DEFAULT_HEALTH_WINDOW: usize = 100;
+
+/// Handle to the health of a reporter, judged from the outcomes of its last few reports, e.g.
+/// to serve from a readiness probe. Clones share the same outcomes.
+///
+/// The reporter is [`Degraded`](ReporterHealth::Degraded) once the share of failures among the
+/// last `window` outcomes reaches `degraded_ratio`, and [`Unhealthy`](ReporterHealth::Unhealthy)
+/// once it reaches `unhealthy_ratio`. It is healthy until it has reported anything.
+///
+/// Returned by [`HealthReporter::handle`], and by
+/// [`new_honeycomb_telemetry_layer_with_health`](crate::new_honeycomb_telemetry_layer_with_health),
+/// which records the outcome of each event libhoney sends to Honeycomb.
+#[derive(Clone)]
+pub struct HealthHandle {
+    outcomes: Arc<Mutex<VecDeque<bool>>>,
+    window: usize,
+    degraded_ratio: f64,
+    unhealthy_ratio: f64,
+}
+
+impl HealthHandle {
+    /// Returns a new `HealthHandle` judging health from the last `window` outcomes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is 0, or if the ratios are not such that
+    /// `0 < degraded_ratio <= unhealthy_ratio <= 1`.
+    pub fn new(window: usize, degraded_ratio: f64, unhealthy_ratio: f64) -> Self {
+        assert!(window > 0, "window must be non-zero");
+        assert!(
+            0.0 < degraded_ratio && degraded_ratio <= unhealthy_ratio && unhealthy_ratio <= 1.0,
+            "ratios must satisfy 0 < degraded_ratio <= unhealthy_ratio <= 1"
+        );
+
+        HealthHandle {
+            outcomes: Arc::new(Mutex::new(VecDeque::with_capacity(window))),
+            window,
+            degraded_ratio,
+            unhealthy_ratio,
+        }
+    }
+
+    /// Returns the reporter's current health.
+    pub fn health(&self) -> ReporterHealth {
+        let outcomes = self.lock_outcomes();
+        if outcomes.is_empty() {
+            return ReporterHealth::Healthy;
+        }
+
+        let failures = outcomes.iter().filter(|succeeded| !**succeeded).count();
+        let ratio = failures as f64 / outcomes.len() as f64;
+        if ratio >= self.unhealthy_ratio {
+            ReporterHealth::Unhealthy
+        } else if ratio >= self.degraded_ratio {
+            ReporterHealth::Degraded
+        } else {
+            ReporterHealth::Healthy
+        }
+    }
+
+    /// Records the outcome of a report, forgetting the oldest outcome if the window is full.
+    pub fn record(&self, succeeded: bool) {
+        let mut outcomes = self.lock_outcomes();
+        if outcomes.len() == self.window {
+            outcomes.pop_front();
+        }
+        outcomes.push_back(succeeded);
+    }
+
+    #[cfg(not(feature = "use_parking_lot"))]
+    fn lock_outcomes(&self) -> std::sync::MutexGuard<'_, VecDeque<bool>> {
+        self.outcomes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "use_parking_lot")]
+    fn lock_outcomes(&self) -> parking_lot::MutexGuard<'_, VecDeque<bool>> {
+        self.outcomes.lock()
+    }
+}
+
+impl Default for HealthHandle {
+    /// Judges health from the last [`DEFAULT_HEALTH_WINDOW`] outcomes, degraded from 10%
+    /// failures and unhealthy from 50%.
+    fn default() -> Self {
+        HealthHandle::new(DEFAULT_HEALTH_WINDOW, 0.1, 0.5)
+    }
+}
+
+impl fmt::Debug for HealthHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HealthHandle")
+            .field("window", &self.window)
+            .field("degraded_ratio", &self.degraded_ratio)
+            .field("unhealthy_ratio", &self.unhealthy_ratio)
+            .field("health", &self.health())
+            .finish()
+    }
+}
+
+/// Reporter that tracks the outcome of each report to another reporter, as its
+/// [`health`](Reporter::health) and that of its [`HealthHandle`]
+///
+/// Each report, batch or flush counts as one outcome. Reporters which send in the background,
+/// e.g. a [`LibhoneyReporter`], only fail to report if they cannot accept the data, so their
+/// health is better tracked where they learn the outcome of sending it, as
+/// [`new_honeycomb_telemetry_layer_with_health`](crate::new_honeycomb_telemetry_layer_with_health)
+/// does, reporting it via a `HealthReporter` returned by [`untracked`](Self::untracked).
+#[derive(Debug)]
+pub struct HealthReporter<R> {
+    reporter: R,
+    handle: HealthHandle,
+    tracked: bool,
+}
+
+impl<R: Reporter> HealthReporter<R> {
+    /// Returns a new `HealthReporter` tracking the health of `reporter` in `handle`.
+    pub fn new(reporter: R, handle: HealthHandle) -> Self {
+        HealthReporter {
+            reporter,
+            handle,
+            tracked: true,
+        }
+    }
+
+    /// Returns a new `HealthReporter` reporting the health judged from the outcomes recorded
+    /// in `handle` elsewhere, without recording the outcome of each report to `reporter`.
+    pub fn untracked(reporter: R, handle: HealthHandle) -> Self {
+        HealthReporter {
+            reporter,
+            handle,
+            tracked: false,
+        }
+    }
+
+    /// Returns a handle to the health of the reporter.
+    pub fn handle(&self) -> HealthHandle {
+        self.handle.clone()
+    }
+
+    fn track(&self, res: Result<(), ReportError>) -> Result<(), ReportError> {
+        if self.tracked {
+            self.handle.record(res.is_ok());
+        }
+        res
+    }
+}
+
+impl<R: Reporter> Reporter for HealthReporter<R> {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        self.track(self.reporter.report_data(data, timestamp))
+    }
+
+    fn report_batch(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<(), ReportError> {
+        self.track(self.reporter.report_batch(batch))
+    }
+
+    fn flush(&self) -> Result<(), ReportError> {
+        self.track(self.reporter.flush())
+    }
+
+    fn shutdown(&self) -> Result<(), ReportError> {
+        self.reporter.shutdown()
+    }
+
+    /// The worse of the tracked health, and that reported by the wrapped reporter.
+    fn health(&self) -> ReporterHealth {
+        self.handle.health().max(self.reporter.health())
+    }
+}
+
+type RoutingPredicate = dyn Fn(&HashMap<String, libhoney::Value>) -> bool + Send + Sync;
+
+/// Reporter that sends each span or event to one of two reporters, depending on a predicate
+///
+/// E.g. to send `ERROR`-level data to a separate reporter:
+///
+/// ```
+/// use libhoney::json;
+/// use tracing_honeycomb::{fields, RoutingReporter, TestReporter};
+///
+/// let errors = TestReporter::new();
+/// let reporter = RoutingReporter::new(
+///     |data| data.get(fields::LEVEL) == Some(&json!("ERROR")),
+///     errors.clone(),
+///     TestReporter::new(),
+/// );
+/// ```
+pub struct RoutingReporter<A, B> {
+    predicate: Box<RoutingPredicate>,
+    matching: A,
+    other: B,
+}
+
+impl<A: Reporter, B: Reporter> RoutingReporter<A, B> {
+    /// Returns a new `RoutingReporter` sending data for which `predicate` returns `true` to
+    /// `matching`, and all other data to `other`
+    pub fn new<F>(predicate: F, matching: A, other: B) -> Self
+    where
+        F: Fn(&HashMap<String, libhoney::Value>) -> bool + Send + Sync + 'static,
+    {
+        RoutingReporter {
+            predicate: Box::new(predicate),
+            matching,
+            other,
+        }
+    }
+}
+
+impl<A: Reporter, B: Reporter> Reporter for RoutingReporter<A, B> {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        if (self.predicate)(&data) {
+            self.matching.report_data(data, timestamp)
+        } else {
+            self.other.report_data(data, timestamp)
+        }
+    }
+
+    /// Splits the batch in two, preserving order, and reports each part to its reporter even
+    /// if the other fails, in which case the first error is returned.
+    fn report_batch(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<(), ReportError> {
+        let (matching, other): (Vec<_>, Vec<_>) = batch
+            .into_iter()
+            .partition(|(data, _)| (self.predicate)(data));
+
+        let mut res = Ok(());
+        if !matching.is_empty() {
+            res = res.and(self.matching.report_batch(matching));
+        }
+        if !other.is_empty() {
+            res = res.and(self.other.report_batch(other));
+        }
+        res
+    }
+
+    /// Flushes both reporters even if one fails, in which case the first error is returned.
+    fn flush(&self) -> Result<(), ReportError> {
+        let res = self.matching.flush();
+        res.and(self.other.flush())
+    }
+
+    /// Shuts down both reporters even if one fails, in which case the first error is returned.
+    fn shutdown(&self) -> Result<(), ReportError> {
+        let res = self.matching.shutdown();
+        res.and(self.other.shutdown())
+    }
+
+    /// The worse health of the two reporters.
+    fn health(&self) -> ReporterHealth {
+        self.matching.health().max(self.other.health())
+    }
+}
+
+impl<A: fmt::Debug, B: fmt::Debug> fmt::Debug for RoutingReporter<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoutingReporter")
+            .field("matching", &self.matching)
+            .field("other", &self.other)
+            .finish()
+    }
+}
+
+/// Reporter that sends events and spans to stdout
+///
+/// Each span or event is printed as a single line of JSON, whose fields are only in a stable
+/// order with the `sorted_fields` feature. See [`PrettyStdoutReporter`] for output meant to be
+/// read during local development.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutReporter;
+
+/// Serializes a set of fields as a JSON object. With the `sorted_fields` feature, the fields
+/// are written in order of their names, so that output is stable between runs, at the cost of
+/// collecting and sorting them on every report; otherwise they are written in `HashMap` order.
+/// Nested objects are always sorted, as `serde_json` maps are. [`DATASET_FIELD`] is omitted.
+pub(crate) fn fields_to_json(
+    data: &HashMap<String, libhoney::Value>,
+) -> Result<String, serde_json::Error> {
+    #[cfg(feature = "sorted_fields")]
+    {
+        let data: BTreeMap<_, _> = data
+            .iter()
+            .filter(|(name, _)| *name != DATASET_FIELD)
+            .collect();
+        serde_json::to_string(&data)
+    }
+    #[cfg(not(feature = "sorted_fields"))]
+    {
+        // only routed data pays for collecting the remaining fields
+        if data.contains_key(DATASET_FIELD) {
+            let data: HashMap<_, _> = data
+                .iter()
+                .filter(|(name, _)| *name != DATASET_FIELD)
+                .collect();
+            serde_json::to_string(&data)
+        } else {
+            serde_json::to_string(data)
+        }
+    }
+}
+
+impl Reporter for StdoutReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        _timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        let data = fields_to_json(&data)?;
+        println!("{}", data);
+        Ok(())
+    }
+}
+
+/// Reporter that sends events and spans to stdout as indented JSON with sorted keys, for
+/// reading during local development
+///
+/// Keys, including those of nested objects, are colorized with ANSI escape codes if stdout is
+/// a terminal.
+#[derive(Debug, Clone, Copy)]
+pub struct PrettyStdoutReporter {
+    color: bool,
+}
+
+const KEY_COLOR: &str = "\x1b[36m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+impl PrettyStdoutReporter {
+    /// Returns a new `PrettyStdoutReporter`, colorizing keys if stdout is a terminal.
+    pub fn new() -> Self {
+        PrettyStdoutReporter {
+            color: io::stdout().is_terminal(),
+        }
+    }
+
+    /// Sets whether keys are colorized, regardless of whether stdout is a terminal.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    fn render(&self, data: &HashMap<String, libhoney::Value>) -> Result<String, ReportError> {
+        let data: BTreeMap<_, _> = data
+            .iter()
+            .filter(|(name, _)| *name != DATASET_FIELD)
+            .collect();
+        if !self.color {
+            return Ok(serde_json::to_string_pretty(&data)?);
+        }
+
+        let mut rendered = String::new();
+        write_colored_object(&mut rendered, data.into_iter(), 0)?;
+        Ok(rendered)
+    }
+}
+
+impl Default for PrettyStdoutReporter {
+    fn default() -> Self {
+        PrettyStdoutReporter::new()
+    }
+}
+
+impl Reporter for PrettyStdoutReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        _timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        let data = self.render(&data)?;
+        println!("{}", data);
+        Ok(())
+    }
+}
+
+// writes a JSON object laid out as by `serde_json::to_string_pretty`, with colorized keys
+fn write_colored_object<'a>(
+    out: &mut String,
+    entries: impl ExactSizeIterator<Item = (&'a String, &'a libhoney::Value)>,
+    indent: usize,
+) -> Result<(), serde_json::Error> {
+    if entries.len() == 0 {
+        out.push_str("{}");
+        return Ok(());
+    }
+
+    out.push_str("{\n");
+    let len = entries.len();
+    for (i, (key, value)) in entries.enumerate() {
+        out.push_str(&" ".repeat(indent + 2));
+        out.push_str(KEY_COLOR);
+        out.push_str(&serde_json::to_string(key)?);
+        out.push_str(RESET_COLOR);
+        out.push_str(": ");
+        write_colored_value(out, value, indent + 2)?;
+        if i + 1 < len {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str(&" ".repeat(indent));
+    out.push('}');
+    Ok(())
+}
+
+fn write_colored_value(
+    out: &mut String,
+    value: &libhoney::Value,
+    indent: usize,
+) -> Result<(), serde_json::Error> {
+    match value {
+        libhoney::Value::Object(map) => write_colored_object(out, map.iter(), indent),
+        libhoney::Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&" ".repeat(indent + 2));
+                write_colored_value(out, item, indent + 2)?;
+                if i + 1 < items.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&" ".repeat(indent));
+            out.push(']');
+            Ok(())
+        }
+        _ => {
+            out.push_str(&serde_json::to_string(value)?);
+            Ok(())
+        }
+    }
+}
+
+/// Reporter that records events and spans in memory, e.g. to assert on them in tests
+///
+/// Clones share the same recorded data, so a clone can be kept to inspect data reported via
+/// a `TelemetryLayer`.
+#[derive(Debug, Clone, Default)]
+pub struct TestReporter {
+    recorded: Arc<Mutex<Vec<HashMap<String, libhoney::Value>>>>,
+}
+
+impl TestReporter {
+    /// Returns a new, empty, `TestReporter`
+    pub fn new() -> Self {
+        TestReporter::default()
+    }
+
+    /// Returns all data reported so far, in the order in which it was reported
+    pub fn recorded(&self) -> Vec<HashMap<String, libhoney::Value>> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let recorded = self.recorded.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let recorded = self.recorded.lock();
+
+        recorded.clone()
+    }
+
+    /// Discards all data reported so far
+    pub fn clear(&self) {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut recorded = self.recorded.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut recorded = self.recorded.lock();
+
+        recorded.clear();
+    }
+}
+
+impl Reporter for TestReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        _timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut recorded = self.recorded.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut recorded = self.recorded.lock();
+
+        recorded.push(data);
+        Ok(())
+    }
+}
+
+/// Reporter that counts spans rather than forwarding them, rendering the counts as
+/// OpenMetrics text, e.g. to serve from a Prometheus scrape endpoint
+///
+/// Spans are counted in `spans_total`, labelled by their `name` and `level`, along with the
+/// value of each of the fields given on construction, which is empty for spans without it.
+/// Only those fields become labels, so the number of series stays bounded as long as they
+/// only take a few values. Events are not counted.
+///
+/// Clones share the same counts, so a clone can be added to a [`MultiReporter`] while
+/// another is kept to [`gather`](Self::gather) the counts.
+#[derive(Debug, Clone)]
+pub struct MetricsReporter {
+    label_fields: Arc<[String]>,
+    counts: Arc<Mutex<BTreeMap<Vec<String>, u64>>>,
+}
+
+impl MetricsReporter {
+    /// Returns a new `MetricsReporter`, labelling counts by the given fields along with each
+    /// span's name and level.
+    pub fn new<I, S>(label_fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        MetricsReporter {
+            label_fields: label_fields.into_iter().map(Into::into).collect(),
+            counts: Arc::default(),
+        }
+    }
+
+    /// Renders the counts so far in the OpenMetrics text format.
+    ///
+    /// Characters other than ASCII letters, digits and `_` in field names are replaced with
+    /// `_` to form label names, e.g. `http.route` is labelled `http_route`.
+    pub fn gather(&self) -> String {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let counts = self.counts.lock();
+
+        let names: Vec<String> = ["name", "level"]
+            .iter()
+            .copied()
+            .chain(self.label_fields.iter().map(String::as_str))
+            .map(label_name)
+            .collect();
+
+        let mut out = String::from("# TYPE spans counter\n");
+        for (values, count) in counts.iter() {
+            let labels: Vec<String> = names
+                .iter()
+                .zip(values)
+                .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+                .collect();
+            out.push_str(&format!("spans_total{{{}}} {}\n", labels.join(","), count));
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+impl Reporter for MetricsReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        _timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        // events and span links carry no span id
+        if !data.contains_key(crate::fields::SPAN_ID) {
+            return Ok(());
+        }
+
+        let values = [crate::fields::NAME, crate::fields::LEVEL]
+            .iter()
+            .copied()
+            .chain(self.label_fields.iter().map(String::as_str))
+            .map(|field| match data.get(field) {
+                Some(libhoney::Value::String(value)) => value.clone(),
+                Some(value) => value.to_string(),
+                None => String::new(),
+            })
+            .collect();
+
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut counts = self.counts.lock();
+
+        *counts.entry(values).or_default() += 1;
+        Ok(())
+    }
+}
+
+fn label_name(field: &str) -> String {
+    field
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// What a reporter does with data it has no capacity for, e.g. a [`RateLimitReporter`] over
+/// its rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the data, never blocking the traced code path
+    Drop,
+    /// Block the reporting thread until there is capacity for the data
+    Block,
+}
+
+// token bucket holding up to a second's worth of tokens, along with the time it was last
+// refilled
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Reporter that forwards at most `events_per_second` events and spans per second to another
+/// reporter, e.g. to protect an event budget from a pathological loop
+///
+/// Rates are enforced by a token bucket allowing bursts of up to a second's worth of data.
+/// Data over the rate is either dropped and counted, or delayed until it is within the rate,
+/// as selected by the [`OverflowPolicy`].
+pub struct RateLimitReporter<R> {
+    reporter: R,
+    events_per_second: u32,
+    overflow: OverflowPolicy,
+    bucket: Mutex<TokenBucket>,
+    dropped: AtomicUsize,
+}
+
+impl<R: Reporter> RateLimitReporter<R> {
+    /// Returns a new `RateLimitReporter` forwarding at most `events_per_second` to `reporter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `events_per_second` is 0.
+    pub fn new(reporter: R, events_per_second: u32, overflow: OverflowPolicy) -> Self {
+        assert!(events_per_second > 0, "events_per_second must be non-zero");
+
+        RateLimitReporter {
+            reporter,
+            events_per_second,
+            overflow,
+            bucket: Mutex::new(TokenBucket {
+                tokens: f64::from(events_per_second),
+                refilled_at: Instant::now(),
+            }),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of events and spans dropped for being over the rate so far
+    pub fn dropped_count(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // takes a token if available, or returns how long until one will be
+    fn try_acquire(&self) -> Result<(), Duration> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut bucket = self.bucket.lock();
+
+        let rate = f64::from(self.events_per_second);
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(rate);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+impl<R: Reporter> Reporter for RateLimitReporter<R> {
+    /// Data dropped for being over the rate is not an error, and is only reflected in
+    /// [`dropped_count`](Self::dropped_count).
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        loop {
+            match (self.try_acquire(), self.overflow) {
+                (Ok(()), _) => return self.reporter.report_data(data, timestamp),
+                (Err(_), OverflowPolicy::Drop) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                (Err(wait), OverflowPolicy::Block) => std::thread::sleep(wait),
+            }
+        }
+    }
+
+    fn flush(&self) -> Result<(), ReportError> {
+        self.reporter.flush()
+    }
+
+    fn shutdown(&self) -> Result<(), ReportError> {
+        self.reporter.shutdown()
+    }
+
+    fn health(&self) -> ReporterHealth {
+        self.reporter.health()
+    }
+}
+
+impl<R> fmt::Debug for RateLimitReporter<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitReporter")
+            .field("events_per_second", &self.events_per_second)
+            .field("overflow", &self.overflow)
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+/// Reporter that forwards only one in `sample_rate` span events to another reporter, along with
+/// every span
+///
+/// Suited to spans which produce far more events than are worth keeping, e.g. a loop logging
+/// each iteration. Events, i.e. data with [`meta.annotation_type`](crate::fields::ANNOTATION_TYPE)
+/// set to [`span_event`](crate::fields::SPAN_EVENT), are kept at random; spans and span links
+/// are always forwarded.
+///
+/// This is applied after, and independently of, the layer's trace [`Sampler`](crate::Sampler):
+/// a trace the sampler drops is dropped whole, while a trace it keeps is reported with all of
+/// its spans, so it stays complete in Honeycomb's trace view, but only some of its events.
+/// Kept events are marked with this rate via [`SAMPLE_RATE_FIELD`], multiplying any rate
+/// they already carry, so that Honeycomb weights them by it in counts and sums.
+pub struct EventSamplingReporter<R> {
+    reporter: R,
+    sample_rate: u32,
+    dropped: AtomicUsize,
+}
+
+impl<R: Reporter> EventSamplingReporter<R> {
+    /// Returns a new `EventSamplingReporter` forwarding one in `sample_rate` events to
+    /// `reporter`. A rate of 1 forwards every event.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is 0.
+    pub fn new(reporter: R, sample_rate: u32) -> Self {
+        assert!(sample_rate > 0, "sample_rate must be non-zero");
+
+        EventSamplingReporter {
+            reporter,
+            sample_rate,
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of events dropped by sampling so far
+    pub fn dropped_count(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // whether to forward the data, counting it as dropped if not, and marking kept events with
+    // the sample rate
+    fn keep(&self, data: &mut HashMap<String, libhoney::Value>) -> bool {
+        let is_event = data.get(crate::fields::ANNOTATION_TYPE)
+            == Some(&libhoney::Value::from(crate::fields::SPAN_EVENT));
+        if !is_event || self.sample_rate == 1 {
+            return true;
+        }
+        if rand::thread_rng().gen_range(0, self.sample_rate) != 0 {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        let sample_rate = data
+            .get(SAMPLE_RATE_FIELD)
+            .and_then(libhoney::Value::as_u64)
+            .unwrap_or(1)
+            .saturating_mul(u64::from(self.sample_rate));
+        data.insert(SAMPLE_RATE_FIELD.to_string(), sample_rate.into());
+        true
+    }
+}
+
+impl<R: Reporter> Reporter for EventSamplingReporter<R> {
+    /// Events dropped by sampling are not an error, and are only reflected in
+    /// [`dropped_count`](Self::dropped_count).
+    fn report_data(
+        &self,
+        mut data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        if self.keep(&mut data) {
+            self.reporter.report_data(data, timestamp)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn report_batch(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<(), ReportError> {
+        let batch: Vec<_> = batch
+            .into_iter()
+            .filter_map(|(mut data, timestamp)| {
+                if self.keep(&mut data) {
+                    Some((data, timestamp))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_batch(batch)
+    }
+
+    fn flush(&self) -> Result<(), ReportError> {
+        self.reporter.flush()
+    }
+
+    fn shutdown(&self) -> Result<(), ReportError> {
+        self.reporter.shutdown()
+    }
+
+    fn health(&self) -> ReporterHealth {
+        self.reporter.health()
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for EventSamplingReporter<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventSamplingReporter")
+            .field("reporter", &self.reporter)
+            .field("sample_rate", &self.sample_rate)
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+/// How a [`RetryReporter`] retries failed reports
+///
+/// Retries back off exponentially from `initial_backoff`, doubling on each attempt up to
+/// `max_backoff`, with each wait randomly jittered to between half and all of the backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    deadline: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns a new `RetryPolicy` retrying up to `max_retries` times, with the default
+    /// backoff and deadline.
+    pub fn new(max_retries: u32) -> Self {
+        RetryPolicy {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the backoff before the first retry. Defaults to 100ms.
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the maximum backoff between retries. Defaults to 5s.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the total time spent reporting each event or span, after which it is given up on
+    /// rather than retried again. Defaults to 10s.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    // jittered backoff before the given retry, counting from 0
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = 2u32
+            .checked_pow(retry)
+            .and_then(|factor| self.initial_backoff.checked_mul(factor))
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff));
+        let half = backoff / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Retries up to 3 times, see the `with_*` methods for the other defaults.
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            deadline: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Reporter that retries data another reporter failed to report, as configured by a
+/// [`RetryPolicy`], e.g. to ride out transient network failures
+///
+/// Data is given up on once the policy's retries are exhausted, or when waiting to retry again
+/// would exceed its deadline. The last error is then returned, and the data counted as dropped.
+///
+/// Retrying blocks the reporting thread for up to the deadline per event or span. That
+/// includes the background task of a `TokioReporter` wrapping this one, which stops draining
+/// its channel meanwhile, so keep the deadline short there. Each event or span is held in
+/// memory, and cloned for each attempt, until it has been reported or given up on, so a failing
+/// backend holds one event or span per reporting thread, on top of any buffered by wrapping
+/// reporters.
+pub struct RetryReporter<R> {
+    reporter: R,
+    policy: RetryPolicy,
+    dropped: AtomicUsize,
+}
+
+impl<R: Reporter> RetryReporter<R> {
+    /// Returns a new `RetryReporter` retrying failed reports to `reporter` as per `policy`.
+    pub fn new(reporter: R, policy: RetryPolicy) -> Self {
+        RetryReporter {
+            reporter,
+            policy,
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of events and spans given up on so far
+    pub fn dropped_count(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<R: Reporter> Reporter for RetryReporter<R> {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        let started = Instant::now();
+        let mut retry = 0;
+        loop {
+            let err = match self.reporter.report_data(data.clone(), timestamp) {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            let backoff = self.policy.backoff(retry);
+            if retry >= self.policy.max_retries
+                || started.elapsed() + backoff > self.policy.deadline
+            {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return Err(err);
+            }
+            std::thread::sleep(backoff);
+            retry += 1;
+        }
+    }
+
+    fn flush(&self) -> Result<(), ReportError> {
+        self.reporter.flush()
+    }
+
+    fn shutdown(&self) -> Result<(), ReportError> {
+        self.reporter.shutdown()
+    }
+
+    fn health(&self) -> ReporterHealth {
+        self.reporter.health()
+    }
+}
+
+impl<R> fmt::Debug for RetryReporter<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryReporter")
+            .field("policy", &self.policy)
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+const FILE_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reporter that appends events and spans to a file as newline-delimited JSON
+///
+/// Writes are buffered, and flushed every second by a background thread, whether or not
+/// further data is reported, as well as on drop.
+pub struct FileReporter {
+    path: PathBuf,
+    writer: Arc<Mutex<BufWriter<File>>>,
+    // flushes the writer until the reporter is dropped
+    _worker: Worker<()>,
+}
+
+impl FileReporter {
+    /// Returns a new `FileReporter` appending to the file at `path`, creating it if needed.
+    ///
+    /// Spawns a thread which flushes buffered writes every second. The thread exits once the
+    /// `FileReporter` is dropped.
+    pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let writer = Arc::new(Mutex::new(BufWriter::new(file)));
+
+        let flushed = writer.clone();
+        let worker = Worker::periodic(FILE_FLUSH_INTERVAL, move || {
+            #[cfg(not(feature = "use_parking_lot"))]
+            let mut writer = flushed.lock().unwrap_or_else(PoisonError::into_inner);
+            #[cfg(feature = "use_parking_lot")]
+            let mut writer = flushed.lock();
+
+            // failures are retried on the next interval
+            let _ = writer.flush();
+        });
+
+        Ok(FileReporter {
+            path,
+            writer,
+            _worker: worker,
+        })
+    }
+
+    /// The path of the file being written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Flushes all buffered data to the file
+    pub fn flush(&self) -> io::Result<()> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut writer = self.writer.lock();
+
+        writer.flush()
+    }
+}
+
+impl Reporter for FileReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        _timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        let data = fields_to_json(&data)?;
+
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut writer = self.writer.lock();
+
+        writeln!(writer, "{}", data)?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), ReportError> {
+        FileReporter::flush(self)?;
+        Ok(())
+    }
+}
+
+impl Drop for FileReporter {
+    // the background thread may still hold the writer, so flush rather than waiting for it to
+    // drop the last reference
+    fn drop(&mut self) {
+        let _ = FileReporter::flush(self);
+    }
+}
+
+impl fmt::Debug for FileReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileReporter")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+const SPOOL_EXTENSION: &str = "json";
+
+type SpooledEntry = (HashMap<String, libhoney::Value>, DateTime<Utc>);
+
+struct SpoolState<R> {
+    reporter: R,
+    dir: PathBuf,
+    max_spooled: usize,
+    // spooled files, oldest first, along with the sequence number of the next one
+    spool: Mutex<(VecDeque<PathBuf>, u64)>,
+    // held while replaying, so that each spooled entry is replayed once and in order
+    replaying: Mutex<()>,
+    dropped: AtomicUsize,
+}
+
+impl<R: Reporter> SpoolState<R> {
+    #[cfg(not(feature = "use_parking_lot"))]
+    fn lock_spool(&self) -> std::sync::MutexGuard<'_, (VecDeque<PathBuf>, u64)> {
+        self.spool.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "use_parking_lot")]
+    fn lock_spool(&self) -> parking_lot::MutexGuard<'_, (VecDeque<PathBuf>, u64)> {
+        self.spool.lock()
+    }
+
+    fn spool(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        let entry = serde_json::to_vec(&serde_json::json!({
+            "time": timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            "data": data,
+        }))?;
+
+        let mut spool = self.lock_spool();
+        let path = self
+            .dir
+            .join(format!("{:020}.{}", spool.1, SPOOL_EXTENSION));
+        // written under another name first, so that a crash never leaves a partial entry
+        let partial = path.with_extension("partial");
+        fs::write(&partial, entry)?;
+        fs::rename(&partial, &path)?;
+
+        spool.1 += 1;
+        spool.0.push_back(path);
+        self.evict_excess(&mut spool.0);
+        Ok(())
+    }
+
+    fn evict_excess(&self, spooled: &mut VecDeque<PathBuf>) {
+        while spooled.len() > self.max_spooled {
+            if let Some(oldest) = spooled.pop_front() {
+                let _ = fs::remove_file(oldest);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // the spool is only locked between entries, so that reports failing meanwhile can still
+    // be spooled
+    fn replay(&self) -> Result<(), ReportError> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let _replaying = self
+            .replaying
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let _replaying = self.replaying.lock();
+
+        loop {
+            let path = match self.lock_spool().0.front() {
+                Some(path) => path.clone(),
+                None => return Ok(()),
+            };
+
+            match read_spooled(&path) {
+                Ok(Some((data, timestamp))) => self.reporter.report_data(data, timestamp)?,
+                // evicted since it was looked up
+                Ok(None) => {}
+                // unreadable entries would otherwise block the spool forever
+                Err(_) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            let mut spool = self.lock_spool();
+            if spool.0.front() == Some(&path) {
+                spool.0.pop_front();
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+fn read_spooled(path: &Path) -> Result<Option<SpooledEntry>, ReportError> {
+    let entry = match fs::read(path) {
+        Ok(entry) => entry,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut entry: serde_json::Map<String, libhoney::Value> = serde_json::from_slice(&entry)?;
+    let data = serde_json::from_value(entry.remove("data").unwrap_or_default())?;
+    let timestamp = entry
+        .get("time")
+        .and_then(libhoney::Value::as_str)
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid spooled timestamp"))?;
+
+    Ok(Some((data, timestamp.with_timezone(&Utc))))
+}
+
+/// Reporter that spools data another reporter failed to report to a local directory, and
+/// replays it once that reporter succeeds again, e.g. for deployments with flaky connectivity
+///
+/// Each failed span or event is written to the spool directory as a JSON file, and reporting it
+/// then succeeds. A background thread replays spooled data every `replay_interval`, oldest
+/// first, stopping at the first failure to try again on the next interval. Spooled data is
+/// also replayed on flush. Data spooled by a previous process using the same directory is
+/// picked up and replayed as well, so only one `SpoolingReporter` should use a directory at a
+/// time.
+///
+/// The spool holds at most `max_spooled` entries, beyond which the oldest are evicted and
+/// counted as dropped. Data reported while the spool is being replayed is reported
+/// immediately, so it may reach the backend ahead of older spooled data. Each span or event is
+/// cloned before being reported, to be able to spool it on failure.
+pub struct SpoolingReporter<R> {
+    state: Arc<SpoolState<R>>,
+    // replays spooled data until the reporter is dropped
+    _worker: Worker<()>,
+}
+
+impl<R> SpoolingReporter<R>
+where
+    R: Reporter + Send + Sync + 'static,
+{
+    /// Returns a new `SpoolingReporter` spooling data `reporter` failed to report to `dir`,
+    /// creating it if needed.
+    ///
+    /// Spawns a thread which replays spooled data every `replay_interval`. The thread exits
+    /// once the `SpoolingReporter` is dropped.
+    pub fn new(
+        reporter: R,
+        dir: impl Into<PathBuf>,
+        max_spooled: usize,
+        replay_interval: Duration,
+    ) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut spooled = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(OsStr::new(SPOOL_EXTENSION)) {
+                continue;
+            }
+            let seq = path
+                .file_stem()
+                .and_then(|stem| stem.to_str()?.parse::<u64>().ok());
+            if let Some(seq) = seq {
+                spooled.push((seq, path));
+            }
+        }
+        spooled.sort();
+        let next_seq = spooled.last().map_or(0, |(seq, _)| seq + 1);
+        let mut spooled: VecDeque<PathBuf> = spooled.into_iter().map(|(_, path)| path).collect();
+
+        let state = Arc::new(SpoolState {
+            reporter,
+            dir,
+            max_spooled,
+            spool: Mutex::new((VecDeque::new(), next_seq)),
+            replaying: Mutex::new(()),
+            dropped: AtomicUsize::new(0),
+        });
+        state.evict_excess(&mut spooled);
+        state.lock_spool().0 = spooled;
+
+        let replayed = state.clone();
+        let worker = Worker::periodic(replay_interval, move || {
+            // failures are retried on the next interval
+            let _ = replayed.replay();
+        });
+
+        Ok(SpoolingReporter {
+            state,
+            _worker: worker,
+        })
+    }
+}
+
+impl<R: Reporter> SpoolingReporter<R> {
+    /// The directory data is spooled to
+    pub fn dir(&self) -> &Path {
+        &self.state.dir
+    }
+
+    /// The number of events and spans currently spooled
+    pub fn spooled_count(&self) -> usize {
+        self.state.lock_spool().0.len()
+    }
+
+    /// The number of spooled events and spans evicted or found unreadable so far
+    pub fn dropped_count(&self) -> usize {
+        self.state.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<R: Reporter> Reporter for SpoolingReporter<R> {
+    /// Errors only if the data could be neither reported nor spooled.
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        match self.state.reporter.report_data(data.clone(), timestamp) {
+            Ok(()) => Ok(()),
+            Err(_) => self.state.spool(data, timestamp),
+        }
+    }
+
+    /// Replays spooled data, then flushes the inner reporter.
+    fn flush(&self) -> Result<(), ReportError> {
+        let res = self.state.replay();
+        res.and(self.state.reporter.flush())
+    }
+
+    /// Replays spooled data, then shuts the inner reporter down. Data which could not be
+    /// replayed remains spooled for the next `SpoolingReporter` using the same directory.
+    fn shutdown(&self) -> Result<(), ReportError> {
+        let res = self.state.replay();
+        res.and(self.state.reporter.shutdown())
+    }
+
+    fn health(&self) -> ReporterHealth {
+        self.state.reporter.health()
+    }
+}
+
+impl<R> fmt::Debug for SpoolingReporter<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpoolingReporter")
+            .field("dir", &self.state.dir)
+            .field("max_spooled", &self.state.max_spooled)
+            .field("dropped", &self.state.dropped)
+            .finish()
+    }
+}
+
+type BatchJsonSender = dyn Fn(Option<&str>, String) -> Result<(), ReportError> + Send + Sync;
+
+/// Reporter that renders events and spans as the JSON body of a request to Honeycomb's
+/// `/1/batch/{dataset}` endpoint, and hands it to a callback, e.g. to forward it to a custom
+/// HTTP endpoint without a libhoney client.
+///
+/// Each body is an array with one object per span or event, in the order reported:
+///
+/// ```json
+/// [{"time":"2021-03-04T05:06:07.890123456Z","samplerate":1,"data":{"name":"root"}}]
+/// ```
+///
+/// - `time` is the span or event's timestamp as an RFC 3339 string, always in UTC with a `Z`
+///   suffix and exactly nine fractional second digits, i.e. `YYYY-MM-DDTHH:MM:SS.nnnnnnnnnZ`.
+/// - `samplerate` is the rate given to [`BatchJsonReporter::new`], which should match the
+///   trace-level sample rate so that Honeycomb weights sampled data accordingly, multiplied by
+///   [`SAMPLE_RATE_FIELD`] if set.
+/// - `data` holds the fields, minus [`DATASET_FIELD`] and [`SAMPLE_RATE_FIELD`].
+///
+/// The callback is invoked once per dataset with that dataset, taken from [`DATASET_FIELD`],
+/// or `None` for spans and events without it. `report_data` renders an array of one, so wrap
+/// this reporter in a [`BatchingReporter`] to render larger batches.
+pub struct BatchJsonReporter {
+    sample_rate: u32,
+    send: Box<BatchJsonSender>,
+}
+
+impl BatchJsonReporter {
+    /// Returns a new `BatchJsonReporter` rendering `samplerate` as `sample_rate` and passing
+    /// each rendered body to `send`.
+    pub fn new<F>(sample_rate: u32, send: F) -> Self
+    where
+        F: Fn(Option<&str>, String) -> Result<(), ReportError> + Send + Sync + 'static,
+    {
+        BatchJsonReporter {
+            sample_rate,
+            send: Box::new(send),
+        }
+    }
+
+    /// Renders `batch` as one batch request body per dataset, in order of each dataset's first
+    /// appearance, without sending them.
+    pub fn render(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<Vec<(Option<String>, String)>, ReportError> {
+        let mut by_dataset: Vec<(Option<String>, Vec<libhoney::Value>)> = Vec::new();
+        for (mut data, timestamp) in batch {
+            let dataset = match data.remove(DATASET_FIELD) {
+                Some(libhoney::Value::String(dataset)) => Some(dataset),
+                _ => None,
+            };
+            let sample_rate = take_sample_rate(&mut data)
+                .unwrap_or(1)
+                .saturating_mul(u64::from(self.sample_rate));
+            let entry = serde_json::json!({
+                "time": timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true),
+                "samplerate": sample_rate,
+                "data": data,
+            });
+
+            match by_dataset.iter_mut().find(|(d, _)| *d == dataset) {
+                Some((_, entries)) => entries.push(entry),
+                None => by_dataset.push((dataset, vec![entry])),
+            }
+        }
+
+        by_dataset
+            .into_iter()
+            .map(|(dataset, entries)| Ok((dataset, serde_json::to_string(&entries)?)))
+            .collect()
+    }
+}
+
+impl Reporter for BatchJsonReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        self.report_batch(vec![(data, timestamp)])
+    }
+
+    fn report_batch(
+        &self,
+        batch: Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>,
+    ) -> Result<(), ReportError> {
+        let mut res = Ok(());
+        for (dataset, body) in self.render(batch)? {
+            res = res.and((self.send)(dataset.as_deref(), body));
+        }
+        res
+    }
+}
+
+impl fmt::Debug for BatchJsonReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchJsonReporter")
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+
+type Batch = Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)>;
+
+struct BatchState<R> {
+    reporter: R,
+    batch_size: usize,
+    max_latency: Duration,
+    // buffered data along with the time the oldest entry was buffered
+    buffer: Mutex<(Batch, Option<Instant>)>,
+}
+
+impl<R: Reporter> BatchState<R> {
+    fn flush(&self) -> Result<(), ReportError> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut buffer = self.buffer.lock();
+
+        self.flush_locked(&mut buffer)
+    }
+
+    fn flush_stale(&self) -> Result<(), ReportError> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut buffer = self.buffer.lock();
+
+        match buffer.1 {
+            Some(oldest) if oldest.elapsed() >= self.max_latency => self.flush_locked(&mut buffer),
+            _ => Ok(()),
+        }
+    }
+
+    // forwarding while holding the lock preserves the order in which data was reported
+    fn flush_locked(&self, buffer: &mut (Batch, Option<Instant>)) -> Result<(), ReportError> {
+        buffer.1 = None;
+        if buffer.0.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut buffer.0);
+        self.reporter.report_batch(batch)
+    }
+}
+
+/// Reporter that buffers data, forwarding it to another reporter in batches
+///
+/// A batch is forwarded once `batch_size` entries have been buffered or the oldest buffered
+/// entry is `max_latency` old, whichever comes first. Data is forwarded in the order in which
+/// it was reported, so spans still reconstruct correctly on the backend.
+///
+/// Any remaining buffered data is flushed on drop.
+pub struct BatchingReporter<R: Reporter> {
+    state: Arc<BatchState<R>>,
+    // forwards stale batches until the reporter is dropped
+    _worker: Worker<()>,
+}
+
+impl<R> BatchingReporter<R>
+where
+    R: Reporter + Send + Sync + 'static,
+{
+    /// Returns a new `BatchingReporter` forwarding batches to `reporter`.
+    ///
+    /// Spawns a thread which flushes buffered data after `max_latency`. The thread exits
+    /// once the `BatchingReporter` is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is 0 or `max_latency` is zero.
+    pub fn new(reporter: R, batch_size: usize, max_latency: Duration) -> Self {
+        assert!(batch_size > 0, "batch_size must be non-zero");
+        // the thread would otherwise spin without sleeping
+        assert!(max_latency > Duration::ZERO, "max_latency must be non-zero");
+
+        let state = Arc::new(BatchState {
+            reporter,
+            batch_size,
+            max_latency,
+            buffer: Mutex::new((Vec::with_capacity(batch_size), None)),
+        });
+
+        let flushed = state.clone();
+        let worker = Worker::periodic(max_latency, move || {
+            // there is no caller to hand errors back to from here
+            let _ = flushed.flush_stale();
+        });
+
+        BatchingReporter {
+            state,
+            _worker: worker,
+        }
+    }
+}
+
+impl<R: Reporter> BatchingReporter<R> {
+    /// Forwards all buffered data immediately
+    pub fn flush(&self) -> Result<(), ReportError> {
+        self.state.flush()
+    }
+}
+
+impl<R: Reporter> Reporter for BatchingReporter<R> {
+    /// Errors are those of the batch forwarded by this call, if any.
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut buffer = self
+            .state
+            .buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut buffer = self.state.buffer.lock();
+
+        buffer.0.push((data, timestamp));
+        let oldest = *buffer.1.get_or_insert_with(Instant::now);
+
+        if buffer.0.len() >= self.state.batch_size || oldest.elapsed() >= self.state.max_latency {
+            self.state.flush_locked(&mut buffer)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Forwards all buffered data, then flushes the wrapped reporter.
+    fn flush(&self) -> Result<(), ReportError> {
+        BatchingReporter::flush(self)?;
+        self.state.reporter.flush()
+    }
+
+    /// Forwards all buffered data, then shuts down the wrapped reporter.
+    fn shutdown(&self) -> Result<(), ReportError> {
+        BatchingReporter::flush(self)?;
+        self.state.reporter.shutdown()
+    }
+
+    fn health(&self) -> ReporterHealth {
+        self.state.reporter.health()
+    }
+}
+
+impl<R: Reporter> Drop for BatchingReporter<R> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl<R: Reporter> fmt::Debug for BatchingReporter<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchingReporter")
+            .field("batch_size", &self.state.batch_size)
+            .field("max_latency", &self.state.max_latency)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ChannelReporter;
+    use chrono::TimeZone;
+    use libhoney::json;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_reporter_records_spans_and_events() {
+        let reporter = TestReporter::new();
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        let trace_id = crate::TraceId::new();
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", user = "alice");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(trace_id.clone(), None).unwrap();
+            tracing::info!(count = 3, "event");
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 2);
+        let (event, span) = (&recorded[0], &recorded[1]);
+        assert_eq!(event.get("count"), Some(&json!(3)));
+        assert_eq!(span.get("name"), Some(&json!("root")));
+        assert_eq!(span.get("user"), Some(&json!("alice")));
+        for data in &recorded {
+            assert_eq!(
+                data.get("trace.trace_id"),
+                Some(&json!(trace_id.to_string()))
+            );
+        }
+
+        reporter.clear();
+        assert!(reporter.recorded().is_empty());
+    }
+
+    struct SlowFlushReporter(Duration);
+
+    impl Reporter for SlowFlushReporter {
+        fn report_data(
+            &self,
+            _: HashMap<String, libhoney::Value>,
+            _: DateTime<Utc>,
+        ) -> Result<(), ReportError> {
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<(), ReportError> {
+            std::thread::sleep(self.0);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct FlushCounter {
+        flushes: AtomicUsize,
+        shutdowns: AtomicUsize,
+    }
+
+    impl Reporter for FlushCounter {
+        fn report_data(
+            &self,
+            _: HashMap<String, libhoney::Value>,
+            _: DateTime<Utc>,
+        ) -> Result<(), ReportError> {
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<(), ReportError> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn shutdown(&self) -> Result<(), ReportError> {
+            self.shutdowns.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flush_guard_shuts_reporter_down_once() {
+        let reporter = Arc::new(FlushCounter::default());
+        let decided = Arc::new(AtomicUsize::new(0));
+        let counted = decided.clone();
+        let guard = FlushGuard::new(reporter.clone()).with_decide_pending(Arc::new(move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        guard.flush().unwrap();
+        assert_eq!(reporter.flushes.load(Ordering::SeqCst), 1);
+        assert_eq!(reporter.shutdowns.load(Ordering::SeqCst), 0);
+        drop(guard);
+        assert_eq!(reporter.flushes.load(Ordering::SeqCst), 1);
+        assert_eq!(reporter.shutdowns.load(Ordering::SeqCst), 1);
+        assert_eq!(decided.load(Ordering::SeqCst), 2);
+
+        // shutting down explicitly consumes the guard, so dropping it does nothing more
+        let reporter = Arc::new(FlushCounter::default());
+        FlushGuard::new(reporter.clone()).shutdown().unwrap();
+        assert_eq!(reporter.shutdowns.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn flush_guard_gives_up_after_timeout() {
+        let guard = FlushGuard::new(Arc::new(SlowFlushReporter(Duration::from_millis(10))));
+        assert!(guard.flush_timeout(Duration::from_secs(10)));
+        assert!(guard.shutdown_timeout(Duration::from_secs(10)));
+
+        let guard = FlushGuard::new(Arc::new(SlowFlushReporter(Duration::from_secs(10))));
+        let start = Instant::now();
+        assert!(!guard.flush_timeout(Duration::from_millis(10)));
+        assert!(!guard.shutdown_timeout(Duration::from_millis(10)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn reloadable_reporter_swaps_reporter() {
+        let (first, second) = (TestReporter::new(), TestReporter::new());
+        let (layer, handle) =
+            crate::new_honeycomb_telemetry_layer_reloadable("test", Box::new(first.clone()));
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(crate::TraceId::new(), None).unwrap();
+            tracing::info!("before");
+            handle.set_reporter(Box::new(second.clone())).unwrap();
+            tracing::info!("after");
+        });
+
+        assert_eq!(first.recorded().len(), 1);
+        assert_eq!(first.recorded()[0][crate::fields::MESSAGE], json!("before"));
+        // the span closed after the swap
+        assert_eq!(second.recorded().len(), 2);
+        assert_eq!(second.recorded()[0][crate::fields::MESSAGE], json!("after"));
+        assert_eq!(second.recorded()[1]["name"], json!("root"));
+    }
+
+    // reports to `inner` once released, signalling `entered` first, and is unhealthy to tell
+    // it apart from `inner`
+    struct BlockingReporter {
+        entered: std::sync::Mutex<mpsc::Sender<()>>,
+        gate: std::sync::Mutex<mpsc::Receiver<()>>,
+        inner: TestReporter,
+    }
+
+    impl Reporter for BlockingReporter {
+        fn report_data(
+            &self,
+            data: HashMap<String, libhoney::Value>,
+            timestamp: DateTime<Utc>,
+        ) -> Result<(), ReportError> {
+            self.entered.lock().unwrap().send(()).unwrap();
+            self.gate.lock().unwrap().recv().unwrap();
+            self.inner.report_data(data, timestamp)
+        }
+
+        fn health(&self) -> ReporterHealth {
+            ReporterHealth::Unhealthy
+        }
+    }
+
+    #[test]
+    fn reloadable_reporter_swaps_during_slow_reports() {
+        let (old, new) = (TestReporter::new(), TestReporter::new());
+        let (entered, entering) = mpsc::channel();
+        let (release, gate) = mpsc::channel();
+        let reporter = Arc::new(ReloadableReporter::new(Box::new(BlockingReporter {
+            entered: std::sync::Mutex::new(entered),
+            gate: std::sync::Mutex::new(gate),
+            inner: old.clone(),
+        })));
+        let handle = reporter.handle();
+
+        let slow = {
+            let reporter = reporter.clone();
+            std::thread::spawn(move || reporter.report_data(HashMap::new(), Utc::now()))
+        };
+        entering.recv().unwrap();
+        let swap = {
+            let new = new.clone();
+            std::thread::spawn(move || handle.set_reporter(Box::new(new)))
+        };
+
+        // the swap and further reports proceed while the slow report is in progress
+        while reporter.health() != ReporterHealth::Healthy {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        reporter.report_data(HashMap::new(), Utc::now()).unwrap();
+        assert_eq!(new.recorded().len(), 1);
+        assert!(old.recorded().is_empty());
+
+        // the previous reporter is only shut down once the slow report completes
+        release.send(()).unwrap();
+        slow.join().unwrap().unwrap();
+        swap.join().unwrap().unwrap();
+        assert_eq!(old.recorded().len(), 1);
+    }
+
+    #[test]
+    fn batching_reporter_forwards_full_batches() {
+        let reporter = TestReporter::new();
+        let batching = BatchingReporter::new(reporter.clone(), 3, Duration::from_secs(60));
+        for _ in 0..2 {
+            batching.report_data(HashMap::new(), Utc::now()).unwrap();
+        }
+        assert!(reporter.recorded().is_empty());
+
+        batching.report_data(HashMap::new(), Utc::now()).unwrap();
+        assert_eq!(reporter.recorded().len(), 3);
+    }
+
+    #[test]
+    fn batching_reporter_forwards_stale_batches() {
+        let reporter = TestReporter::new();
+        let batching = BatchingReporter::new(reporter.clone(), 100, Duration::from_millis(10));
+        batching.report_data(HashMap::new(), Utc::now()).unwrap();
+
+        // forwarded by the background thread, as nothing else is reported
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while reporter.recorded().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(reporter.recorded().len(), 1);
+        drop(batching);
+    }
+
+    #[test]
+    #[should_panic(expected = "batch_size must be non-zero")]
+    fn batching_reporter_rejects_empty_batches() {
+        BatchingReporter::new(TestReporter::new(), 0, Duration::from_secs(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "max_latency must be non-zero")]
+    fn batching_reporter_rejects_zero_latency() {
+        BatchingReporter::new(TestReporter::new(), 1, Duration::ZERO);
+    }
+
+    #[test]
+    fn metrics_reporter_counts_spans_by_label() {
+        let reporter = MetricsReporter::new(vec!["http.route"]);
+        let layer = crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(crate::TraceId::new(), None).unwrap();
+            for route in &["/a", "/a", "/\"b\""] {
+                tracing::warn_span!("request", http.route = route, user = "alice").in_scope(|| {
+                    tracing::info!("event");
+                });
+            }
+        });
+
+        assert_eq!(
+            reporter.gather(),
+            concat!(
+                "# TYPE spans counter\n",
+                "spans_total{name=\"request\",level=\"WARN\",http_route=\"/\\\"b\\\"\"} 1\n",
+                "spans_total{name=\"request\",level=\"WARN\",http_route=\"/a\"} 2\n",
+                "spans_total{name=\"root\",level=\"INFO\",http_route=\"\"} 1\n",
+                "# EOF\n",
+            )
+        );
+    }
+
+    #[test]
+    fn buffer_full_policy_only_handles_queue_overflow() {
+        for policy in [BufferFullPolicy::Drop, BufferFullPolicy::Log] {
+            policy.handle(None);
+            policy.handle(Some("request failed"));
+            policy.handle(Some(QUEUE_OVERFLOW));
+        }
+    }
+
+    type FakeBatch = (String, Vec<HashMap<String, libhoney::Value>>);
+
+    // stands in for Honeycomb's batch API, responding to the i-th event received with
+    // `status(i)` and passing on the path and events of each request
+    fn fake_honeycomb<S>(status: S) -> (String, mpsc::Receiver<FakeBatch>)
+    where
+        S: Fn(usize) -> u16 + Send + 'static,
+    {
+        use std::io::{BufRead, BufReader, Read};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_host = format!("http://{}", listener.local_addr().unwrap());
+        let (requests, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut received = 0;
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let events: Vec<HashMap<String, libhoney::Value>> =
+                    serde_json::from_slice(&body).unwrap();
+
+                let statuses = (received..received + events.len())
+                    .map(|i| json!({ "status": status(i) }))
+                    .collect::<Vec<_>>();
+                received += events.len();
+                let response = serde_json::to_string(&statuses).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+
+                let path = request_line.split(' ').nth(1).unwrap().to_string();
+                if requests.send((path, events)).is_err() {
+                    return;
+                }
+            }
+        });
+        (api_host, received)
+    }
+
+    #[test]
+    fn drains_libhoney_responses_past_a_full_buffer() {
+        // the first 100 events are accepted and the rest rejected
+        let (api_host, received) = fake_honeycomb(|i| if i < 100 { 202 } else { 400 });
+        let config = libhoney::Config {
+            options: libhoney::client::Options {
+                api_key: "key".to_string(),
+                dataset: "dataset".to_string(),
+                api_host,
+                ..libhoney::client::Options::default()
+            },
+            // libhoney bounds its channels of pending events and of responses to four times
+            // this, far fewer than the responses to the events reported below
+            transmission_options: libhoney::transmission::Options {
+                pending_work_capacity: 4,
+                ..libhoney::transmission::Options::default()
+            },
+        };
+        let health = HealthHandle::new(10, 0.5, 1.0);
+        let reporter = crate::libhoney_reporter_with_buffer_full_policy(
+            config,
+            BufferFullPolicy::Drop,
+            Some(health.clone()),
+        );
+
+        // libhoney blocks on sending a response to its full channel of responses unless they
+        // are drained. Events are reported a few at a time, leaving room in the channel of
+        // pending events for the message stopping libhoney's worker on flush and on drop,
+        // which libhoney panics if it cannot queue.
+        let mut sent = 0;
+        for chunk in 0..20 {
+            for i in 0..8 {
+                let mut data = HashMap::new();
+                data.insert("i".to_string(), json!(chunk * 8 + i));
+                reporter.report_data(data, Utc::now()).unwrap();
+            }
+            reporter.flush().unwrap();
+            while sent < (chunk + 1) * 8 {
+                let (_, events) = received
+                    .recv_timeout(Duration::from_secs(10))
+                    .expect("sending stalled");
+                sent += events.len();
+            }
+        }
+
+        // only responses to the last events, far past the channel's capacity, are failures
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while health.health() != ReporterHealth::Unhealthy {
+            assert!(Instant::now() < deadline, "responses not drained");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn rate_limit_reporter_drops_over_rate() {
+        let inner = TestReporter::new();
+        let reporter = RateLimitReporter::new(inner.clone(), 3, OverflowPolicy::Drop);
+
+        for _ in 0..5 {
+            reporter.report_data(HashMap::new(), Utc::now()).unwrap();
+        }
+        assert_eq!(inner.recorded().len(), 3);
+        assert_eq!(reporter.dropped_count(), 2);
+    }
+
+    #[test]
+    fn rate_limit_reporter_blocks_over_rate() {
+        let inner = TestReporter::new();
+        let reporter = RateLimitReporter::new(inner.clone(), 20, OverflowPolicy::Block);
+
+        let start = Instant::now();
+        for _ in 0..22 {
+            reporter.report_data(HashMap::new(), Utc::now()).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(inner.recorded().len(), 22);
+        assert_eq!(reporter.dropped_count(), 0);
+    }
+
+    #[test]
+    fn batch_json_reporter_renders_batch_per_dataset() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sink = sent.clone();
+        let reporter = BatchJsonReporter::new(4, move |dataset: Option<&str>, body| {
+            #[cfg(not(feature = "use_parking_lot"))]
+            let mut sink = sink.lock().unwrap();
+            #[cfg(feature = "use_parking_lot")]
+            let mut sink = sink.lock();
+
+            sink.push((dataset.map(str::to_string), body));
+            Ok(())
+        });
+
+        let timestamp = Utc.timestamp_opt(1_614_834_367, 890_123_456).unwrap();
+        let mut routed = HashMap::new();
+        routed.insert(DATASET_FIELD.to_string(), json!("other"));
+        routed.insert("name".to_string(), json!("routed"));
+        let mut default = HashMap::new();
+        default.insert("name".to_string(), json!("default"));
+        reporter
+            .report_batch(vec![
+                (default.clone(), timestamp),
+                (routed, timestamp),
+                (default, timestamp),
+            ])
+            .unwrap();
+
+        #[cfg(not(feature = "use_parking_lot"))]
+        let sent = sent.lock().unwrap();
+        #[cfg(feature = "use_parking_lot")]
+        let sent = sent.lock();
+
+        assert_eq!(sent.len(), 2);
+        let entry = json!({
+            "time": "2021-03-04T05:06:07.890123456Z",
+            "samplerate": 4,
+            "data": { "name": "default" },
+        });
+        assert_eq!(sent[0].0, None);
+        let body: libhoney::Value = serde_json::from_str(&sent[0].1).unwrap();
+        assert_eq!(body, json!([entry, entry]));
+
+        assert_eq!(sent[1].0.as_deref(), Some("other"));
+        let body: libhoney::Value = serde_json::from_str(&sent[1].1).unwrap();
+        assert_eq!(body[0]["data"], json!({ "name": "routed" }));
+    }
+
+    #[test]
+    fn routing_reporter_routes_by_predicate() {
+        let (errors, other) = (TestReporter::new(), TestReporter::new());
+        let reporter = RoutingReporter::new(
+            |data| data.get(crate::fields::LEVEL) == Some(&json!("ERROR")),
+            errors.clone(),
+            other.clone(),
+        );
+        let subscriber = tracing_subscriber::registry().with(
+            crate::new_honeycomb_telemetry_layer_with_reporter("test", reporter),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(crate::TraceId::new(), None).unwrap();
+            tracing::error!("failed");
+            tracing::info!("fine");
+        });
+
+        let errors = errors.recorded();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].get("message"), Some(&json!("failed")));
+        assert_eq!(other.recorded().len(), 2);
+    }
+
+    // fails the first `failures` reports
+    struct FlakyReporter {
+        failures: AtomicUsize,
+        inner: TestReporter,
+    }
+
+    impl Reporter for FlakyReporter {
+        fn report_data(
+            &self,
+            data: HashMap<String, libhoney::Value>,
+            timestamp: DateTime<Utc>,
+        ) -> Result<(), ReportError> {
+            let failures = self.failures.load(Ordering::Relaxed);
+            if failures > 0 {
+                self.failures.store(failures - 1, Ordering::Relaxed);
+                return Err(ReportError::Closed);
+            }
+            self.inner.report_data(data, timestamp)
+        }
+    }
+
+    fn flaky(failures: usize) -> (FlakyReporter, TestReporter) {
+        let inner = TestReporter::new();
+        let reporter = FlakyReporter {
+            failures: AtomicUsize::new(failures),
+            inner: inner.clone(),
+        };
+        (reporter, inner)
+    }
+
+    #[test]
+    fn multi_reporter_reports_to_every_reporter() {
+        let (first, second) = (TestReporter::new(), TestReporter::new());
+        let (flaky, _) = flaky(1);
+        let multi = MultiReporter::new(vec![
+            Box::new(first.clone()),
+            Box::new(flaky),
+            Box::new(second.clone()),
+        ]);
+        let data: HashMap<String, libhoney::Value> =
+            vec![("n".to_string(), json!(1))].into_iter().collect();
+
+        // a failing reporter does not keep the others from being reported to
+        assert!(multi.report_data(data.clone(), Utc::now()).is_err());
+        multi.report_data(data.clone(), Utc::now()).unwrap();
+        assert_eq!(first.recorded(), vec![data.clone(), data.clone()]);
+        assert_eq!(second.recorded(), vec![data.clone(), data]);
+    }
+
+    #[test]
+    fn file_reporter_flushes_without_further_writes() {
+        let path =
+            std::env::temp_dir().join(format!("tracing-honeycomb-{}.json", uuid::Uuid::new_v4()));
+        let reporter = FileReporter::new(&path).unwrap();
+        let data: HashMap<String, libhoney::Value> =
+            vec![("n".to_string(), json!(1))].into_iter().collect();
+        reporter.report_data(data.clone(), Utc::now()).unwrap();
+        reporter.report_data(data, Utc::now()).unwrap();
+
+        // written out by the background thread, as nothing else is reported
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut written = String::new();
+        while written.lines().count() < 2 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+            written = fs::read_to_string(&path).unwrap();
+        }
+        assert_eq!(written, "{\"n\":1}\n{\"n\":1}\n");
+
+        drop(reporter);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn spooling_reporter_spools_failures_and_replays_them() {
+        let dir = std::env::temp_dir().join(format!("tracing-honeycomb-{}", uuid::Uuid::new_v4()));
+        let timestamp = Utc.timestamp_opt(1_600_000_000, 123_456_789).unwrap();
+        let entry = |n: u32| -> HashMap<String, libhoney::Value> {
+            vec![("n".to_string(), json!(n))].into_iter().collect()
+        };
+
+        let (flaky, inner) = flaky(3);
+        let reporter = SpoolingReporter::new(flaky, &dir, 2, Duration::from_secs(3600)).unwrap();
+        for n in 0..4 {
+            reporter.report_data(entry(n), timestamp).unwrap();
+        }
+        assert_eq!(inner.recorded(), vec![entry(3)]);
+        assert_eq!(reporter.spooled_count(), 2);
+        assert_eq!(reporter.dropped_count(), 1);
+        drop(reporter);
+
+        // spooled data survives the reporter, and is replayed oldest first by the next one
+        let inner = TestReporter::new();
+        let reporter =
+            SpoolingReporter::new(inner.clone(), &dir, 2, Duration::from_secs(3600)).unwrap();
+        assert_eq!(reporter.spooled_count(), 2);
+        reporter.flush().unwrap();
+        assert_eq!(inner.recorded(), vec![entry(1), entry(2)]);
+        assert_eq!(reporter.spooled_count(), 0);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn health_reporter_judges_health_from_recent_outcomes() {
+        let (failing, _) = flaky(2);
+        let reporter = Arc::new(HealthReporter::new(
+            failing,
+            HealthHandle::new(4, 0.25, 0.5),
+        ));
+        let handle = reporter.handle();
+        let multi = MultiReporter::new(vec![
+            Box::new(TestReporter::new()),
+            Box::new(reporter.clone()),
+        ]);
+        assert_eq!(handle.health(), ReporterHealth::Healthy);
+
+        let mut health = Vec::new();
+        for _ in 0..6 {
+            let _ = reporter.report_data(HashMap::new(), Utc::now());
+            health.push(handle.health());
+        }
+        use ReporterHealth::*;
+        assert_eq!(
+            health,
+            [Unhealthy, Unhealthy, Unhealthy, Unhealthy, Degraded, Healthy]
+        );
+
+        reporter.handle.record(false);
+        assert_eq!(reporter.health(), Degraded);
+        assert_eq!(multi.health(), Degraded);
+        assert_eq!(TestReporter::new().health(), Healthy);
+
+        // handing data off to a background thread forwards the health of the wrapped reporter
+        let channel = ChannelReporter::new(reporter, 4);
+        assert_eq!(channel.health(), Degraded);
+        channel.shutdown().unwrap();
+        assert_eq!(channel.health(), Unhealthy);
+
+        // outcomes recorded elsewhere are reported, but reports are not tracked
+        let (failing, _) = flaky(1);
+        let untracked = HealthReporter::untracked(failing, HealthHandle::new(4, 0.25, 0.5));
+        assert!(untracked.report_data(HashMap::new(), Utc::now()).is_err());
+        assert_eq!(untracked.health(), Healthy);
+        untracked.handle().record(false);
+        assert_eq!(untracked.health(), Unhealthy);
+    }
+
+    fn retry_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy::new(max_retries)
+            .with_initial_backoff(Duration::from_millis(1))
+            .with_max_backoff(Duration::from_millis(4))
+    }
+
+    #[test]
+    fn retry_reporter_retries_until_success() {
+        let (flaky, inner) = flaky(2);
+        let reporter = RetryReporter::new(flaky, retry_policy(2));
+
+        reporter.report_data(HashMap::new(), Utc::now()).unwrap();
+        assert_eq!(inner.recorded().len(), 1);
+        assert_eq!(reporter.dropped_count(), 0);
+    }
+
+    #[test]
+    fn retry_reporter_gives_up_after_max_retries() {
+        let (flaky, inner) = flaky(3);
+        let reporter = RetryReporter::new(flaky, retry_policy(2));
+
+        let res = reporter.report_data(HashMap::new(), Utc::now());
+        assert!(matches!(res, Err(ReportError::Closed)));
+        assert!(inner.recorded().is_empty());
+        assert_eq!(reporter.dropped_count(), 1);
+    }
+
+    #[test]
+    fn retry_reporter_gives_up_at_deadline() {
+        let (flaky, inner) = flaky(1);
+        let policy = retry_policy(5).with_deadline(Duration::from_millis(0));
+        let reporter = RetryReporter::new(flaky, policy);
+
+        assert!(reporter.report_data(HashMap::new(), Utc::now()).is_err());
+        assert!(inner.recorded().is_empty());
+        assert_eq!(reporter.dropped_count(), 1);
+    }
+
+    #[test]
+    fn retry_backoff_is_capped_and_jittered() {
+        let policy = retry_policy(40);
+        for retry in 0..40 {
+            let backoff = policy.backoff(retry);
+            assert!(backoff <= Duration::from_millis(4));
+        }
+        assert!(policy.backoff(0) >= Duration::from_micros(500));
+        assert!(policy.backoff(10) >= Duration::from_millis(2));
+    }
+
+    #[cfg(not(feature = "use_parking_lot"))]
+    #[test]
+    fn reports_after_mutex_is_poisoned() {
+        let reporter = TestReporter::new();
+        let recorded = reporter.recorded.clone();
+        let _ = std::thread::spawn(move || {
+            let _recorded = recorded.lock().unwrap();
+            panic!("panicking while reporting");
+        })
+        .join();
+        assert!(reporter.recorded.is_poisoned());
+
+        reporter.report_data(HashMap::new(), Utc::now()).unwrap();
+        assert_eq!(reporter.recorded().len(), 1);
+        reporter.clear();
+        assert!(reporter.recorded().is_empty());
+    }
+
+    #[test]
+    fn pretty_stdout_reporter_renders_indented_json() {
+        let data: HashMap<_, _> = vec![
+            ("name".to_string(), json!("root")),
+            ("duration_ms".to_string(), json!(1.5)),
+            (
+                "request".to_string(),
+                json!({ "path": "/", "headers": ["a", "b"], "query": {} }),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let sorted: BTreeMap<_, _> = data.iter().collect();
+
+        let pretty = PrettyStdoutReporter::new()
+            .with_color(false)
+            .render(&data)
+            .unwrap();
+        assert_eq!(pretty, serde_json::to_string_pretty(&sorted).unwrap());
+
+        let colored = PrettyStdoutReporter::new()
+            .with_color(true)
+            .render(&data)
+            .unwrap();
+        assert_eq!(
+            colored.replace(KEY_COLOR, "").replace(RESET_COLOR, ""),
+            pretty
+        );
+        assert_eq!(
+            colored,
+            concat!(
+                "{\n",
+                "  \x1b[36m\"duration_ms\"\x1b[0m: 1.5,\n",
+                "  \x1b[36m\"name\"\x1b[0m: \"root\",\n",
+                "  \x1b[36m\"request\"\x1b[0m: {\n",
+                "    \x1b[36m\"headers\"\x1b[0m: [\n",
+                "      \"a\",\n",
+                "      \"b\"\n",
+                "    ],\n",
+                "    \x1b[36m\"path\"\x1b[0m: \"/\",\n",
+                "    \x1b[36m\"query\"\x1b[0m: {}\n",
+                "  }\n",
+                "}",
+            )
+        );
+    }
+
+    #[test]
+    fn json_output_omits_dataset_field() {
+        let data: HashMap<_, _> = vec![
+            (DATASET_FIELD.to_string(), json!("tenant-a")),
+            ("name".to_string(), json!("root")),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(fields_to_json(&data).unwrap(), r#"{"name":"root"}"#);
+        let pretty = PrettyStdoutReporter::new().with_color(false);
+        assert_eq!(pretty.render(&data).unwrap(), "{\n  \"name\": \"root\"\n}");
+    }
+
+    #[test]
+    fn libhoney_reporter_routes_by_dataset_field() {
+        let (api_host, received) = fake_honeycomb(|_| 202);
+        let config = libhoney::Config {
+            options: libhoney::client::Options {
+                api_key: "key".to_string(),
+                dataset: "dataset".to_string(),
+                api_host,
+                ..libhoney::client::Options::default()
+            },
+            transmission_options: libhoney::transmission::Options::default(),
+        };
+        let reporter = crate::new_libhoney_reporter(config);
+        for (name, dataset) in &[("default", None), ("routed", Some("tenant-a"))] {
+            let mut data = HashMap::new();
+            data.insert("name".to_string(), json!(name));
+            if let Some(dataset) = dataset {
+                data.insert(DATASET_FIELD.to_string(), json!(dataset));
+            }
+            reporter.report_data(data, Utc::now()).unwrap();
+        }
+        reporter.flush().unwrap();
+
+        let mut batches = (0..2)
+            .map(|_| received.recv_timeout(Duration::from_secs(10)).unwrap())
+            .collect::<Vec<_>>();
+        batches.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let names = |events: &[HashMap<String, libhoney::Value>]| {
+            events
+                .iter()
+                .map(|event| event["data"]["name"].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(batches[0].0, "/1/batch/dataset");
+        assert_eq!(names(&batches[0].1), vec![json!("default")]);
+        assert_eq!(batches[1].0, "/1/batch/tenant-a");
+        assert_eq!(names(&batches[1].1), vec![json!("routed")]);
+        assert!(batches[1].1[0]["data"].get(DATASET_FIELD).is_none());
+    }
+
+    #[cfg(feature = "sorted_fields")]
+    #[test]
+    fn sorts_compact_json_fields() {
+        let data: HashMap<_, _> = (0..32)
+            .map(|i| (format!("field.{:02}", 31 - i), json!({ "b": i, "a": i })))
+            .collect();
+
+        let expected = (0..32)
+            .map(|i| format!("\"field.{:02}\":{{\"a\":{},\"b\":{}}}", i, 31 - i, 31 - i))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(fields_to_json(&data).unwrap(), format!("{{{}}}", expected));
+    }
+
+    #[test]
+    fn event_sampling_reporter_samples_only_events() {
+        let inner = TestReporter::new();
+        let reporter = EventSamplingReporter::new(inner.clone(), 10);
+        let event: HashMap<_, _> = vec![(
+            crate::fields::ANNOTATION_TYPE.to_string(),
+            json!(crate::fields::SPAN_EVENT),
+        )]
+        .into_iter()
+        .collect();
+        let span: HashMap<_, _> = vec![(crate::fields::SPAN_ID.to_string(), json!("1"))]
+            .into_iter()
+            .collect();
+
+        for _ in 0..10_000 {
+            reporter.report_data(event.clone(), Utc::now()).unwrap();
+        }
+        reporter
+            .report_batch(vec![(span.clone(), Utc::now()); 100])
+            .unwrap();
+
+        let recorded = inner.recorded();
+        let spans = recorded.iter().filter(|data| **data == span).count();
+        assert_eq!(spans, 100);
+        let events = recorded.len() - spans;
+        assert!((700..1300).contains(&events), "kept {} events", events);
+        assert_eq!(reporter.dropped_count(), 10_000 - events);
+
+        // kept events are weighted by the rate, multiplying that of earlier sampling
+        assert!(recorded
+            .iter()
+            .filter(|data| **data != span)
+            .all(|data| data[SAMPLE_RATE_FIELD] == json!(10)));
+        let inner = TestReporter::new();
+        let reporter = EventSamplingReporter::new(inner.clone(), 2);
+        let mut sampled = event.clone();
+        sampled.insert(SAMPLE_RATE_FIELD.to_string(), json!(10));
+        for _ in 0..100 {
+            reporter.report_data(sampled.clone(), Utc::now()).unwrap();
+        }
+        let recorded = inner.recorded();
+        assert!(!recorded.is_empty());
+        assert!(recorded
+            .iter()
+            .all(|data| data[SAMPLE_RATE_FIELD] == json!(20)));
+
+        // at a rate of 1, events are forwarded unchanged
+        let inner = TestReporter::new();
+        let reporter = EventSamplingReporter::new(inner.clone(), 1);
+        reporter.report_data(event.clone(), Utc::now()).unwrap();
+        assert_eq!(inner.recorded(), vec![event]);
+    }
+
+    #[test]
+    fn batch_json_reporter_renders_sample_rate_field() {
+        let reporter = BatchJsonReporter::new(4, |_: Option<&str>, _| Ok(()));
+        let timestamp = Utc.timestamp_opt(1_614_834_367, 890_123_456).unwrap();
+        let mut sampled = HashMap::new();
+        sampled.insert(SAMPLE_RATE_FIELD.to_string(), json!(10));
+        sampled.insert("name".to_string(), json!("event"));
+
+        let rendered = reporter.render(vec![(sampled, timestamp)]).unwrap();
+        let body: libhoney::Value = serde_json::from_str(&rendered[0].1).unwrap();
+        assert_eq!(body[0]["samplerate"], json!(40));
+        assert_eq!(body[0]["data"], json!({ "name": "event" }));
+    }
+
+    #[test]
+    fn validates_libhoney_config() {
+        let config = |api_key: &str, dataset: &str, api_host: &str| libhoney::Config {
+            options: libhoney::client::Options {
+                api_key: api_key.to_string(),
+                dataset: dataset.to_string(),
+                api_host: api_host.to_string(),
+                ..libhoney::client::Options::default()
+            },
+            transmission_options: libhoney::transmission::Options::default(),
+        };
+
+        let valid = ["https://api.honeycomb.io", "http://localhost:8080/"];
+        for api_host in valid.iter() {
+            assert_eq!(
+                validate_libhoney_config(&config("key", "dataset", api_host)),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            validate_libhoney_config(&config(" ", "dataset", valid[0])),
+            Err(InitError::MissingApiKey)
+        );
+        assert!(matches!(
+            crate::try_new_honeycomb_telemetry_layer("test", config("", "dataset", valid[0])),
+            Err(InitError::MissingApiKey)
+        ));
+        // only the fallible constructors validate the config
+        let _ = crate::new_honeycomb_telemetry_layer("test", config("", "dataset", valid[0]));
+        assert_eq!(
+            validate_libhoney_config(&config("key", "", valid[0])),
+            Err(InitError::MissingDataset)
+        );
+        for api_host in [
+            "api.honeycomb.io",
+            "https://",
+            "ftp://api.honeycomb.io",
+            "https://a b",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                validate_libhoney_config(&config("key", "dataset", api_host)),
+                Err(InitError::InvalidApiHost(api_host.to_string()))
+            );
         }
     }
 }
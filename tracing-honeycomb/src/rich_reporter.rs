@@ -0,0 +1,251 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use tracing_distributed::{Event, Span};
+
+use crate::reporter::{ReportError, Reporter};
+use crate::visitor::HoneycombVisitor;
+use crate::{SpanId, TraceId};
+
+/// A span as handed to a [`RichReporter`]
+pub type HoneycombSpan = Span<HoneycombVisitor, SpanId, TraceId>;
+
+/// An event as handed to a [`RichReporter`]
+pub type HoneycombEvent = Event<HoneycombVisitor, SpanId, TraceId>;
+
+type Data = (HashMap<String, libhoney::Value>, DateTime<Utc>);
+
+/// Reports spans and events to some backend as they were gathered by the layer, rather than
+/// flattened into Honeycomb's columns as with a [`Reporter`]
+///
+/// Suited to backends with a richer data model, such as OpenTelemetry's, which can map the
+/// span's metadata, links, baggage and recorded fields (via [`HoneycombVisitor::fields`])
+/// each to their own place. Used with a [`Builder`](crate::Builder) like any reporter, so
+/// spans and events are only handed over once sampled in, with their fields redacted,
+/// truncated and converted as configured. Options applying to Honeycomb's columns, such as key
+/// and status mapping, apply when converting them via the given [`Flattening`].
+///
+/// Every `Reporter` is a `RichReporter`, reporting the flattened spans and events.
+pub trait RichReporter {
+    /// Reports a completed span to the backend
+    fn report_span(
+        &self,
+        span: HoneycombSpan,
+        flattening: &Flattening<'_>,
+    ) -> Result<(), ReportError>;
+
+    /// Reports an event to the backend
+    fn report_event(
+        &self,
+        event: HoneycombEvent,
+        flattening: &Flattening<'_>,
+    ) -> Result<(), ReportError>;
+
+    /// Transmits any data buffered by the reporter
+    ///
+    /// Defaults to doing nothing.
+    fn flush(&self) -> Result<(), ReportError> {
+        Ok(())
+    }
+
+    /// Transmits any data buffered by the reporter, ahead of the process exiting
+    ///
+    /// Defaults to calling `flush`.
+    fn shutdown(&self) -> Result<(), ReportError> {
+        self.flush()
+    }
+}
+
+impl<R: Reporter + ?Sized> RichReporter for R {
+    fn report_span(
+        &self,
+        span: HoneycombSpan,
+        flattening: &Flattening<'_>,
+    ) -> Result<(), ReportError> {
+        self.report_batch(flattening.span(span))
+    }
+
+    fn report_event(
+        &self,
+        event: HoneycombEvent,
+        flattening: &Flattening<'_>,
+    ) -> Result<(), ReportError> {
+        let (data, timestamp) = flattening.event(event);
+        self.report_data(data, timestamp)
+    }
+
+    fn flush(&self) -> Result<(), ReportError> {
+        Reporter::flush(self)
+    }
+
+    fn shutdown(&self) -> Result<(), ReportError> {
+        Reporter::shutdown(self)
+    }
+}
+
+// implemented by the `HoneycombTelemetry` handing spans and events over, which holds the
+// configuration for flattening them
+pub(crate) trait Flatten {
+    fn span(&self, span: HoneycombSpan) -> Vec<Data>;
+
+    fn event(&self, event: HoneycombEvent, trace_start: bool) -> Data;
+}
+
+/// Converts spans and events into Honeycomb's columns, as they are reported to a [`Reporter`],
+/// applying the options configured via the [`Builder`](crate::Builder), e.g. key mapping and
+/// global fields
+pub struct Flattening<'a> {
+    flatten: &'a dyn Flatten,
+    trace_start: bool,
+}
+
+impl<'a> Flattening<'a> {
+    pub(crate) fn new(flatten: &'a dyn Flatten, trace_start: bool) -> Self {
+        Flattening {
+            flatten,
+            trace_start,
+        }
+    }
+
+    /// Flattens a span, preceded by an entry for each span it follows from.
+    pub fn span(
+        &self,
+        span: HoneycombSpan,
+    ) -> Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)> {
+        self.flatten.span(span)
+    }
+
+    /// Flattens an event, or the event marking the start of a trace if it is handed over as
+    /// such, see [`Builder::with_trace_start_events`](crate::Builder::with_trace_start_events).
+    pub fn event(
+        &self,
+        event: HoneycombEvent,
+    ) -> (HashMap<String, libhoney::Value>, DateTime<Utc>) {
+        self.flatten.event(event, self.trace_start)
+    }
+}
+
+impl fmt::Debug for Flattening<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Flattening")
+            .field("trace_start", &self.trace_start)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fields;
+    use libhoney::json;
+    use std::sync::Arc;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[cfg(feature = "use_parking_lot")]
+    use parking_lot::Mutex;
+    #[cfg(not(feature = "use_parking_lot"))]
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct NameReporter {
+        names: Arc<Mutex<Vec<String>>>,
+        flattened: Arc<Mutex<Vec<HashMap<String, libhoney::Value>>>>,
+    }
+
+    impl RichReporter for NameReporter {
+        fn report_span(
+            &self,
+            span: HoneycombSpan,
+            flattening: &Flattening<'_>,
+        ) -> Result<(), ReportError> {
+            let fields: Vec<_> = span
+                .values
+                .fields()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            self.push(format!("{}{:?}", span.meta.name(), fields));
+            for (data, _) in flattening.span(span) {
+                lock(&self.flattened).push(data);
+            }
+            Ok(())
+        }
+
+        fn report_event(
+            &self,
+            event: HoneycombEvent,
+            _: &Flattening<'_>,
+        ) -> Result<(), ReportError> {
+            self.push(event.meta.name().to_string());
+            Ok(())
+        }
+    }
+
+    impl NameReporter {
+        fn push(&self, name: String) {
+            lock(&self.names).push(name);
+        }
+
+        fn names(&self) -> Vec<String> {
+            lock(&self.names).clone()
+        }
+    }
+
+    #[cfg(not(feature = "use_parking_lot"))]
+    fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+        mutex.lock().unwrap()
+    }
+
+    #[cfg(feature = "use_parking_lot")]
+    fn lock<T>(mutex: &Mutex<T>) -> parking_lot::MutexGuard<'_, T> {
+        mutex.lock()
+    }
+
+    #[test]
+    fn hands_spans_and_events_to_rich_reporter() {
+        let rich = NameReporter::default();
+        let layer = crate::new_honeycomb_telemetry_layer_with_rich_reporter("test", rich.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root", user = "alice", count = 3);
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(TraceId::new(), None).unwrap();
+            tracing::info!("event");
+        });
+
+        let names = rich.names();
+        assert_eq!(names.len(), 2);
+        assert!(names[0].starts_with("event "));
+        assert_eq!(names[1], r#"root["user=\"alice\"", "count=3"]"#);
+    }
+
+    #[test]
+    fn applies_builder_configuration() {
+        let rich = NameReporter::default();
+        let layer = crate::Builder::new("test", rich.clone())
+            .with_redacted_fields(vec!["password"])
+            .with_key_mapping(|key| format!("app.{}", key))
+            .with_sampler(|trace_id: &TraceId| trace_id.to_string() != "dropped")
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for trace_id in &["kept", "dropped"] {
+                let span = tracing::info_span!("root", password = "hunter2");
+                let _guard = span.enter();
+                crate::register_dist_tracing_root(TraceId::from(*trace_id), None).unwrap();
+            }
+        });
+
+        let names = rich.names();
+        assert_eq!(names.len(), 1);
+        assert_eq!(
+            names[0],
+            format!(r#"root["password=\"{}\""]"#, crate::REDACTED)
+        );
+        let flattened = lock(&rich.flattened).clone();
+        assert_eq!(flattened.len(), 1);
+        assert_eq!(flattened[0]["app.password"], json!(crate::REDACTED));
+        assert_eq!(flattened[0][fields::NAME], json!("root"));
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::TraceId;
+
+/// Decides whether the spans and events belonging to a trace are reported.
+///
+/// Implemented for closures taking a `&TraceId`.
+pub trait Sampler: Send + Sync {
+    /// Returns `true` if the trace should be reported.
+    fn should_sample(&self, trace_id: &TraceId) -> bool;
+
+    /// Returns `true` if the trace, whose local root span (as registered via
+    /// `register_dist_tracing_root`) has the given name, should be reported.
+    ///
+    /// Defaults to `should_sample`, ignoring the name.
+    fn should_sample_root(&self, trace_id: &TraceId, root_name: &str) -> bool {
+        let _ = root_name;
+        self.should_sample(trace_id)
+    }
+
+    /// Name of the field whose value on the trace's root span this sampler keys its decisions
+    /// on, if any, as passed to `should_sample_keyed`. See [`FieldSampler`].
+    fn key_field(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns `true` if the trace should be reported, given the name of its local root span,
+    /// and the value of `key_field` for the trace, if found.
+    ///
+    /// This is what `HoneycombTelemetry` calls, for every span and event in the trace.
+    /// Defaults to `should_sample_root`, ignoring the key.
+    fn should_sample_keyed(&self, trace_id: &TraceId, root_name: &str, key: Option<&str>) -> bool {
+        let _ = key;
+        self.should_sample_root(trace_id, root_name)
+    }
+}
+
+impl<F> Sampler for F
+where
+    F: Fn(&TraceId) -> bool + Send + Sync,
+{
+    fn should_sample(&self, trace_id: &TraceId) -> bool {
+        self(trace_id)
+    }
+}
+
+impl fmt::Debug for dyn Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sampler")
+    }
+}
+
+/// Sampler that reports every trace.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AlwaysSampler;
+
+impl Sampler for AlwaysSampler {
+    fn should_sample(&self, _: &TraceId) -> bool {
+        true
+    }
+}
+
+/// Sampler that deterministically reports one in every `sample_rate` traces, based on a
+/// hash of the `TraceId`.
+///
+/// Since the decision only depends on the `TraceId`, spans and events in a single trace are
+/// never sampled differently, even across services. The hash is stable across versions and
+/// matches the Honeycomb beelines: a trace is kept if the first 4 bytes of the SHA-1 digest of
+/// its id, read as a big-endian `u32`, are at most `u32::MAX / sample_rate`. A rate of 1 thus
+/// keeps every trace, and as with the other samplers, so does a rate of 0, disabling sampling.
+/// As in the beelines, the id is hashed exactly as propagated, without normalizing its encoding.
+///
+/// A salted sampler, from [`ModuloSampler::salted`] or [`ModuloSampler::per_process`], mixes
+/// its salt into the hash instead. It keeps the same share of traces, but decides independently
+/// of samplers with another salt, e.g. so that replicas of a service each keep different
+/// traces, covering more of them between them. The price is consistency: a trace kept by one
+/// service is generally dropped by the others, and so is reported only in part.
+#[derive(Debug, Clone, Copy)]
+pub struct ModuloSampler {
+    sample_rate: u32,
+    salt: Option<u64>,
+}
+
+impl ModuloSampler {
+    /// Returns a new `ModuloSampler` reporting one in every `sample_rate` traces.
+    pub fn new(sample_rate: u32) -> Self {
+        ModuloSampler {
+            sample_rate,
+            salt: None,
+        }
+    }
+
+    /// Returns a new `ModuloSampler` reporting one in every `sample_rate` traces, deciding
+    /// independently of samplers with another salt.
+    pub fn salted(sample_rate: u32, salt: u64) -> Self {
+        ModuloSampler {
+            sample_rate,
+            salt: Some(salt),
+        }
+    }
+
+    /// Returns a new `ModuloSampler` reporting one in every `sample_rate` traces, salted with
+    /// a random salt chosen once per process, so that each process decides independently.
+    pub fn per_process(sample_rate: u32) -> Self {
+        static SALT: OnceLock<u64> = OnceLock::new();
+        Self::salted(sample_rate, *SALT.get_or_init(rand::random))
+    }
+}
+
+impl Sampler for ModuloSampler {
+    fn should_sample(&self, trace_id: &TraceId) -> bool {
+        if self.sample_rate == 0 {
+            return true;
+        }
+
+        match self.salt {
+            Some(salt) => {
+                crate::deterministic_sampler::sample_salted(self.sample_rate, trace_id, salt)
+            }
+            None => crate::deterministic_sampler::sample(self.sample_rate, trace_id),
+        }
+    }
+}
+
+/// Handle to a sample rate which can be adjusted at runtime, e.g. from a feature-flag system.
+///
+/// Acts as a [`ModuloSampler`] for the current rate, read each time a sampling decision is
+/// made. A rate of 0 disables sampling, reporting every trace. Clones share the same rate.
+#[derive(Debug, Clone, Default)]
+pub struct SampleRateControl {
+    sample_rate: Arc<AtomicU32>,
+}
+
+impl SampleRateControl {
+    /// Returns a new `SampleRateControl` with the given initial rate.
+    pub fn new(sample_rate: u32) -> Self {
+        SampleRateControl {
+            sample_rate: Arc::new(AtomicU32::new(sample_rate)),
+        }
+    }
+
+    /// Sets the sample rate, taking effect for all subsequent sampling decisions.
+    pub fn set_rate(&self, sample_rate: u32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    /// Returns the current sample rate.
+    pub fn get_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+}
+
+impl Sampler for SampleRateControl {
+    fn should_sample(&self, trace_id: &TraceId) -> bool {
+        match self.get_rate() {
+            0 => true,
+            sample_rate => crate::deterministic_sampler::sample(sample_rate, trace_id),
+        }
+    }
+}
+
+/// Sampler that reports traces at a rate selected by the name of their local root span, e.g.
+/// to report one in every 1000 health checks while keeping every `checkout`.
+///
+/// Traces are sampled as by a [`ModuloSampler`] at the rate configured for their root span's
+/// name, or at `default_rate` for names without one. A rate of 0 disables sampling, reporting
+/// every trace.
+///
+/// The root span is the span registered via `register_dist_tracing_root`, so every span and
+/// event in a trace is sampled alike within a service. Other services continuing the trace
+/// register their own root span, which generally has a different name, so for the whole
+/// trace to be kept or dropped across services they must configure the same rate for it.
+#[derive(Debug, Clone, Default)]
+pub struct NameBasedSampler {
+    rates: HashMap<&'static str, u32>,
+    default_rate: u32,
+}
+
+impl NameBasedSampler {
+    /// Returns a new `NameBasedSampler` using the given rates per root span name, and
+    /// `default_rate` for other names.
+    pub fn new(rates: HashMap<&'static str, u32>, default_rate: u32) -> Self {
+        NameBasedSampler {
+            rates,
+            default_rate,
+        }
+    }
+
+    /// Sets the rate at which traces whose root span has the given name are sampled.
+    pub fn with_rate(mut self, root_name: &'static str, sample_rate: u32) -> Self {
+        self.rates.insert(root_name, sample_rate);
+        self
+    }
+
+    fn sample(sample_rate: u32, trace_id: &TraceId) -> bool {
+        match sample_rate {
+            0 => true,
+            sample_rate => crate::deterministic_sampler::sample(sample_rate, trace_id),
+        }
+    }
+}
+
+impl Sampler for NameBasedSampler {
+    /// Samples at the default rate.
+    fn should_sample(&self, trace_id: &TraceId) -> bool {
+        Self::sample(self.default_rate, trace_id)
+    }
+
+    fn should_sample_root(&self, trace_id: &TraceId, root_name: &str) -> bool {
+        let sample_rate = self.rates.get(root_name).copied();
+        Self::sample(sample_rate.unwrap_or(self.default_rate), trace_id)
+    }
+}
+
+/// Sampler that deterministically reports one in every `sample_rate` traces, based on a hash of
+/// the value of a field set on the root span of each trace, e.g. to keep or drop all traces of
+/// a customer together. Traces without the field are sampled on their `TraceId`, as by a
+/// [`ModuloSampler`]. A rate of 0 disables sampling, reporting every trace.
+///
+/// Spans and events are reported before the root span closes, so the field is read from the
+/// root span when it is registered via `register_dist_tracing_root`: record it when creating
+/// the span, or register it via
+/// [`register_dist_tracing_root_with_fields`](crate::register_dist_tracing_root_with_fields).
+/// Traces whose root span lacks it fall back to the trace's baggage, as set via
+/// [`set_baggage`](crate::set_baggage). With tail sampling, which buffers the trace until its
+/// root span closes, a field recorded on the root span later is used as well. The value is
+/// hashed as a string, with non-string field values in their JSON form.
+///
+/// Unlike trace-id sampling, which needs no coordination for services to sample a trace alike,
+/// the decision is only consistent across services which configure the same field and rate and
+/// see the same value. Propagate the value as baggage, via
+/// [`propagation::inject`](crate::propagation::inject) and
+/// [`propagation::extract_baggage`](crate::propagation::extract_baggage), and set it on each
+/// service's root span: a service without it falls back to the trace id and may keep parts of
+/// a trace that other services drop. Keys also skew the effective rate, since every trace with
+/// a given value is kept or dropped together.
+#[derive(Debug, Clone)]
+pub struct FieldSampler {
+    field: String,
+    sample_rate: u32,
+}
+
+impl FieldSampler {
+    /// Returns a new `FieldSampler` reporting one in every `sample_rate` traces, keyed on the
+    /// value of `field`.
+    pub fn new(field: impl Into<String>, sample_rate: u32) -> Self {
+        FieldSampler {
+            field: field.into(),
+            sample_rate,
+        }
+    }
+}
+
+impl Sampler for FieldSampler {
+    /// Samples on the `TraceId`, as for traces without the field.
+    fn should_sample(&self, trace_id: &TraceId) -> bool {
+        match self.sample_rate {
+            0 => true,
+            sample_rate => crate::deterministic_sampler::sample(sample_rate, trace_id),
+        }
+    }
+
+    fn key_field(&self) -> Option<&str> {
+        Some(&self.field)
+    }
+
+    fn should_sample_keyed(&self, trace_id: &TraceId, _: &str, key: Option<&str>) -> bool {
+        match (self.sample_rate, key) {
+            (0, _) => true,
+            (sample_rate, Some(key)) => crate::deterministic_sampler::sample_key(sample_rate, key),
+            (_, None) => self.should_sample(trace_id),
+        }
+    }
+}
+
+/// Counts of the spans and events kept ("sampled in") and dropped ("sampled out") by
+/// trace-level sampling, e.g. to confirm that the effective sample rate matches the configured
+/// one.
+///
+/// Clones share the same counts, so a clone can be kept to read the counts of a layer built via
+/// [`Builder::with_sampling_stats`](crate::Builder::with_sampling_stats). Spans and events kept
+/// because their trace was forced or, with tail sampling, contained an error count as sampled
+/// in. With tail sampling, spans and events are counted once their trace is decided.
+#[derive(Clone, Debug, Default)]
+pub struct SamplingStats(Arc<SamplingCounts>);
+
+#[derive(Debug, Default)]
+struct SamplingCounts {
+    spans_sampled_in: AtomicUsize,
+    spans_sampled_out: AtomicUsize,
+    events_sampled_in: AtomicUsize,
+    events_sampled_out: AtomicUsize,
+}
+
+impl SamplingStats {
+    /// Returns a new `SamplingStats` with all counts at zero.
+    pub fn new() -> Self {
+        SamplingStats::default()
+    }
+
+    /// Number of spans reported.
+    pub fn spans_sampled_in(&self) -> usize {
+        self.0.spans_sampled_in.load(Ordering::Relaxed)
+    }
+
+    /// Number of spans dropped by sampling.
+    pub fn spans_sampled_out(&self) -> usize {
+        self.0.spans_sampled_out.load(Ordering::Relaxed)
+    }
+
+    /// Number of events reported.
+    pub fn events_sampled_in(&self) -> usize {
+        self.0.events_sampled_in.load(Ordering::Relaxed)
+    }
+
+    /// Number of events dropped by sampling.
+    pub fn events_sampled_out(&self) -> usize {
+        self.0.events_sampled_out.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_span(&self, sampled_in: bool) {
+        let count = if sampled_in {
+            &self.0.spans_sampled_in
+        } else {
+            &self.0.spans_sampled_out
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_event(&self, sampled_in: bool) {
+        let count = if sampled_in {
+            &self.0.events_sampled_in
+        } else {
+            &self.0.events_sampled_out
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn modulo_sampler_keeps_all_at_rate_0_or_1() {
+        // rejected at a rate of u32::MAX, see deterministic_sampler
+        let trace_id = TraceId::from("80f198ee56343ba864fe8b2a57d3eff7");
+        for sample_rate in [0, 1] {
+            assert!(ModuloSampler::new(sample_rate).should_sample(&trace_id));
+            assert!(ModuloSampler::salted(sample_rate, 7).should_sample(&trace_id));
+        }
+        assert!(!ModuloSampler::new(u32::MAX).should_sample(&trace_id));
+        assert!(AlwaysSampler.should_sample(&trace_id));
+    }
+
+    #[test]
+    fn sample_rate_control_applies_rate_changes_to_clones() {
+        // see deterministic_sampler for this decision at a rate of 2
+        let dropped = TraceId::from("80f198ee56343ba864fe8b2a57d3eff7");
+        let control = SampleRateControl::new(0);
+        let sampler = control.clone();
+        assert!(sampler.should_sample(&dropped));
+
+        control.set_rate(2);
+        assert_eq!(sampler.get_rate(), 2);
+        assert!(!sampler.should_sample(&dropped));
+
+        control.set_rate(1);
+        assert!(sampler.should_sample(&dropped));
+    }
+
+    #[test]
+    fn name_based_sampler_uses_rate_for_root_name() {
+        let sampler = NameBasedSampler::default()
+            .with_rate("health_check", u32::MAX)
+            .with_rate("checkout", 1);
+
+        // rejected at a rate of u32::MAX, see deterministic_sampler
+        let trace_id = TraceId::from("80f198ee56343ba864fe8b2a57d3eff7");
+        assert!(!sampler.should_sample_root(&trace_id, "health_check"));
+        assert!(sampler.should_sample_root(&trace_id, "checkout"));
+        assert!(sampler.should_sample_root(&trace_id, "other"));
+        assert!(sampler.should_sample(&trace_id));
+
+        let sampler = NameBasedSampler::new(HashMap::new(), u32::MAX);
+        assert!(!sampler.should_sample_root(&trace_id, "other"));
+    }
+
+    #[test]
+    fn field_sampler_hashes_key_or_falls_back_to_trace_id() {
+        let sampler = FieldSampler::new("customer_id", 2);
+        assert_eq!(sampler.key_field(), Some("customer_id"));
+
+        // see deterministic_sampler for these decisions at a rate of 2
+        let kept = TraceId::from("4bf92f3577b34da6a3ce929d0e0e4736");
+        let dropped = TraceId::from("80f198ee56343ba864fe8b2a57d3eff7");
+        assert!(sampler.should_sample_keyed(&dropped, "root", Some("trace-1")));
+        assert!(!sampler.should_sample_keyed(
+            &kept,
+            "root",
+            Some("80f198ee56343ba864fe8b2a57d3eff7")
+        ));
+        assert!(sampler.should_sample_keyed(&kept, "root", None));
+        assert!(!sampler.should_sample_keyed(&dropped, "root", None));
+
+        let sampler = FieldSampler::new("customer_id", 0);
+        assert!(sampler.should_sample_keyed(
+            &dropped,
+            "root",
+            Some("80f198ee56343ba864fe8b2a57d3eff7")
+        ));
+    }
+}
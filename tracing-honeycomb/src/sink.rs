@@ -0,0 +1,306 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "use_parking_lot")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "use_parking_lot"))]
+use std::sync::{Mutex, PoisonError};
+
+use crate::reporter::{ReportError, Reporter, ReporterHealth};
+
+/// Background thread shared by the reporters which hand work off to one
+///
+/// The thread passes each message sent over a bounded channel to `handle`, in order, and
+/// calls `tick` every `interval`, if any. It exits once the `Worker` is dropped and the
+/// channel is empty, or once `handle` returns `false`.
+pub(crate) struct Worker<M> {
+    sender: mpsc::SyncSender<M>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl<M: Send + 'static> Worker<M> {
+    pub(crate) fn spawn<H, T>(
+        capacity: usize,
+        interval: Option<Duration>,
+        mut handle: H,
+        mut tick: T,
+    ) -> Self
+    where
+        H: FnMut(M) -> bool + Send + 'static,
+        T: FnMut() + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let thread = std::thread::spawn(move || {
+            let mut next_tick = interval.map(|interval| Instant::now() + interval);
+            loop {
+                let received = match next_tick {
+                    Some(at) => receiver.recv_timeout(at.saturating_duration_since(Instant::now())),
+                    None => receiver
+                        .recv()
+                        .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(message) => {
+                        if !handle(message) {
+                            break;
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        tick();
+                        next_tick = interval.map(|interval| Instant::now() + interval);
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        Worker {
+            sender,
+            thread: Mutex::new(Some(thread)),
+        }
+    }
+
+    /// Queues `message`, failing rather than waiting if the channel is full.
+    pub(crate) fn try_send(&self, message: M) -> Result<(), mpsc::TrySendError<M>> {
+        self.sender.try_send(message)
+    }
+
+    /// Queues `message`, waiting for room in the channel.
+    pub(crate) fn send(&self, message: M) -> Result<(), mpsc::SendError<M>> {
+        self.sender.send(message)
+    }
+
+    /// Waits for the thread to exit, if it has not been waited for yet.
+    pub(crate) fn join(&self) {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let thread = self
+            .thread
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        #[cfg(feature = "use_parking_lot")]
+        let thread = self.thread.lock().take();
+
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Worker<()> {
+    /// Spawns a thread which only calls `tick` every `interval`, until the `Worker` is dropped.
+    pub(crate) fn periodic<T>(interval: Duration, tick: T) -> Self
+    where
+        T: FnMut() + Send + 'static,
+    {
+        Self::spawn(0, Some(interval), |()| true, tick)
+    }
+}
+
+enum ChannelMessage {
+    Data(HashMap<String, libhoney::Value>, DateTime<Utc>),
+    Flush(mpsc::Sender<Result<(), ReportError>>),
+    Shutdown(mpsc::Sender<Result<(), ReportError>>),
+    Health(mpsc::Sender<ReporterHealth>),
+}
+
+/// Reporter that hands data off to a background thread via a bounded channel
+///
+/// The thread owns the wrapped reporter and drains the channel into it, so the traced code
+/// path never contends on the wrapped reporter, without requiring an async runtime as
+/// [`TokioReporter`](crate::TokioReporter) does. Data reported while the channel is full is
+/// dropped and counted.
+///
+/// Once the `ChannelReporter` is dropped, the thread reports whatever is left in the channel
+/// and exits. Use [`shutdown`](Reporter::shutdown) to wait for that to happen.
+pub struct ChannelReporter {
+    worker: Worker<ChannelMessage>,
+    capacity: usize,
+    dropped: AtomicUsize,
+}
+
+impl ChannelReporter {
+    /// Returns a new `ChannelReporter`, spawning a thread which drains a channel of the given
+    /// capacity into `reporter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new<R>(reporter: R, capacity: usize) -> Self
+    where
+        R: Reporter + Send + 'static,
+    {
+        assert!(capacity > 0, "capacity must be non-zero");
+
+        let handle = move |message: ChannelMessage| match message {
+            ChannelMessage::Data(data, timestamp) => {
+                if let Err(err) = reporter.report_data(data, timestamp) {
+                    // no caller to hand the error back to, so log msg to stderr
+                    eprintln!("error sending event to honeycomb, {}", err);
+                }
+                true
+            }
+            ChannelMessage::Flush(done) => {
+                let _ = done.send(reporter.flush());
+                true
+            }
+            ChannelMessage::Shutdown(done) => {
+                let _ = done.send(reporter.shutdown());
+                false
+            }
+            ChannelMessage::Health(done) => {
+                let _ = done.send(reporter.health());
+                true
+            }
+        };
+
+        ChannelReporter {
+            worker: Worker::spawn(capacity, None, handle, || {}),
+            capacity,
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of events and spans dropped for arriving while the channel was full so far
+    pub fn dropped_count(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // queues `message` behind any data already in the channel, waiting for its reply, or
+    // returns `None` if the background thread has exited
+    fn call<T>(&self, message: impl FnOnce(mpsc::Sender<T>) -> ChannelMessage) -> Option<T> {
+        let (done, result) = mpsc::channel();
+        self.worker.send(message(done)).ok()?;
+        result.recv().ok()
+    }
+}
+
+impl Reporter for ChannelReporter {
+    /// Errors only indicate that the background thread has exited. Data dropped for arriving
+    /// while the channel is full is not an error, and is only reflected in
+    /// [`dropped_count`](ChannelReporter::dropped_count). Errors from the wrapped reporter are
+    /// logged to stderr by the background thread.
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        match self.worker.try_send(ChannelMessage::Data(data, timestamp)) {
+            Ok(()) => Ok(()),
+            Err(mpsc::TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => Err(ReportError::Closed),
+        }
+    }
+
+    /// Waits for all data already in the channel to be reported, then flushes the wrapped
+    /// reporter.
+    fn flush(&self) -> Result<(), ReportError> {
+        self.call(ChannelMessage::Flush)
+            .unwrap_or(Err(ReportError::Closed))
+    }
+
+    /// Waits for all data already in the channel to be reported, then shuts down the wrapped
+    /// reporter and waits for the background thread to exit. Any data reported afterwards
+    /// is rejected with [`ReportError::Closed`].
+    fn shutdown(&self) -> Result<(), ReportError> {
+        let res = self
+            .call(ChannelMessage::Shutdown)
+            .unwrap_or(Err(ReportError::Closed));
+        self.worker.join();
+        res
+    }
+
+    /// Waits for all data already in the channel to be reported, then returns the health of
+    /// the wrapped reporter, or [`ReporterHealth::Unhealthy`] once the background thread has
+    /// exited.
+    fn health(&self) -> ReporterHealth {
+        self.call(ChannelMessage::Health)
+            .unwrap_or(ReporterHealth::Unhealthy)
+    }
+}
+
+impl fmt::Debug for ChannelReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChannelReporter")
+            .field("capacity", &self.capacity)
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TestReporter;
+
+    // reports to `inner` only once released
+    struct GatedReporter {
+        gate: mpsc::Receiver<()>,
+        inner: TestReporter,
+    }
+
+    impl Reporter for GatedReporter {
+        fn report_data(
+            &self,
+            data: HashMap<String, libhoney::Value>,
+            timestamp: DateTime<Utc>,
+        ) -> Result<(), ReportError> {
+            let _ = self.gate.recv();
+            self.inner.report_data(data, timestamp)
+        }
+    }
+
+    #[test]
+    fn channel_reporter_drops_when_full_and_drains_on_shutdown() {
+        let (release, gate) = mpsc::channel();
+        let inner = TestReporter::new();
+        let reporter = ChannelReporter::new(
+            GatedReporter {
+                gate,
+                inner: inner.clone(),
+            },
+            1,
+        );
+
+        // at most one entry is held by the blocked thread and one is queued
+        for _ in 0..5 {
+            reporter.report_data(HashMap::new(), Utc::now()).unwrap();
+        }
+        assert!(reporter.dropped_count() >= 3);
+
+        drop(release);
+        reporter.shutdown().unwrap();
+        assert_eq!(inner.recorded().len(), 5 - reporter.dropped_count());
+        assert!(matches!(
+            reporter.report_data(HashMap::new(), Utc::now()),
+            Err(ReportError::Closed)
+        ));
+    }
+
+    #[test]
+    fn worker_ticks_until_dropped() {
+        let (ticked, ticks) = mpsc::channel();
+        let worker = Worker::periodic(Duration::from_millis(1), move || {
+            let _ = ticked.send(());
+        });
+        for _ in 0..3 {
+            ticks.recv_timeout(Duration::from_secs(10)).unwrap();
+        }
+
+        drop(worker);
+        // the thread drops `ticked` on exiting
+        while ticks.recv_timeout(Duration::from_secs(10)).is_ok() {}
+        assert!(matches!(
+            ticks.try_recv(),
+            Err(mpsc::TryRecvError::Disconnected)
+        ));
+    }
+}
@@ -1,12 +1,29 @@
 use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt::{self, Display};
 use std::num::{NonZeroU64, ParseIntError, TryFromIntError};
 use std::str::FromStr;
+
+#[cfg(feature = "deterministic_span_ids")]
+use std::collections::HashMap;
+
+#[cfg(all(feature = "deterministic_span_ids", feature = "use_parking_lot"))]
+use parking_lot::Mutex;
+#[cfg(all(feature = "deterministic_span_ids", not(feature = "use_parking_lot")))]
+use std::sync::{Mutex, PoisonError};
+
 /// Unique Span identifier.
 ///
-/// Wraps a `tracing::span::Id` with a suitable parser.
+/// Wraps a `tracing::span::Id` with a suitable parser. The id is the one assigned to the span
+/// by the `tracing_subscriber::Registry`, and carries no other component, such as a per-process
+/// nonce. Registry ids are only unique among the spans open at the same time in one process:
+/// they are reused once spans close, and other processes assign ids independently. Spans are
+/// identified in Honeycomb by their id together with their trace id, so such collisions only
+/// matter for spans of the same trace.
 ///
-/// `Display` and `FromStr` are guaranteed to round-trip.
+/// `Display` and `FromStr` are guaranteed to round-trip, as both use the hex representation of
+/// the wrapped id. `Display` emits lowercase hex, while `FromStr` accepts either case, as some
+/// peers send uppercase ids.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct SpanId {
     pub(crate) tracing_id: tracing::span::Id,
@@ -19,17 +36,29 @@ impl SpanId {
     }
 }
 
+/// Error parsing a `SpanId` from a string.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ParseSpanIdError {
+    /// The string is not a hex `u64`.
     ParseIntError(ParseIntError),
+    /// The span id is zero.
     TryFromIntError(TryFromIntError),
 }
 
 impl Display for ParseSpanIdError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::ParseIntError(e) => write!(f, "{}", e),
-            Self::TryFromIntError(e) => write!(f, "{}", e),
+            Self::ParseIntError(_) => write!(f, "span id is not a hex u64"),
+            Self::TryFromIntError(_) => write!(f, "span id is zero"),
+        }
+    }
+}
+
+impl Error for ParseSpanIdError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ParseIntError(e) => Some(e),
+            Self::TryFromIntError(e) => Some(e),
         }
     }
 }
@@ -49,7 +78,7 @@ impl From<TryFromIntError> for ParseSpanIdError {
 impl FromStr for SpanId {
     type Err = ParseSpanIdError;
 
-    /// Parses a Span Id from a hex value.
+    /// Parses a Span Id from a hex value, in either case.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let raw_id = u64::from_str_radix(s, 16)?;
         let id = NonZeroU64::try_from(raw_id)?;
@@ -66,6 +95,49 @@ impl Display for SpanId {
     }
 }
 
+/// Serializes to the same hex string as `Display`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SpanId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes from a hex string, as with `FromStr`.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SpanId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        SpanId::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Assigns span ids from a counter, in the order spans are first seen, rather than using the
+/// ids the registry assigned, see
+/// [`Builder::with_deterministic_span_ids`](crate::Builder::with_deterministic_span_ids).
+#[cfg(feature = "deterministic_span_ids")]
+#[derive(Debug, Default)]
+pub(crate) struct DeterministicSpanIds {
+    // registry id -> assigned id, never pruned
+    ids: Mutex<HashMap<u64, NonZeroU64>>,
+}
+
+#[cfg(feature = "deterministic_span_ids")]
+impl DeterministicSpanIds {
+    pub(crate) fn promote(&self, tracing_id: tracing::span::Id) -> SpanId {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let mut ids = self.ids.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "use_parking_lot")]
+        let mut ids = self.ids.lock();
+
+        let next = NonZeroU64::new(ids.len() as u64 + 1).expect("len + 1 is non-zero");
+        let id = *ids.entry(tracing_id.into_u64()).or_insert(next);
+        SpanId {
+            tracing_id: tracing::Id::from_non_zero_u64(id),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -75,7 +147,8 @@ mod test {
 
     proptest! {
         #[test]
-        // ua is [1..] and not [0..] because 0 is not a valid tracing::Id (tracing::from_u64 throws on 0)
+        // ua is [1..] and not [0..] because 0 is not a valid tracing::Id (tracing::from_u64 throws on 0).
+        // every non-zero u64 is a possible registry id, so all of them must round-trip
         fn span_id_round_trip(ua in 1u64..) {
             let span_id = SpanId {
                 tracing_id: tracing::Id::from_u64(ua),
@@ -84,5 +157,44 @@ mod test {
             let res = SpanId::from_str(&s);
             assert_eq!(Ok(span_id), res);
         }
+
+        #[test]
+        fn span_id_parses_either_case(ua in 1u64..) {
+            let lower = SpanId::from_str(&format!("{:x}", ua)).unwrap();
+            let upper = SpanId::from_str(&format!("{:X}", ua)).unwrap();
+            assert_eq!(lower, upper);
+            assert_eq!(upper.to_string(), format!("{:x}", ua));
+            assert_eq!(SpanId::from_str(&upper.to_string()), Ok(lower));
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn span_id_round_trip_serde(ua in 1u64..) {
+            let span_id = SpanId {
+                tracing_id: tracing::Id::from_u64(ua),
+            };
+            let json = serde_json::to_string(&span_id).unwrap();
+            assert_eq!(json, format!("\"{}\"", span_id));
+            let res: SpanId = serde_json::from_str(&json).unwrap();
+            assert_eq!(span_id, res);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn span_id_rejects_invalid_serde() {
+        assert!(serde_json::from_str::<SpanId>("\"0\"").is_err());
+        assert!(serde_json::from_str::<SpanId>("\"not hex\"").is_err());
+    }
+
+    #[test]
+    fn parse_errors_have_sources() {
+        let err: Box<dyn Error> = Box::new(SpanId::from_str("xyz").unwrap_err());
+        assert_eq!(err.to_string(), "span id is not a hex u64");
+        assert!(err.source().is_some());
+
+        let err = SpanId::from_str("0").unwrap_err();
+        assert_eq!(err.to_string(), "span id is zero");
+        assert!(err.source().is_some());
     }
 }
@@ -0,0 +1,282 @@
+use libhoney::json;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "use_parking_lot")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "use_parking_lot"))]
+use std::sync::{Mutex, PoisonError};
+
+use crate::honeycomb::Buffered;
+use crate::visitor::HoneycombVisitor;
+use crate::{fields, TraceId};
+
+/// Spans and events buffered for a trace whose sampling decision is pending.
+#[derive(Debug)]
+pub(crate) struct PendingTrace<T = Buffered> {
+    // position in the expiry index, i.e. when the trace started, unique among pending traces
+    started: (Instant, u64),
+    pub(crate) root_name: &'static str,
+    pub(crate) has_error: bool,
+    pub(crate) data: Vec<T>,
+}
+
+#[derive(Debug)]
+struct Pending<T> {
+    traces: HashMap<TraceId, PendingTrace<T>>,
+    // the pending traces, oldest first
+    expiry: BTreeMap<(Instant, u64), TraceId>,
+    next_seq: u64,
+}
+
+impl<T> Default for Pending<T> {
+    fn default() -> Self {
+        Pending {
+            traces: HashMap::new(),
+            expiry: BTreeMap::new(),
+            next_seq: 0,
+        }
+    }
+}
+
+impl<T> Pending<T> {
+    fn remove(&mut self, trace_id: &TraceId) -> Option<(TraceId, PendingTrace<T>)> {
+        let trace = self.traces.remove_entry(trace_id)?;
+        self.expiry.remove(&trace.1.started);
+        Some(trace)
+    }
+
+    fn pop_oldest(&mut self) -> Option<(TraceId, PendingTrace<T>)> {
+        let (_, trace_id) = self.expiry.pop_first()?;
+        self.traces.remove_entry(&trace_id)
+    }
+
+    fn oldest_start(&self) -> Option<Instant> {
+        self.expiry.keys().next().map(|(started, _)| *started)
+    }
+}
+
+/// Tail-based sampling state.
+///
+/// With tail sampling enabled, `HoneycombTelemetry` buffers the spans and events of each trace
+/// until its local root span closes, and then reports the whole trace if any of them recorded
+/// `error = true` or was at the `ERROR` level. Traces without errors are sampled as usual.
+///
+/// Traces are decided early if they are still pending after `timeout`, or if more than
+/// `max_traces` are pending (oldest first). Expiry is checked when new data is recorded, and
+/// by the `HoneycombTelemetry` in the background via [`take_expired`](Self::take_expired).
+#[derive(Debug)]
+pub(crate) struct TailSampling<T = Buffered> {
+    timeout: Duration,
+    max_traces: usize,
+    pending: Mutex<Pending<T>>,
+}
+
+impl<T> TailSampling<T> {
+    pub(crate) fn new(timeout: Duration, max_traces: usize) -> Self {
+        TailSampling {
+            timeout,
+            max_traces,
+            pending: Mutex::new(Pending::default()),
+        }
+    }
+
+    pub(crate) fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Buffers a span or event of the given trace, returning any traces which are ready to be
+    /// decided.
+    ///
+    /// `is_error` is whether the span or event recorded `error = true` or was at the `ERROR`
+    /// level.
+    pub(crate) fn record(
+        &self,
+        trace_id: &TraceId,
+        root_name: &'static str,
+        item: T,
+        local_root: bool,
+        is_error: bool,
+    ) -> Vec<(TraceId, PendingTrace<T>)> {
+        let mut pending = self.lock_pending();
+        let now = Instant::now();
+
+        let Pending {
+            traces,
+            expiry,
+            next_seq,
+        } = &mut *pending;
+        let trace = traces.entry(trace_id.clone()).or_insert_with(|| {
+            let started = (now, *next_seq);
+            *next_seq += 1;
+            expiry.insert(started, trace_id.clone());
+            PendingTrace {
+                started,
+                root_name,
+                has_error: false,
+                data: Vec::new(),
+            }
+        });
+        trace.has_error |= is_error;
+        trace.data.push(item);
+
+        let mut ready = Vec::new();
+        if local_root {
+            ready.extend(pending.remove(trace_id));
+        }
+        self.collect_expired(&mut pending, now, &mut ready);
+        while pending.traces.len() > self.max_traces {
+            match pending.pop_oldest() {
+                Some(trace) => ready.push(trace),
+                None => break,
+            }
+        }
+
+        ready
+    }
+
+    /// Returns the traces which have been pending for `timeout`, along with how long until
+    /// the next pending trace expires, if any.
+    pub(crate) fn take_expired(&self) -> (Vec<(TraceId, PendingTrace<T>)>, Option<Duration>) {
+        let mut pending = self.lock_pending();
+        let now = Instant::now();
+
+        let mut expired = Vec::new();
+        self.collect_expired(&mut pending, now, &mut expired);
+        let next_expiry = pending.oldest_start().map(|started| {
+            self.timeout
+                .saturating_sub(now.saturating_duration_since(started))
+        });
+        (expired, next_expiry)
+    }
+
+    /// Returns all pending traces, oldest first, e.g. to decide them before exiting.
+    pub(crate) fn drain(&self) -> Vec<(TraceId, PendingTrace<T>)> {
+        let mut pending = self.lock_pending();
+        std::iter::from_fn(|| pending.pop_oldest()).collect()
+    }
+
+    fn collect_expired(
+        &self,
+        pending: &mut Pending<T>,
+        now: Instant,
+        ready: &mut Vec<(TraceId, PendingTrace<T>)>,
+    ) {
+        while let Some(started) = pending.oldest_start() {
+            if now.saturating_duration_since(started) < self.timeout {
+                break;
+            }
+            ready.extend(pending.pop_oldest());
+        }
+    }
+
+    #[cfg(not(feature = "use_parking_lot"))]
+    fn lock_pending(&self) -> std::sync::MutexGuard<'_, Pending<T>> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "use_parking_lot")]
+    fn lock_pending(&self) -> parking_lot::MutexGuard<'_, Pending<T>> {
+        self.pending.lock()
+    }
+}
+
+/// Returns whether a span or event recorded `error = true` or was at the `ERROR` level.
+pub(crate) fn recorded_error(values: &HoneycombVisitor, meta: &tracing::Metadata<'_>) -> bool {
+    values.values.get(fields::ERROR) == Some(&json!(true)) || *meta.level() == tracing::Level::ERROR
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(
+        tail_sampling: &TailSampling<()>,
+        trace_id: &TraceId,
+        is_error: bool,
+        local_root: bool,
+    ) -> Vec<(TraceId, PendingTrace<()>)> {
+        tail_sampling.record(trace_id, "root", (), local_root, is_error)
+    }
+
+    #[test]
+    fn decides_trace_when_local_root_closes() {
+        let tail_sampling = TailSampling::<()>::new(Duration::from_secs(60), 16);
+        let trace_id = TraceId::new();
+
+        assert!(record(&tail_sampling, &trace_id, false, false).is_empty());
+        assert!(record(&tail_sampling, &trace_id, true, false).is_empty());
+
+        let ready = record(&tail_sampling, &trace_id, false, true);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].0, trace_id);
+        assert!(ready[0].1.has_error);
+        assert_eq!(ready[0].1.data.len(), 3);
+        assert!(tail_sampling.drain().is_empty());
+    }
+
+    #[test]
+    fn evicts_oldest_trace_over_capacity() {
+        let tail_sampling = TailSampling::<()>::new(Duration::from_secs(60), 2);
+        let first = TraceId::new();
+        let second = TraceId::new();
+        let third = TraceId::new();
+
+        assert!(record(&tail_sampling, &first, false, false).is_empty());
+        assert!(record(&tail_sampling, &second, false, false).is_empty());
+        // completing a trace frees its slot
+        assert_eq!(record(&tail_sampling, &first, false, true).len(), 1);
+        assert!(record(&tail_sampling, &first, false, false).is_empty());
+
+        let ready = record(&tail_sampling, &third, false, false);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].0, second);
+        assert!(!ready[0].1.has_error);
+    }
+
+    #[test]
+    fn evicts_expired_traces() {
+        let tail_sampling = TailSampling::<()>::new(Duration::from_millis(0), 16);
+        let trace_id = TraceId::new();
+
+        let ready = record(&tail_sampling, &trace_id, false, false);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].0, trace_id);
+    }
+
+    #[test]
+    fn takes_expired_traces_without_new_data() {
+        let tail_sampling = TailSampling::<()>::new(Duration::from_millis(50), 16);
+        let trace_id = TraceId::new();
+
+        assert!(record(&tail_sampling, &trace_id, false, false).is_empty());
+        let (expired, next_expiry) = tail_sampling.take_expired();
+        assert!(expired.is_empty());
+        assert!(next_expiry.unwrap() <= Duration::from_millis(50));
+
+        std::thread::sleep(Duration::from_millis(60));
+        let (expired, next_expiry) = tail_sampling.take_expired();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0, trace_id);
+        assert_eq!(next_expiry, None);
+    }
+
+    #[test]
+    fn drains_pending_traces_oldest_first() {
+        let tail_sampling = TailSampling::<()>::new(Duration::from_secs(60), 16);
+        let first = TraceId::new();
+        let second = TraceId::new();
+
+        record(&tail_sampling, &first, false, false);
+        record(&tail_sampling, &second, false, false);
+        record(&tail_sampling, &first, true, false);
+
+        let drained = tail_sampling.drain();
+        assert_eq!(drained.len(), 2);
+        assert_eq!(drained[0].0, first);
+        assert_eq!(drained[0].1.data.len(), 2);
+        assert!(drained[0].1.has_error);
+        assert_eq!(drained[1].0, second);
+        assert!(tail_sampling.drain().is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{self, Arc};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+use crate::reporter::{OverflowPolicy, ReportError, Reporter, ReporterHealth};
+
+/// Boxed future returned by [`AsyncReporter::report_data`]
+pub type ReportFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ReportError>> + Send + 'a>>;
+
+/// Reports data to some backend asynchronously
+///
+/// Wrap a [`Reporter`] in an [`InlineReporter`] to report to it from the background task of a
+/// [`TokioReporter`].
+pub trait AsyncReporter {
+    /// Reports data to the backend
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> ReportFuture<'_>;
+
+    /// Transmits any data buffered by the reporter, as for [`Reporter::flush`]
+    ///
+    /// Defaults to doing nothing.
+    fn flush(&self) -> ReportFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Health of the reporter, as for [`Reporter::health`]
+    ///
+    /// Defaults to [`ReporterHealth::Healthy`].
+    fn health(&self) -> ReporterHealth {
+        ReporterHealth::Healthy
+    }
+}
+
+/// [`AsyncReporter`] which reports to the wrapped [`Reporter`] inline when polled, blocking
+/// the task polling it for as long as the wrapped reporter does
+#[derive(Debug, Clone)]
+pub struct InlineReporter<R>(R);
+
+impl<R: Reporter> InlineReporter<R> {
+    /// Returns a new `InlineReporter` reporting to `reporter`.
+    pub fn new(reporter: R) -> Self {
+        InlineReporter(reporter)
+    }
+}
+
+impl<R: Reporter + Sync> AsyncReporter for InlineReporter<R> {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> ReportFuture<'_> {
+        Box::pin(async move { self.0.report_data(data, timestamp) })
+    }
+
+    fn flush(&self) -> ReportFuture<'_> {
+        Box::pin(async move { self.0.flush() })
+    }
+
+    fn health(&self) -> ReporterHealth {
+        self.0.health()
+    }
+}
+
+enum Message {
+    Data(HashMap<String, libhoney::Value>, DateTime<Utc>),
+    Flush(sync::mpsc::Sender<Result<(), ReportError>>),
+}
+
+/// Reporter that hands data off to a background task via a bounded `tokio` channel
+///
+/// The background task owns the wrapped [`AsyncReporter`] and drains the channel into it,
+/// so the traced code path never contends on the wrapped reporter.
+///
+/// [`OverflowPolicy::Block`] only blocks outside of a `tokio` runtime. Within one, where
+/// blocking would stall a worker thread which may be the one running the drainer, data the
+/// channel has no room for is dropped, as with [`OverflowPolicy::Drop`].
+/// [`flush`](Reporter::flush) waits for the drainer either way, so must not be called from a
+/// single-threaded runtime which also runs the drainer.
+pub struct TokioReporter {
+    sender: mpsc::Sender<Message>,
+    overflow: OverflowPolicy,
+    // shared with the background task, to report its health
+    reporter: Arc<dyn AsyncReporter + Send + Sync>,
+}
+
+impl TokioReporter {
+    /// Spawns a task on the current `tokio` runtime which drains a channel of the given
+    /// capacity into `reporter`.
+    ///
+    /// The task exits once the returned `TokioReporter` is dropped and the channel is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a `tokio` runtime, or if `capacity` is 0.
+    pub fn spawn<A>(reporter: A, capacity: usize, overflow: OverflowPolicy) -> Self
+    where
+        A: AsyncReporter + Send + Sync + 'static,
+    {
+        let reporter = Arc::new(reporter);
+        let (sender, mut receiver) = mpsc::channel::<Message>(capacity);
+        let drained = reporter.clone();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                match message {
+                    Message::Data(data, timestamp) => {
+                        let res = AsyncReporter::report_data(&*drained, data, timestamp).await;
+                        if let Err(err) = res {
+                            // no caller to hand the error back to, so log msg to stderr
+                            eprintln!("error sending event to honeycomb, {}", err);
+                        }
+                    }
+                    Message::Flush(done) => {
+                        let _ = done.send(AsyncReporter::flush(&*drained).await);
+                    }
+                }
+            }
+        });
+
+        TokioReporter {
+            sender,
+            overflow,
+            reporter,
+        }
+    }
+
+    // blocks for room in the channel under `OverflowPolicy::Block` outside of a runtime only,
+    // as blocking within one could starve the background task of the thread it needs to drain
+    fn send(&self, message: Message, overflow: OverflowPolicy) -> Result<(), ReportError> {
+        // sending requires &mut, clone the (cheap) sender rather than locking it
+        let mut sender = self.sender.clone();
+        let block = overflow == OverflowPolicy::Block && Handle::try_current().is_err();
+        let mut message = message;
+        loop {
+            match sender.try_send(message) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::error::TrySendError::Closed(_)) => return Err(ReportError::Closed),
+                Err(mpsc::error::TrySendError::Full(_)) if !block => {
+                    return Err(ReportError::Overflow)
+                }
+                // tokio 0.2 has no blocking send, poll for room instead
+                Err(mpsc::error::TrySendError::Full(returned)) => {
+                    message = returned;
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+            }
+        }
+    }
+}
+
+impl Reporter for TokioReporter {
+    /// Errors only indicate that the data could not be queued. Errors from the wrapped
+    /// reporter are logged to stderr by the background task.
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        self.send(Message::Data(data, timestamp), self.overflow)
+    }
+
+    /// Waits for all data already in the channel to be reported, then flushes the wrapped
+    /// reporter. Outside of a `tokio` runtime, waits for room in the channel whatever the
+    /// [`OverflowPolicy`]; within one, fails with [`ReportError::Overflow`] if it is full.
+    fn flush(&self) -> Result<(), ReportError> {
+        let (done, result) = sync::mpsc::channel();
+        self.send(Message::Flush(done), OverflowPolicy::Block)?;
+        result.recv().unwrap_or(Err(ReportError::Closed))
+    }
+
+    fn health(&self) -> ReporterHealth {
+        AsyncReporter::health(&*self.reporter)
+    }
+}
+
+impl fmt::Debug for TokioReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokioReporter")
+            .field("sender", &self.sender)
+            .field("overflow", &self.overflow)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[cfg(feature = "use_parking_lot")]
+    use parking_lot::Mutex;
+    #[cfg(not(feature = "use_parking_lot"))]
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct CountingReporter(Mutex<usize>);
+
+    impl Reporter for Arc<CountingReporter> {
+        fn report_data(
+            &self,
+            _: HashMap<String, libhoney::Value>,
+            _: DateTime<Utc>,
+        ) -> Result<(), ReportError> {
+            #[cfg(not(feature = "use_parking_lot"))]
+            let mut count = self.0.lock().unwrap();
+            #[cfg(feature = "use_parking_lot")]
+            let mut count = self.0.lock();
+
+            *count += 1;
+            Ok(())
+        }
+    }
+
+    fn count(reporter: &CountingReporter) -> usize {
+        #[cfg(not(feature = "use_parking_lot"))]
+        let count = reporter.0.lock().unwrap();
+        #[cfg(feature = "use_parking_lot")]
+        let count = reporter.0.lock();
+
+        *count
+    }
+
+    // single-threaded, so the drainer only makes progress while the runtime is being driven
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn drop_policy_discards_overflow() {
+        let mut rt = runtime();
+        let counter = Arc::new(CountingReporter::default());
+
+        let reporter = rt.enter(|| {
+            TokioReporter::spawn(
+                InlineReporter::new(counter.clone()),
+                2,
+                OverflowPolicy::Drop,
+            )
+        });
+        let results: Vec<_> = (0..10)
+            .map(|_| Reporter::report_data(&reporter, HashMap::new(), Utc::now()))
+            .collect();
+        drop(reporter);
+        assert!(matches!(results.last(), Some(Err(ReportError::Overflow))));
+
+        rt.block_on(async {
+            tokio::time::delay_for(std::time::Duration::from_millis(50)).await;
+        });
+        assert!(count(&counter) < 10);
+    }
+
+    #[test]
+    fn block_policy_delivers_everything() {
+        let mut rt = runtime();
+        let counter = Arc::new(CountingReporter::default());
+
+        let reporter = rt.enter(|| {
+            TokioReporter::spawn(
+                InlineReporter::new(counter.clone()),
+                2,
+                OverflowPolicy::Block,
+            )
+        });
+        let producer = thread::spawn(move || {
+            for _ in 0..10 {
+                Reporter::report_data(&reporter, HashMap::new(), Utc::now()).unwrap();
+            }
+        });
+
+        rt.block_on(async {
+            while count(&counter) < 10 {
+                tokio::time::delay_for(std::time::Duration::from_millis(1)).await;
+            }
+        });
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn block_policy_drops_overflow_within_runtime() {
+        let rt = runtime();
+        let counter = Arc::new(CountingReporter::default());
+
+        let results: Vec<_> = rt.enter(|| {
+            let reporter = TokioReporter::spawn(
+                InlineReporter::new(counter.clone()),
+                2,
+                OverflowPolicy::Block,
+            );
+            (0..10)
+                .map(|_| Reporter::report_data(&reporter, HashMap::new(), Utc::now()))
+                .collect()
+        });
+        assert!(matches!(results.last(), Some(Err(ReportError::Overflow))));
+    }
+
+    #[test]
+    fn flush_waits_for_queued_data() {
+        let mut rt = runtime();
+        let counter = Arc::new(CountingReporter::default());
+
+        let reporter = rt.enter(|| {
+            TokioReporter::spawn(
+                InlineReporter::new(counter.clone()),
+                16,
+                OverflowPolicy::Drop,
+            )
+        });
+        for _ in 0..5 {
+            Reporter::report_data(&reporter, HashMap::new(), Utc::now()).unwrap();
+        }
+        let flushed = counter.clone();
+        let flusher = thread::spawn(move || {
+            Reporter::flush(&reporter).unwrap();
+            count(&flushed)
+        });
+
+        rt.block_on(async {
+            while !flusher.is_finished() {
+                tokio::time::delay_for(std::time::Duration::from_millis(1)).await;
+            }
+        });
+        assert_eq!(flusher.join().unwrap(), 5);
+    }
+}
@@ -3,6 +3,7 @@ use std::convert::{Infallible, TryFrom};
 use std::fmt::{self, Display};
 use std::str::FromStr;
 
+use rand::Rng;
 use uuid::Uuid;
 
 /// A Honeycomb Trace ID.
@@ -12,7 +13,10 @@ use uuid::Uuid;
 /// Does no parsing on string input values. Can be generated new from a UUID V4.
 ///
 /// `Display` and `FromStr` are guaranteed to round-trip.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+///
+/// Ordered by the bytes of its string form, consistently with `Eq`. For ids in the canonical
+/// 32 character lowercase hex form, this matches the order of their 128-bit values.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct TraceId(pub(crate) String);
 
 impl TraceId {
@@ -26,13 +30,62 @@ impl TraceId {
         Uuid::new_v4().into()
     }
 
-    #[deprecated(since = "0.2.0", note = "Use `TraceId::new()` instead.")]
-    /// Generate a new `TraceId` from a UUID V4.
+    /// Construct a `TraceId` from a 128-bit value.
+    ///
+    /// `Display` produces the canonical 32 character lowercase hex form, as used by
+    /// OpenTelemetry and the W3C Trace Context.
+    pub fn from_u128(u: u128) -> Self {
+        u.into()
+    }
+
+    /// Returns the 128-bit value of this `TraceId`, if it is a UUID or a hex value of up to 32
+    /// characters, such as a 64-bit B3 trace id.
+    pub fn as_u128(&self) -> Option<u128> {
+        if let Ok(uuid) = Uuid::parse_str(&self.0) {
+            return Some(uuid.as_u128());
+        }
+
+        if !self.0.is_empty() && self.0.len() <= 32 && self.0.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            u128::from_str_radix(&self.0, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the 16 byte, big-endian, binary form of this `TraceId`, if it is a UUID or a hex
+    /// value of up to 32 characters, as with `as_u128`.
     ///
-    /// Prefer `TraceId::new()`.
+    /// Converting the bytes back with `TraceId::try_from` yields the canonical 32 character
+    /// lowercase hex form of the id, so round-trips are lossless for ids in that form, such as
+    /// those from `TraceId::new` and `TraceId::from_u128`.
+    pub fn to_bytes(&self) -> Option<[u8; 16]> {
+        self.as_u128().map(u128::to_be_bytes)
+    }
+
+    /// Generate a new random `TraceId`, suitable for use with `register_dist_tracing_root`.
+    ///
+    /// Equivalent to `TraceId::new()`, using the operating system's random number generator.
+    /// The generated id is a UUID V4 in the 32 character lowercase hex form produced by
+    /// `Display`, and round-trips through `FromStr`.
     pub fn generate() -> Self {
         TraceId::new()
     }
+
+    /// Generate a new `TraceId` using the provided random number generator, e.g. a seeded
+    /// RNG for deterministic tests.
+    ///
+    /// The generated id has the same format as those from `TraceId::generate()`.
+    pub fn generate_from<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut bytes = [0; 16];
+        rng.fill(&mut bytes);
+
+        uuid::Builder::from_bytes(bytes)
+            .set_variant(uuid::Variant::RFC4122)
+            .set_version(uuid::Version::Random)
+            .build()
+            .into()
+    }
 }
 
 impl Default for TraceId {
@@ -47,6 +100,29 @@ impl AsRef<[u8]> for TraceId {
     }
 }
 
+/// Error converting a byte slice into a `TraceId`, holding the length of the slice, which
+/// must be 16.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TraceIdLengthError(pub usize);
+
+impl Display for TraceIdLengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "trace id must be 16 bytes, got {}", self.0)
+    }
+}
+
+impl std::error::Error for TraceIdLengthError {}
+
+impl TryFrom<&[u8]> for TraceId {
+    type Error = TraceIdLengthError;
+
+    /// Converts the 16 byte, big-endian, binary form produced by `TraceId::to_bytes`.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes = <[u8; 16]>::try_from(bytes).map_err(|_| TraceIdLengthError(bytes.len()))?;
+        Ok(TraceId::from_u128(u128::from_be_bytes(bytes)))
+    }
+}
+
 impl From<TraceId> for String {
     fn from(trace_id: TraceId) -> String {
         format!("{}", trace_id)
@@ -105,17 +181,46 @@ impl FromStr for TraceId {
     type Err = Infallible;
 
     /// Is actually infalliable.
+    ///
+    /// Ids from `TraceId::new` and 32 character hex OpenTelemetry or W3C trace ids share the
+    /// same format, so parse alike, to ids comparing equal to the originals. Hex ids of 16 or
+    /// 32 characters, such as those from B3 headers, are lowercased, as some peers send them in
+    /// uppercase. Any other string is kept unchanged, so that `Display` reproduces it.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(s.to_owned()))
+        Ok(Self(normalize_case(s.to_owned())))
     }
 }
 
+/// Lowercases 16 and 32 character hex ids, leaving other strings unchanged.
+fn normalize_case(mut s: String) -> String {
+    if (s.len() == 16 || s.len() == 32) && s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        s.make_ascii_lowercase();
+    }
+    s
+}
+
 impl Display for TraceId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
+/// Serializes to the same string as `Display`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TraceId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// Deserializes from any string, as with `FromStr`.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TraceId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|s| Self(normalize_case(s)))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -132,6 +237,104 @@ mod test {
         }
     }
 
+    proptest! {
+        #[test]
+        fn trace_id_u128_round_trip(u: u128) {
+            let trace_id = TraceId::from_u128(u);
+            assert_eq!(trace_id.to_string().len(), 32);
+            assert_eq!(trace_id.as_u128(), Some(u));
+            assert_eq!(TraceId::from_str(&trace_id.to_string()).unwrap().as_u128(), Some(u));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn trace_id_native_and_otel_round_trip(u: u128) {
+            let native = TraceId::from_u128(u);
+            let otel = format!("{:032x}", u);
+            assert_eq!(TraceId::from_str(&native.to_string()), Ok(native.clone()));
+            assert_eq!(TraceId::from_str(&otel), Ok(native));
+            assert_eq!(TraceId::from_str(&otel).unwrap().to_string(), otel);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn trace_id_parses_either_case(u: u128) {
+            let lower = format!("{:032x}", u);
+            let upper = format!("{:032X}", u);
+            assert_eq!(TraceId::from_str(&upper), TraceId::from_str(&lower));
+            let trace_id = TraceId::from_str(&upper).unwrap();
+            assert_eq!(trace_id.to_string(), lower);
+            assert_eq!(TraceId::from_str(&trace_id.to_string()), Ok(trace_id));
+
+            let b3 = TraceId::from_str(&format!("{:016X}", u as u64)).unwrap();
+            assert_eq!(b3.to_string(), format!("{:016x}", u as u64));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn trace_id_orders_by_u128(a: u128, b: u128) {
+            let (x, y) = (TraceId::from_u128(a), TraceId::from_u128(b));
+            assert_eq!(x.cmp(&y), a.cmp(&b));
+            assert_eq!(x == y, x.cmp(&y) == std::cmp::Ordering::Equal);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn trace_id_bytes_round_trip(u: u128) {
+            let trace_id = TraceId::from_u128(u);
+            let bytes = trace_id.to_bytes().unwrap();
+            assert_eq!(TraceId::try_from(&bytes[..]), Ok(trace_id));
+        }
+    }
+
+    #[test]
+    fn trace_id_from_bytes_checks_length() {
+        assert_eq!(
+            TraceId::try_from(&[0u8; 15][..]),
+            Err(TraceIdLengthError(15))
+        );
+        assert_eq!(TraceId::try_from(&[0u8; 0][..]), Err(TraceIdLengthError(0)));
+        assert_eq!(TraceId::from("a string").to_bytes(), None);
+    }
+
+    #[test]
+    fn trace_id_as_u128() {
+        assert_eq!(
+            TraceId::from("463ac35c9f6413ad").as_u128(),
+            Some(0x463a_c35c_9f64_13ad)
+        );
+        assert_eq!(
+            TraceId::from("80f198ee-5634-3ba8-64fe-8b2a57d3eff7").as_u128(),
+            TraceId::from("80F198EE56343BA864FE8B2A57D3EFF7").as_u128()
+        );
+        assert_eq!(TraceId::from("").as_u128(), None);
+        assert_eq!(TraceId::from("a string").as_u128(), None);
+        assert_eq!(TraceId::from("0".repeat(33)).as_u128(), None);
+    }
+
+    #[test]
+    fn trace_id_generate_from_seeded_rng() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let trace_id = TraceId::generate_from(&mut rng);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        assert_eq!(trace_id, TraceId::generate_from(&mut rng));
+        assert_ne!(trace_id, TraceId::generate_from(&mut rng));
+
+        let uuid = Uuid::try_from(trace_id.clone()).unwrap();
+        assert_eq!(uuid.get_version(), Some(uuid::Version::Random));
+        assert_eq!(
+            trace_id.to_string().len(),
+            TraceId::generate().to_string().len()
+        );
+        assert_eq!(TraceId::from_str(&trace_id.to_string()), Ok(trace_id));
+    }
+
     #[test]
     fn trace_id_round_trip_str() {
         let trace_id: TraceId = "a string".into();
@@ -147,4 +350,14 @@ mod test {
         let res = TraceId::from_str(&s);
         assert_eq!(Ok(trace_id), res);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn trace_id_round_trip_serde() {
+        let trace_id = TraceId::new();
+        let json = serde_json::to_string(&trace_id).unwrap();
+        assert_eq!(json, format!("\"{}\"", trace_id));
+        let res: TraceId = serde_json::from_str(&json).unwrap();
+        assert_eq!(trace_id, res);
+    }
 }
@@ -0,0 +1,25 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Reported as the [`target`](crate::fields::TARGET) of the events marking the start of a
+/// trace, if enabled via
+/// [`Builder::with_trace_start_events`](crate::Builder::with_trace_start_events).
+pub const TRACE_START_TARGET: &str = "tracing_honeycomb::trace_start";
+
+// set once any layer reports the start of traces, so that registering the root of a trace
+// skips looking up the layer otherwise
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Reports the start of the trace the current span is the registered root of, if any layer
+/// reports the start of traces.
+///
+/// The span is registered by now, so failing to report the start of its trace is not an error
+/// of registering it, and is ignored.
+pub(crate) fn report() {
+    if ENABLED.load(Ordering::Relaxed) {
+        let _ = tracing_distributed::report_trace_start::<crate::SpanId, crate::TraceId>();
+    }
+}
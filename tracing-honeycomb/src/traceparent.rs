@@ -0,0 +1,277 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::num::NonZeroU64;
+use std::str::FromStr;
+
+use crate::{SpanId, TraceId};
+
+const SUPPORTED_VERSION: u8 = 0x00;
+const SAMPLED_FLAG: u8 = 0x01;
+
+/// Error parsing or emitting a W3C Trace Context `traceparent` header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TraceParentError {
+    /// The header is not of the form `<2 hex>-<32 hex>-<16 hex>-<2 hex>`, using lowercase hex.
+    Malformed,
+    /// The header's version is not `00`.
+    UnsupportedVersion(u8),
+    /// The trace id is all zeroes, or a `TraceId` is not a 128-bit hex or UUID value.
+    InvalidTraceId,
+    /// The parent span id is all zeroes.
+    InvalidSpanId,
+}
+
+impl Display for TraceParentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed traceparent"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported traceparent version {:02x}", v),
+            Self::InvalidTraceId => write!(f, "invalid traceparent trace id"),
+            Self::InvalidSpanId => write!(f, "invalid traceparent span id"),
+        }
+    }
+}
+
+impl std::error::Error for TraceParentError {}
+
+/// A W3C Trace Context `traceparent` header, as propagated by OpenTelemetry.
+///
+/// `Display` emits the header and `FromStr` parses it; only version `00` is supported.
+/// Parsed values can be passed to `register_dist_tracing_root` to continue a trace started
+/// by a W3C-propagating peer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceParent {
+    trace_id: TraceId,
+    span_id: SpanId,
+    sampled: bool,
+}
+
+impl TraceParent {
+    /// Metadata field name associated with `traceparent` header values.
+    pub fn meta_field_name() -> &'static str {
+        "traceparent"
+    }
+
+    /// Returns a new, sampled, `TraceParent`.
+    ///
+    /// Fails if `trace_id` is not a 128-bit hex or UUID value, such as one created by
+    /// `TraceId::new`, or if it is all zeroes.
+    pub fn new(trace_id: TraceId, span_id: SpanId) -> Result<Self, TraceParentError> {
+        match u128::try_from(trace_id.clone()) {
+            Ok(0) | Err(_) => Err(TraceParentError::InvalidTraceId),
+            Ok(_) => Ok(TraceParent {
+                trace_id,
+                span_id,
+                sampled: true,
+            }),
+        }
+    }
+
+    /// Sets the sampled flag.
+    pub fn with_sampled(mut self, sampled: bool) -> Self {
+        self.sampled = sampled;
+        self
+    }
+
+    /// The trace this header belongs to.
+    pub fn trace_id(&self) -> &TraceId {
+        &self.trace_id
+    }
+
+    /// The span which propagated this header.
+    pub fn span_id(&self) -> &SpanId {
+        &self.span_id
+    }
+
+    /// Whether the caller may have recorded this trace.
+    pub fn sampled(&self) -> bool {
+        self.sampled
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = TraceParentError;
+
+    /// Parses a version `00` `traceparent` header.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('-').collect();
+        let version = parse_hex_field(parts.first(), 2)?;
+        let version = u8::try_from(version).map_err(|_| TraceParentError::Malformed)?;
+        if version != SUPPORTED_VERSION {
+            return Err(TraceParentError::UnsupportedVersion(version));
+        }
+        if parts.len() != 4 {
+            return Err(TraceParentError::Malformed);
+        }
+
+        let trace_id = parse_hex_field(parts.get(1), 32)?;
+        let span_id = parse_hex_field(parts.get(2), 16)?;
+        let flags = parse_hex_field(parts.get(3), 2)?;
+
+        if trace_id == 0 {
+            return Err(TraceParentError::InvalidTraceId);
+        }
+        let span_id = NonZeroU64::new(span_id as u64).ok_or(TraceParentError::InvalidSpanId)?;
+
+        Ok(TraceParent {
+            trace_id: trace_id.into(),
+            span_id: SpanId {
+                tracing_id: tracing::Id::from_non_zero_u64(span_id),
+            },
+            sampled: flags as u8 & SAMPLED_FLAG != 0,
+        })
+    }
+}
+
+impl Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // validated by TraceParent::new, or produced from a u128 by FromStr
+        let trace_id =
+            u128::try_from(self.trace_id.clone()).expect("TraceParent holds a 128-bit trace id");
+        let flags = if self.sampled { SAMPLED_FLAG } else { 0 };
+
+        write!(
+            f,
+            "{:02x}-{:032x}-{:016x}-{:02x}",
+            SUPPORTED_VERSION,
+            trace_id,
+            self.span_id.tracing_id.into_u64(),
+            flags
+        )
+    }
+}
+
+fn parse_hex_field(field: Option<&&str>, len: usize) -> Result<u128, TraceParentError> {
+    match field {
+        Some(field)
+            if field.len() == len
+                && field
+                    .bytes()
+                    .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()) =>
+        {
+            u128::from_str_radix(field, 16).map_err(|_| TraceParentError::Malformed)
+        }
+        _ => Err(TraceParentError::Malformed),
+    }
+}
+
+impl TraceId {
+    /// Parses the trace id from a W3C `traceparent` header.
+    pub fn from_traceparent(traceparent: &str) -> Result<Self, TraceParentError> {
+        Ok(traceparent.parse::<TraceParent>()?.trace_id)
+    }
+
+    /// Emits a W3C `traceparent` header for this trace, with `span_id` as the parent span.
+    ///
+    /// Fails if this `TraceId` is not a 128-bit hex or UUID value, such as one created by
+    /// `TraceId::new`.
+    pub fn to_traceparent(
+        &self,
+        span_id: &SpanId,
+        sampled: bool,
+    ) -> Result<String, TraceParentError> {
+        let traceparent = TraceParent::new(self.clone(), span_id.clone())?;
+        Ok(traceparent.with_sampled(sampled).to_string())
+    }
+}
+
+impl SpanId {
+    /// Parses the parent span id from a W3C `traceparent` header.
+    pub fn from_traceparent(traceparent: &str) -> Result<Self, TraceParentError> {
+        Ok(traceparent.parse::<TraceParent>()?.span_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EXAMPLE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    proptest! {
+        #[test]
+        fn traceparent_round_trip(t in 1u128.., s in 1u64.., sampled: bool) {
+            let trace_id: TraceId = t.into();
+            let span_id = SpanId {
+                tracing_id: tracing::Id::from_u64(s),
+            };
+            let header = trace_id.to_traceparent(&span_id, sampled).unwrap();
+            let res = TraceParent::from_str(&header);
+            assert_eq!(Ok(TraceParent { trace_id, span_id, sampled }), res);
+        }
+    }
+
+    #[test]
+    fn parses_traceparent() {
+        let traceparent = TraceParent::from_str(EXAMPLE).unwrap();
+        assert_eq!(
+            traceparent.trace_id,
+            TraceId::from("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        assert_eq!(traceparent.span_id.to_string(), "f067aa0ba902b7");
+        assert!(traceparent.sampled);
+        assert_eq!(traceparent.to_string(), EXAMPLE);
+
+        assert_eq!(TraceId::from_traceparent(EXAMPLE), Ok(traceparent.trace_id));
+        assert_eq!(SpanId::from_traceparent(EXAMPLE), Ok(traceparent.span_id));
+    }
+
+    #[test]
+    fn parses_unsampled_traceparent() {
+        let traceparent =
+            TraceParent::from_str("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00");
+        assert!(!traceparent.unwrap().sampled);
+    }
+
+    #[test]
+    fn rejects_malformed_traceparent() {
+        for s in &[
+            "",
+            "00",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-",
+            "0-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-+bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(TraceParent::from_str(s), Err(TraceParentError::Malformed));
+        }
+    }
+
+    #[test]
+    fn rejects_zero_ids() {
+        assert_eq!(
+            TraceParent::from_str("00-00000000000000000000000000000000-00f067aa0ba902b7-01"),
+            Err(TraceParentError::InvalidTraceId)
+        );
+        assert_eq!(
+            TraceParent::from_str("00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01"),
+            Err(TraceParentError::InvalidSpanId)
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_version() {
+        assert_eq!(
+            TraceParent::from_str("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            Err(TraceParentError::UnsupportedVersion(0x01))
+        );
+        assert_eq!(
+            TraceParent::from_str("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            Err(TraceParentError::UnsupportedVersion(0xff))
+        );
+    }
+
+    #[test]
+    fn rejects_non_hex_trace_id_on_emit() {
+        let span_id = SpanId::from_str("1").unwrap();
+        assert_eq!(
+            TraceId::from("a string").to_traceparent(&span_id, true),
+            Err(TraceParentError::InvalidTraceId)
+        );
+    }
+}
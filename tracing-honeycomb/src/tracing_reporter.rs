@@ -0,0 +1,186 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use crate::reporter::{fields_to_json, ReportError, Reporter};
+
+/// Target of the events emitted by [`TracingReporter`].
+pub const TRACING_REPORTER_TARGET: &str = "tracing_honeycomb::reported";
+
+thread_local! {
+    // set while a `TracingReporter` emits an event on this thread
+    static REPORTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether a `TracingReporter` is emitting an event on the current thread, in which case
+/// neither the event nor spans closing meanwhile may be captured by a `TelemetryLayer`.
+pub(crate) fn is_reporting() -> bool {
+    REPORTING.with(Cell::get)
+}
+
+// sets the flag read by `is_reporting` while calling `f`, restoring its previous value after,
+// should this be nested in another `TracingReporter`
+fn reporting<T>(f: impl FnOnce() -> T) -> T {
+    let reporting = REPORTING.with(|reporting| reporting.replace(true));
+    let res = f();
+    REPORTING.with(|r| r.set(reporting));
+    res
+}
+
+/// Reporter that re-emits each span and event as a `TRACE` level `tracing` event, e.g. to see
+/// in an application's regular logs exactly what would be sent to Honeycomb.
+///
+/// Events are emitted with the [`TRACING_REPORTER_TARGET`] target, the reported data as a JSON
+/// object in their `data` field, and the span or event's timestamp in their `timestamp` field.
+/// So they are only logged if the subscriber enables `TRACE` for that target, e.g. with an
+/// `EnvFilter` of `tracing_honeycomb::reported=trace`.
+///
+/// Events emitted while reporting would otherwise be captured by the `TelemetryLayer` reporting
+/// to this reporter, and reported again, without end. To prevent this, a thread-local flag is
+/// set while the event is emitted, and `TelemetryLayer`s built by this crate ignore all events,
+/// and all spans closing, on a thread while it is set, e.g. spans opened by another layer
+/// handling the event. Since `tracing` dispatches events on the thread emitting them,
+/// this holds even when reporting happens on a background thread, e.g. behind a
+/// [`BatchingReporter`](crate::BatchingReporter).
+///
+/// `tracing` itself drops events emitted while a subscriber set via
+/// `tracing::subscriber::with_default` is handling another event on the same thread. So with
+/// such a scoped subscriber, events reported from within their own dispatch are not re-emitted,
+/// unlike spans, which are reported as they close. Report via a background thread, e.g. with a
+/// `BatchingReporter`, to see them too. Subscribers set via `set_global_default` are not
+/// affected.
+///
+/// Requires the `use_tracing_reporter` feature.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingReporter;
+
+impl TracingReporter {
+    /// Returns a new `TracingReporter`.
+    pub fn new() -> Self {
+        TracingReporter
+    }
+}
+
+impl Reporter for TracingReporter {
+    fn report_data(
+        &self,
+        data: HashMap<String, libhoney::Value>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ReportError> {
+        let data = fields_to_json(&data)?;
+        let timestamp = timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true);
+
+        reporting(|| {
+            tracing::trace!(
+                target: TRACING_REPORTER_TARGET,
+                data = %data,
+                timestamp = %timestamp,
+                "reporting to honeycomb"
+            )
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    // counts the events emitted by `TracingReporter`
+    struct ReportedEvents(Arc<AtomicUsize>);
+
+    impl<S: tracing::Subscriber> Layer<S> for ReportedEvents {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            if event.metadata().target() == TRACING_REPORTER_TARGET {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_without_recapturing_its_own_events() {
+        let reported = Arc::new(AtomicUsize::new(0));
+        let layer = crate::Builder::new("test", TracingReporter::new()).build();
+        let subscriber = tracing_subscriber::registry()
+            .with(layer)
+            .with(ReportedEvents(reported.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(crate::TraceId::new(), None).unwrap();
+            tracing::info!("event");
+        });
+
+        // the span, but not the event emitted to report it. The event, reported while the
+        // scoped subscriber dispatches it, is dropped by `tracing`
+        assert_eq!(reported.load(Ordering::Relaxed), 1);
+        assert!(!is_reporting());
+    }
+
+    // reports to a `TracingReporter`, tracking how deeply it is nested in itself
+    #[derive(Default)]
+    struct NestingReporter {
+        depth: AtomicUsize,
+        max_depth: Arc<AtomicUsize>,
+        reports: Arc<AtomicUsize>,
+    }
+
+    impl Reporter for NestingReporter {
+        fn report_data(
+            &self,
+            data: HashMap<String, libhoney::Value>,
+            timestamp: DateTime<Utc>,
+        ) -> Result<(), ReportError> {
+            let depth = self.depth.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_depth.fetch_max(depth, Ordering::SeqCst);
+            self.reports.fetch_add(1, Ordering::SeqCst);
+            let res = TracingReporter.report_data(data, timestamp);
+            self.depth.fetch_sub(1, Ordering::SeqCst);
+            res
+        }
+    }
+
+    #[test]
+    fn does_not_reenter_reporter() {
+        let reporter = NestingReporter::default();
+        let (max_depth, reports) = (reporter.max_depth.clone(), reporter.reports.clone());
+        let layer = crate::Builder::new("test", reporter).build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        // the child is reported as it closes, outside of the dispatch of an event, so the
+        // event emitted to report it is dispatched to the layer
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(crate::TraceId::new(), None).unwrap();
+            tracing::info_span!("child").in_scope(|| {});
+        });
+
+        assert_eq!(reports.load(Ordering::SeqCst), 2);
+        assert_eq!(max_depth.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn ignores_spans_and_events_while_reporting() {
+        let reporter = crate::TestReporter::new();
+        let layer = crate::Builder::new("test", reporter.clone()).build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("root");
+            let _guard = span.enter();
+            crate::register_dist_tracing_root(crate::TraceId::new(), None).unwrap();
+            reporting(|| {
+                tracing::info_span!("ignored").in_scope(|| tracing::info!("ignored"));
+            });
+        });
+
+        let recorded = reporter.recorded();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0]["name"], libhoney::json!("root"));
+    }
+}
@@ -1,152 +1,1555 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use libhoney::{json, Value};
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
 use tracing::field::{Field, Visit};
 use tracing_distributed::{Event, Span};
 
-use crate::{SpanId, TraceId};
+use crate::sampler::Sampler;
+use crate::{fields, SpanId, TraceId};
 
 const MILLIS_PER_SECOND: f64 = 1000_f64;
 
-// Visitor that builds honeycomb-compatible values from tracing fields.
-#[derive(Default, Debug)]
-#[doc(hidden)]
-pub struct HoneycombVisitor(pub(crate) HashMap<String, Value>);
-
-// reserved field names (TODO: document)
-static RESERVED_WORDS: [&str; 9] = [
-    "trace.span_id",
-    "trace.trace_id",
-    "trace.parent_id",
-    "service_name",
-    "level",
-    "Timestamp",
-    "name",
-    "target",
-    "duration_ms",
-];
+/// Field which, when recorded on a span or event as a string, overrides the layer's service
+/// name for that span or event, e.g. `tracing::info_span!("charge", service.name = "billing")`.
+///
+/// The override is reported as `service_name`, taking precedence over the `service_name`
+/// the layer was constructed with, which still applies to spans and events without the field.
+/// It is not inherited by child spans or events, which must record the field themselves.
+pub const SERVICE_NAME_FIELD: &str = "service.name";
+
+/// Function mapping the names of recorded fields to the keys they are reported under, e.g. to
+/// namespace them with a prefix.
+///
+/// Not applied to the keys set by this crate, listed in [`fields`](crate::fields), nor to
+/// [`DATASET_FIELD`](crate::DATASET_FIELD), which reporters route by. Fields recorded with
+/// one of the former names are prefixed with `tracing.` before being mapped.
+#[derive(Clone)]
+pub struct KeyMapping(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl KeyMapping {
+    /// Returns a new `KeyMapping` applying the provided function.
+    pub fn new<F: Fn(&str) -> String + Send + Sync + 'static>(key_mapping: F) -> Self {
+        KeyMapping(Arc::new(key_mapping))
+    }
+}
+
+impl fmt::Debug for KeyMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyMapping(..)")
+    }
+}
+
+/// Function converting the value of a field recorded via its `Debug` implementation into the
+/// value it is reported as, e.g. [`duration_as_ms`].
+///
+/// Converters are applied in [`Visit::record_debug`], which receives values recorded with the
+/// `?` and `%` sigils, as well as `f64` values and any other type without a dedicated `record_*`
+/// method. Values recorded as `i64`, `u64`, `bool`, `&str` or errors are reported as-is.
+/// `tracing` only passes such values on as `&dyn Debug`, so converters can inspect their `Debug`
+/// output but not their type.
+#[derive(Clone)]
+pub struct FieldConverter(Arc<ConvertFn>);
+
+type ConvertFn = dyn Fn(&dyn fmt::Debug) -> Value + Send + Sync;
+
+impl FieldConverter {
+    /// Returns a new `FieldConverter` applying the provided function.
+    pub fn new<F>(converter: F) -> Self
+    where
+        F: Fn(&dyn fmt::Debug) -> Value + Send + Sync + 'static,
+    {
+        FieldConverter(Arc::new(converter))
+    }
+}
+
+impl fmt::Debug for FieldConverter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FieldConverter(..)")
+    }
+}
+
+/// Type to which the values of a field are coerced before they are reported, as configured via
+/// [`Builder::with_field_type`](crate::Builder::with_field_type), so that Honeycomb, which
+/// infers a column's type from the first value it sees, sees the same type every time.
+///
+/// Values which cannot be coerced are reported as recorded, along with a
+/// `<name>.coercion_error` field describing the failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// Integers are kept, and floats without a fractional part converted, if they fit an `i64`
+    /// or `u64`. Strings are parsed as integers, ignoring surrounding whitespace. Other values
+    /// fail to coerce.
+    Int,
+    /// Numbers are converted to floats. Strings are parsed as finite floats, ignoring
+    /// surrounding whitespace. Other values fail to coerce.
+    Float,
+    /// Booleans are kept. The strings `true` and `false` are parsed, ignoring case and
+    /// surrounding whitespace. Other values fail to coerce.
+    Bool,
+    /// Strings are kept, and other values converted to their JSON representation, e.g. `42`
+    /// or `true`. Never fails.
+    String,
+}
+
+impl FieldType {
+    // coerces `value` to this type, returning it unchanged if it cannot be coerced
+    fn coerce(self, value: Value) -> Result<Value, Value> {
+        match (self, value) {
+            (FieldType::Int, Value::Number(n)) if n.is_i64() || n.is_u64() => Ok(Value::Number(n)),
+            (FieldType::Int, Value::Number(n)) => match n.as_f64() {
+                Some(f) if f.fract() == 0.0 && f >= i64::MIN as f64 && f < u64::MAX as f64 => {
+                    Ok(if f < 0.0 {
+                        json!(f as i64)
+                    } else {
+                        json!(f as u64)
+                    })
+                }
+                _ => Err(Value::Number(n)),
+            },
+            (FieldType::Int, Value::String(s)) => {
+                let trimmed = s.trim();
+                match (trimmed.parse::<i64>(), trimmed.parse::<u64>()) {
+                    (Ok(i), _) => Ok(json!(i)),
+                    (_, Ok(u)) => Ok(json!(u)),
+                    _ => Err(Value::String(s)),
+                }
+            }
+            (FieldType::Float, Value::Number(n)) => match n.as_f64() {
+                Some(f) => Ok(json!(f)),
+                None => Err(Value::Number(n)),
+            },
+            (FieldType::Float, Value::String(s)) => match s.trim().parse::<f64>() {
+                Ok(f) if f.is_finite() => Ok(json!(f)),
+                _ => Err(Value::String(s)),
+            },
+            (FieldType::Bool, Value::Bool(b)) => Ok(Value::Bool(b)),
+            (FieldType::Bool, Value::String(s)) => {
+                let trimmed = s.trim();
+                if trimmed.eq_ignore_ascii_case("true") {
+                    Ok(json!(true))
+                } else if trimmed.eq_ignore_ascii_case("false") {
+                    Ok(json!(false))
+                } else {
+                    Err(Value::String(s))
+                }
+            }
+            (FieldType::String, Value::String(s)) => Ok(Value::String(s)),
+            (FieldType::String, value) => Ok(json!(value.to_string())),
+            (_, value) => Err(value),
+        }
+    }
+}
+
+/// Format of the timestamp field set via
+/// [`Builder::with_timestamp_field`](crate::Builder::with_timestamp_field)
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TimestampFormat {
+    /// RFC 3339 string in UTC with nanosecond precision, e.g. `2021-03-04T05:06:07.890123456Z`,
+    /// as Honeycomb expects in its `time` column. This is the default.
+    #[default]
+    Rfc3339,
+    /// RFC 3339 string in UTC with exactly six digits of fractional seconds, i.e. microsecond
+    /// precision, e.g. `2021-03-04T05:06:07.890123Z`.
+    Rfc3339Micros,
+    /// RFC 3339 string in UTC with exactly three digits of fractional seconds, i.e.
+    /// millisecond precision, e.g. `2021-03-04T05:06:07.890Z`. Spans and events less than a
+    /// millisecond apart may then share a timestamp.
+    Rfc3339Millis,
+    /// Integer number of milliseconds since the Unix epoch.
+    EpochMillis,
+    /// Integer number of microseconds since the Unix epoch.
+    EpochMicros,
+}
+
+impl TimestampFormat {
+    pub(crate) fn format(self, timestamp: DateTime<Utc>) -> Value {
+        match self {
+            TimestampFormat::Rfc3339 => {
+                json!(timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true))
+            }
+            TimestampFormat::Rfc3339Micros => {
+                json!(timestamp.to_rfc3339_opts(SecondsFormat::Micros, true))
+            }
+            TimestampFormat::Rfc3339Millis => {
+                json!(timestamp.to_rfc3339_opts(SecondsFormat::Millis, true))
+            }
+            TimestampFormat::EpochMillis => json!(timestamp.timestamp_millis()),
+            TimestampFormat::EpochMicros => json!(timestamp.timestamp_micros()),
+        }
+    }
+}
+
+/// How the status of a span is reported, as configured via
+/// [`Builder::with_status_mapping`](crate::Builder::with_status_mapping).
+///
+/// A span failed if one of the events directly within it was at the `ERROR` level, e.g. from
+/// `tracing::error!`, or if it recorded an [`error`](fields::ERROR) field with any value other
+/// than `false`. Honeycomb, like OpenTelemetry, derives error rates from this status.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StatusMapping {
+    /// Sets [`error`](fields::ERROR) to `true` on spans which failed, unless recorded by the span
+    /// itself, as Honeycomb's error-rate queries expect.
+    ErrorField,
+    /// Additionally sets [`status_code`](fields::STATUS_CODE) on every span, to `2` on spans
+    /// which failed and `0` otherwise, as Honeycomb reports spans received via OpenTelemetry.
+    StatusCode,
+}
+
+impl StatusMapping {
+    // whether a span failed, judged from the fields it recorded rather than the reported ones
+    pub(crate) fn failed(span: &Span<HoneycombVisitor, SpanId, TraceId>) -> bool {
+        span.errored
+            || span
+                .values
+                .values
+                .get(fields::ERROR)
+                .is_some_and(|error| *error != json!(false))
+    }
+
+    pub(crate) fn apply(self, values: &mut HashMap<String, Value>, failed: bool) {
+        if failed {
+            values
+                .entry(fields::ERROR.to_string())
+                .or_insert_with(|| json!(true));
+        }
+        if self == StatusMapping::StatusCode {
+            let status_code = if failed { 2 } else { 0 };
+            values.insert(fields::STATUS_CODE.to_string(), json!(status_code));
+        }
+    }
+}
+
+/// Converts a [`std::time::Duration`] recorded via its `Debug` implementation, e.g.
+/// `tracing::info!(elapsed = ?start.elapsed())`, into fractional milliseconds.
+///
+/// Parses `Duration`'s `Debug` output, such as `1.5s`, `150ms`, `12µs` or `3ns`. Other values
+/// are reported as their `Debug` output, as if no converter were registered. For use with
+/// [`Builder::with_field_converter`](crate::Builder::with_field_converter).
+pub fn duration_as_ms(value: &dyn fmt::Debug) -> Value {
+    let value = format!("{:?}", value);
+    let ms = if let Some(ns) = value.strip_suffix("ns") {
+        ns.parse::<f64>().map(|ns| ns / 1_000_000_f64)
+    } else if let Some(us) = value.strip_suffix("µs") {
+        us.parse::<f64>().map(|us| us / 1000_f64)
+    } else if let Some(ms) = value.strip_suffix("ms") {
+        ms.parse::<f64>()
+    } else if let Some(s) = value.strip_suffix('s') {
+        s.parse::<f64>().map(|s| s * MILLIS_PER_SECOND)
+    } else {
+        return json!(value);
+    };
+
+    match ms {
+        Ok(ms) => json!(ms),
+        Err(_) => json!(value),
+    }
+}
+
+/// Default maximum length, in bytes, of recorded string field values.
+pub const DEFAULT_MAX_FIELD_LEN: usize = 64 * 1024;
+
+/// Default maximum number of sources recorded for error field values.
+pub const DEFAULT_MAX_ERROR_SOURCES: usize = 8;
+
+/// Default maximum depth to which structured field values are flattened.
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+pub const DEFAULT_MAX_FLATTEN_DEPTH: usize = 4;
+
+// appended to truncated string field values
+const TRUNCATION_MARKER: &str = "...";
+
+/// Value recorded in place of redacted fields.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Field names whose values are replaced with [`REDACTED`] before being reported, e.g. for
+/// PII scrubbing.
+///
+/// Names are matched case-insensitively. A name ending in `*`, such as `secret_*`, matches
+/// every field name starting with the rest of it.
+#[derive(Clone, Debug, Default)]
+pub struct RedactedFields {
+    names: Vec<String>,
+    prefixes: Vec<String>,
+}
+
+impl RedactedFields {
+    /// Returns a new `RedactedFields` matching the given field names or `*`-suffixed prefixes.
+    pub fn new<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut redacted = RedactedFields::default();
+        for name in names {
+            let name = name.as_ref();
+            match name.strip_suffix('*') {
+                Some(prefix) => redacted.prefixes.push(prefix.to_string()),
+                None => redacted.names.push(name.to_string()),
+            }
+        }
+        redacted
+    }
+
+    /// Returns `true` if values of the given field should be redacted.
+    pub fn is_redacted(&self, field_name: &str) -> bool {
+        self.names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(field_name))
+            || self.prefixes.iter().any(|prefix| {
+                field_name
+                    .get(..prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+            })
+    }
+}
+
+// Values recorded by a visitor, in the order they were first recorded.
+//
+// Spans and events usually have few fields, so these are kept in a `Vec`, which is searched
+// linearly, rather than a `HashMap`. Past `INDEXED_FIELDS` fields, an index of their positions
+// by name is kept as well, so that recording many fields does not take quadratic time. The
+// `HashMap` handed to the reporter is built once the span or event is reported, sized to also
+// hold the fields set by this crate.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FieldValues {
+    values: Vec<(String, Value)>,
+    index: Option<HashMap<String, usize>>,
+}
+
+// number of fields past which `FieldValues` are indexed by name
+const INDEXED_FIELDS: usize = 16;
+
+impl FieldValues {
+    // replaces the value of an already recorded field, e.g. one recorded again via
+    // `Span::record`, in place
+    pub(crate) fn insert(&mut self, name: String, value: Value) {
+        if let Some(position) = self.position(&name) {
+            self.values[position].1 = value;
+            return;
+        }
+
+        if let Some(index) = &mut self.index {
+            index.insert(name.clone(), self.values.len());
+        }
+        self.values.push((name, value));
+        if self.index.is_none() && self.values.len() > INDEXED_FIELDS {
+            let index = self
+                .values
+                .iter()
+                .enumerate()
+                .map(|(position, (name, _))| (name.clone(), position))
+                .collect();
+            self.index = Some(index);
+        }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Value> {
+        self.position(name).map(|position| &self.values[position].1)
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        match &self.index {
+            Some(index) => index.get(name).copied(),
+            None => self.values.iter().position(|(n, _)| n == name),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    // leaves room for `additional` more fields
+    fn into_map(self, additional: usize) -> HashMap<String, Value> {
+        let mut map = HashMap::with_capacity(self.values.len() + additional);
+        map.extend(self.values);
+        map
+    }
+}
+
+/// Visitor that builds honeycomb-compatible values from tracing fields.
+///
+/// Handed to a [`RichReporter`](crate::RichReporter) as the values of each span and event.
+#[derive(Clone, Debug)]
+pub struct HoneycombVisitor {
+    pub(crate) values: FieldValues,
+    pub(crate) redacted_fields: Option<Arc<RedactedFields>>,
+    pub(crate) max_field_len: usize,
+    pub(crate) escape_control_chars: bool,
+    pub(crate) max_error_sources: usize,
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    pub(crate) max_flatten_depth: usize,
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    pub(crate) nest_values: bool,
+    pub(crate) force_sample_field: Option<&'static str>,
+    // set once `force_sample_field` is recorded as `true`
+    pub(crate) force_sample: bool,
+    pub(crate) max_fields: Option<usize>,
+    pub(crate) field_converters: Option<Arc<HashMap<String, FieldConverter>>>,
+    pub(crate) field_types: Option<Arc<HashMap<String, FieldType>>>,
+    // the sampler of the telemetry which made the visitor, for `is_current_trace_sampled`
+    pub(crate) sampler: Option<Arc<dyn Sampler>>,
+}
+
+impl Default for HoneycombVisitor {
+    fn default() -> Self {
+        HoneycombVisitor {
+            values: FieldValues::default(),
+            redacted_fields: None,
+            max_field_len: DEFAULT_MAX_FIELD_LEN,
+            escape_control_chars: false,
+            max_error_sources: DEFAULT_MAX_ERROR_SOURCES,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            max_flatten_depth: DEFAULT_MAX_FLATTEN_DEPTH,
+            #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+            nest_values: false,
+            force_sample_field: None,
+            force_sample: false,
+            max_fields: None,
+            field_converters: None,
+            field_types: None,
+            sampler: None,
+        }
+    }
+}
+
+impl HoneycombVisitor {
+    /// The recorded fields and their values, in the order they were first recorded.
+    ///
+    /// Values are as they would be reported, i.e. redacted, truncated and converted as
+    /// configured. Fields with one of the [reserved names](fields::RESERVED) are prefixed with
+    /// `tracing.`.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values
+            .values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    fn is_redacted(&self, field: &Field) -> bool {
+        match &self.redacted_fields {
+            Some(redacted) => redacted.is_redacted(field.name()),
+            None => false,
+        }
+    }
+
+    fn record_field(&mut self, field: &Field, value: impl FnOnce() -> Value) {
+        self.record_named(field.name(), value)
+    }
+
+    // records a field by name rather than via `tracing`, e.g. one set by
+    // `register_dist_tracing_root_with_fields`
+    pub(crate) fn record_named(&mut self, name: &str, value: impl FnOnce() -> Value) {
+        let redacted = match &self.redacted_fields {
+            Some(redacted) => redacted.is_redacted(name),
+            None => false,
+        };
+        let key = mk_field_name(name.to_string());
+        if redacted {
+            self.insert(key, json!(REDACTED));
+            return;
+        }
+
+        let field_type = self
+            .field_types
+            .as_ref()
+            .and_then(|field_types| field_types.get(name).copied());
+        let value = value();
+        match field_type {
+            Some(field_type) => match field_type.coerce(value) {
+                Ok(value) => self.insert(key, value),
+                Err(value) => {
+                    self.insert(
+                        format!("{}.coercion_error", key),
+                        json!(format!("cannot coerce {} to {:?}", value, field_type)),
+                    );
+                    self.insert(key, value);
+                }
+            },
+            None => self.insert(key, value),
+        }
+    }
+
+    fn insert(&mut self, name: String, mut value: Value) {
+        if let Value::String(s) = &mut value {
+            if self.escape_control_chars {
+                escape_control_chars(s);
+            }
+            if truncate(s, self.max_field_len) {
+                self.values
+                    .insert(format!("{}.truncated", name), json!(true));
+            }
+        }
+        self.values.insert(name, value);
+    }
+}
+
+// replaces control characters other than tab and newline, e.g. NUL or the ESC beginning an
+// ANSI escape sequence, with their `\u{..}` escape
+fn escape_control_chars(s: &mut String) {
+    let is_escaped = |c: char| c.is_control() && c != '\t' && c != '\n';
+    if !s.contains(is_escaped) {
+        return;
+    }
+
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if is_escaped(c) {
+            escaped.extend(c.escape_unicode());
+        } else {
+            escaped.push(c);
+        }
+    }
+    *s = escaped;
+}
+
+// truncates `s` on a char boundary so that, followed by the truncation marker, it is at most
+// `max_len` bytes long, or just the marker if `max_len` is shorter than that, returning
+// whether it was truncated
+fn truncate(s: &mut String, max_len: usize) -> bool {
+    if s.len() <= max_len {
+        return false;
+    }
+
+    let mut end = max_len.saturating_sub(TRUNCATION_MARKER.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s.truncate(end);
+    s.push_str(TRUNCATION_MARKER);
+    true
+}
 
 impl Visit for HoneycombVisitor {
     fn record_i64(&mut self, field: &Field, value: i64) {
-        self.0
-            .insert(mk_field_name(field.name().to_string()), json!(value));
+        self.record_field(field, || json!(value));
     }
 
     fn record_u64(&mut self, field: &Field, value: u64) {
-        self.0
-            .insert(mk_field_name(field.name().to_string()), json!(value));
+        self.record_field(field, || json!(value));
+    }
+
+    /// Records values which fit neither an `i64` nor a `u64` as their decimal string, as
+    /// `libhoney::Value` has no wider numbers.
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.record_field(field, || match i64::try_from(value) {
+            Ok(value) => json!(value),
+            Err(_) => match u64::try_from(value) {
+                Ok(value) => json!(value),
+                Err(_) => json!(value.to_string()),
+            },
+        });
+    }
+
+    /// Records values which do not fit a `u64` as their decimal string, as `libhoney::Value` has
+    /// no wider numbers.
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.record_field(field, || match u64::try_from(value) {
+            Ok(value) => json!(value),
+            Err(_) => json!(value.to_string()),
+        });
+    }
+
+    /// Records `NaN` and infinities, which JSON numbers cannot hold, as the strings `NaN`,
+    /// `inf` and `-inf`.
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record_field(field, || {
+            if value.is_finite() {
+                json!(value)
+            } else {
+                json!(value.to_string())
+            }
+        });
     }
 
     fn record_bool(&mut self, field: &Field, value: bool) {
-        self.0
-            .insert(mk_field_name(field.name().to_string()), json!(value));
+        if value && self.force_sample_field == Some(field.name()) {
+            self.force_sample = true;
+        }
+        self.record_field(field, || json!(value));
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
-        self.0
-            .insert(mk_field_name(field.name().to_string()), json!(value));
+        self.record_field(field, || json!(value));
     }
 
+    /// Applies the [`FieldConverter`] registered for the field, if any, and otherwise records
+    /// the value's `Debug` output.
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        let s = format!("{:?}", value);
-        self.0
-            .insert(mk_field_name(field.name().to_string()), json!(s));
+        let converter = self
+            .field_converters
+            .as_ref()
+            .and_then(|converters| converters.get(field.name()))
+            .cloned();
+        match converter {
+            Some(converter) => self.record_field(field, || (converter.0)(value)),
+            None => self.record_field(field, || json!(format!("{:?}", value))),
+        }
+    }
+
+    /// Records the error's message under both `<name>` and `<name>.message`, and the messages
+    /// of its `source` chain under `<name>.source.0`, `<name>.source.1`, etc.
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        self.record_field(field, || json!(value.to_string()));
+        if self.is_redacted(field) {
+            return;
+        }
+
+        let name = mk_field_name(field.name().to_string());
+        self.insert(format!("{}.message", name), json!(value.to_string()));
+
+        let sources = std::iter::successors(value.source(), |&err| err.source());
+        for (i, source) in sources.take(self.max_error_sources).enumerate() {
+            self.insert(format!("{}.source.{}", name, i), json!(source.to_string()));
+        }
+    }
+
+    /// Flattens structured values into one field per leaf, keyed by its dotted path from
+    /// `<name>`, e.g. `user.id` and `user.role`, or records them as a single nested JSON value
+    /// if `nest_values` is set.
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    fn record_value(&mut self, field: &Field, value: valuable::Value<'_>) {
+        let name = mk_field_name(field.name().to_string());
+        if self.is_redacted(field) {
+            self.insert(name, json!(REDACTED));
+        } else if self.nest_values {
+            let value = self.nest(value, 0);
+            self.insert(name, value);
+        } else {
+            self.flatten(name, value, 0);
+        }
+    }
+}
+
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+impl HoneycombVisitor {
+    // records scalars as-is, and recurses into structured values up to `max_flatten_depth`,
+    // falling back to their `Debug` representation beyond it
+    fn flatten(&mut self, key: String, value: valuable::Value<'_>, depth: usize) {
+        use valuable::{Valuable, Value as V};
+
+        match value {
+            V::Enumerable(e) if depth < self.max_flatten_depth => {
+                self.insert(format!("{}.variant", key), json!(e.variant().name()));
+                value.visit(&mut Flattener::new(self, key, depth + 1));
+            }
+            V::Structable(_) | V::Mappable(_) | V::Tuplable(_) | V::Listable(_)
+                if depth < self.max_flatten_depth =>
+            {
+                value.visit(&mut Flattener::new(self, key, depth + 1));
+            }
+            value => self.insert(key, leaf_to_json(value)),
+        }
+    }
+
+    fn flatten_field(&mut self, key: String, name: &str, value: valuable::Value<'_>, depth: usize) {
+        let key = format!("{}.{}", key, name);
+        match &self.redacted_fields {
+            Some(redacted) if redacted.is_redacted(name) => self.insert(key, json!(REDACTED)),
+            _ => self.flatten(key, value, depth),
+        }
+    }
+
+    // converts structured values into JSON objects and arrays up to `max_flatten_depth`,
+    // falling back to their `Debug` representation beyond it. Enums are represented as their
+    // variant name if they have no fields, or as an object with their variant name as the only
+    // key, holding their fields, otherwise.
+    fn nest(&self, value: valuable::Value<'_>, depth: usize) -> Value {
+        use valuable::{Valuable, Value as V};
+
+        match value {
+            V::Enumerable(e) if depth < self.max_flatten_depth => {
+                let mut nester = Nester::new(self, depth + 1, Value::Null);
+                value.visit(&mut nester);
+                match nester.value {
+                    Value::Null => json!(e.variant().name()),
+                    fields => {
+                        let mut variant = serde_json::Map::new();
+                        variant.insert(e.variant().name().to_string(), fields);
+                        Value::Object(variant)
+                    }
+                }
+            }
+            V::Structable(_) | V::Mappable(_) if depth < self.max_flatten_depth => {
+                let mut nester = Nester::new(self, depth + 1, json!({}));
+                value.visit(&mut nester);
+                nester.value
+            }
+            V::Tuplable(_) | V::Listable(_) if depth < self.max_flatten_depth => {
+                let mut nester = Nester::new(self, depth + 1, json!([]));
+                value.visit(&mut nester);
+                nester.value
+            }
+            value => leaf_to_json(value),
+        }
+    }
+
+    fn nest_field(&self, name: &str, value: valuable::Value<'_>, depth: usize) -> Value {
+        match &self.redacted_fields {
+            Some(redacted) if redacted.is_redacted(name) => json!(REDACTED),
+            _ => self.nest(value, depth),
+        }
+    }
+}
+
+// scalars as-is, anything else as its `Debug` representation
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+fn leaf_to_json(value: valuable::Value<'_>) -> Value {
+    use valuable::Value as V;
+
+    match value {
+        V::Bool(v) => json!(v),
+        V::Char(v) => json!(v.to_string()),
+        V::F32(v) => json!(v),
+        V::F64(v) => json!(v),
+        V::I8(v) => json!(v),
+        V::I16(v) => json!(v),
+        V::I32(v) => json!(v),
+        V::I64(v) => json!(v),
+        V::Isize(v) => json!(v),
+        V::U8(v) => json!(v),
+        V::U16(v) => json!(v),
+        V::U32(v) => json!(v),
+        V::U64(v) => json!(v),
+        V::Usize(v) => json!(v),
+        // may not fit in a json number
+        V::I128(v) => json!(v.to_string()),
+        V::U128(v) => json!(v.to_string()),
+        V::String(v) => json!(v),
+        V::Unit => Value::Null,
+        value => json!(format!("{:?}", value)),
+    }
+}
+
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+fn entry_name(key: valuable::Value<'_>) -> String {
+    match key {
+        valuable::Value::String(name) => name.to_string(),
+        key => format!("{:?}", key),
+    }
+}
+
+// visits the children of a structured value, flattening each under `key`
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+struct Flattener<'a> {
+    visitor: &'a mut HoneycombVisitor,
+    key: String,
+    depth: usize,
+    index: usize,
+}
+
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+impl<'a> Flattener<'a> {
+    fn new(visitor: &'a mut HoneycombVisitor, key: String, depth: usize) -> Self {
+        Flattener {
+            visitor,
+            key,
+            depth,
+            index: 0,
+        }
+    }
+}
+
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+impl valuable::Visit for Flattener<'_> {
+    // called for list elements
+    fn visit_value(&mut self, value: valuable::Value<'_>) {
+        let key = format!("{}.{}", self.key, self.index);
+        self.index += 1;
+        self.visitor.flatten(key, value, self.depth);
+    }
+
+    fn visit_named_fields(&mut self, named_values: &valuable::NamedValues<'_>) {
+        for (field, value) in named_values.iter() {
+            self.visitor
+                .flatten_field(self.key.clone(), field.name(), *value, self.depth);
+        }
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[valuable::Value<'_>]) {
+        for (i, value) in values.iter().enumerate() {
+            let key = format!("{}.{}", self.key, i);
+            self.visitor.flatten(key, *value, self.depth);
+        }
+    }
+
+    fn visit_entry(&mut self, key: valuable::Value<'_>, value: valuable::Value<'_>) {
+        let name = entry_name(key);
+        self.visitor
+            .flatten_field(self.key.clone(), &name, value, self.depth);
+    }
+}
+
+// visits the children of a structured value, collecting them into `value`, which starts out
+// as an empty object or array, or as null for enums
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+struct Nester<'a> {
+    visitor: &'a HoneycombVisitor,
+    depth: usize,
+    value: Value,
+}
+
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+impl<'a> Nester<'a> {
+    fn new(visitor: &'a HoneycombVisitor, depth: usize, value: Value) -> Self {
+        Nester {
+            visitor,
+            depth,
+            value,
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        match &mut self.value {
+            Value::Array(values) => values.push(value),
+            // tuple structs and variants, and enum variants with unnamed fields
+            other => *other = json!([value]),
+        }
+    }
+
+    fn insert(&mut self, name: String, value: Value) {
+        match &mut self.value {
+            Value::Object(values) => {
+                values.insert(name, value);
+            }
+            other => {
+                let mut values = serde_json::Map::new();
+                values.insert(name, value);
+                *other = Value::Object(values);
+            }
+        }
+    }
+}
+
+#[cfg(all(tracing_unstable, feature = "use_valuable"))]
+impl valuable::Visit for Nester<'_> {
+    // called for list elements
+    fn visit_value(&mut self, value: valuable::Value<'_>) {
+        let value = self.visitor.nest(value, self.depth);
+        self.push(value);
+    }
+
+    fn visit_named_fields(&mut self, named_values: &valuable::NamedValues<'_>) {
+        for (field, value) in named_values.iter() {
+            let value = self.visitor.nest_field(field.name(), *value, self.depth);
+            self.insert(field.name().to_string(), value);
+        }
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[valuable::Value<'_>]) {
+        for value in values {
+            let value = self.visitor.nest(*value, self.depth);
+            self.push(value);
+        }
+    }
+
+    fn visit_entry(&mut self, key: valuable::Value<'_>, value: valuable::Value<'_>) {
+        let name = entry_name(key);
+        let value = self.visitor.nest_field(&name, value, self.depth);
+        self.insert(name, value);
     }
 }
 
 fn mk_field_name(s: String) -> String {
     // TODO: do another pass, optimize for efficiency (lazy static set?)
-    if RESERVED_WORDS.contains(&&s[..]) {
+    if fields::RESERVED.contains(&&s[..]) {
         format!("tracing.{}", s)
     } else {
         s
     }
 }
 
+fn map_keys(
+    values: HashMap<String, Value>,
+    key_mapping: Option<&KeyMapping>,
+) -> HashMap<String, Value> {
+    match key_mapping {
+        Some(key_mapping) => {
+            let mut mapped = HashMap::with_capacity(values.capacity());
+            mapped.extend(values.into_iter().map(|(key, value)| {
+                // reporters route by the dataset field, so it must keep its name
+                if key == crate::DATASET_FIELD {
+                    (key, value)
+                } else {
+                    ((key_mapping.0)(&key), value)
+                }
+            }));
+            mapped
+        }
+        None => values,
+    }
+}
+
 pub(crate) fn event_to_values(
     event: Event<HoneycombVisitor, SpanId, TraceId>,
+    key_mapping: Option<&KeyMapping>,
 ) -> (HashMap<String, libhoney::Value>, DateTime<Utc>) {
-    let mut values = event.values.0;
+    let service_name = service_name(&event.values.values, event.service_name);
+    let mut values = event.values.values.into_map(fields::RESERVED.len());
+    let fields_dropped = cap_fields(&mut values, event.values.max_fields);
+    let mut values = map_keys(values, key_mapping);
 
     values.insert(
         // magic honeycomb string (trace.trace_id)
-        "trace.trace_id".to_string(),
+        fields::TRACE_ID.to_string(),
         // using explicit trace id passed in from ctx (req'd for lazy eval)
         json!(event.trace_id.to_string()),
     );
 
-    values.insert(
-        // magic honeycomb string (trace.parent_id)
-        "trace.parent_id".to_string(),
-        event
-            .parent_id
-            .map(|pid| json!(pid.to_string()))
-            .unwrap_or(json!(null)),
-    );
+    // magic honeycomb string (trace.parent_id), omitted for the root span of a trace
+    if let Some(parent_id) = event.parent_id {
+        values.insert(fields::PARENT_ID.to_string(), json!(parent_id.to_string()));
+    }
 
     // magic honeycomb string (service_name)
-    values.insert("service_name".to_string(), json!(event.service_name));
+    values.insert(fields::SERVICE_NAME.to_string(), service_name);
 
     values.insert(
-        "level".to_string(),
+        fields::LEVEL.to_string(),
         json!(format!("{}", event.meta.level())),
     );
 
     // not honeycomb-special but tracing-provided
-    values.insert("name".to_string(), json!(event.meta.name()));
-    values.insert("target".to_string(), json!(event.meta.target()));
+    values.insert(fields::NAME.to_string(), json!(event.meta.name()));
+    values.insert(fields::TARGET.to_string(), json!(event.meta.target()));
+
+    // magic honeycomb string (meta.annotation_type), displays the event on its parent span
+    values.insert(
+        fields::ANNOTATION_TYPE.to_string(),
+        json!(fields::SPAN_EVENT),
+    );
+
+    if fields_dropped > 0 {
+        values.insert(fields::FIELDS_DROPPED.to_string(), json!(fields_dropped));
+    }
 
     (values, event.initialized_at.into())
 }
 
 pub(crate) fn span_to_values(
     span: Span<HoneycombVisitor, SpanId, TraceId>,
+    key_mapping: Option<&KeyMapping>,
 ) -> (HashMap<String, libhoney::Value>, DateTime<Utc>) {
-    let mut values = span.values.0;
+    let service_name = service_name(&span.values.values, span.service_name);
+    let mut values = span.values.values.into_map(fields::RESERVED.len());
+    let span_kind = take_span_kind(&mut values);
+    // recorded fields take precedence over baggage, which is then capped and mapped alike, and
+    // the fields set below take precedence over both
+    for (key, value) in span.baggage.iter() {
+        values
+            .entry(mk_field_name(key.clone()))
+            .or_insert_with(|| json!(value));
+    }
+    let fields_dropped = cap_fields(&mut values, span.values.max_fields);
+    let mut values = map_keys(values, key_mapping);
 
     values.insert(
         // magic honeycomb string (trace.span_id)
-        "trace.span_id".to_string(),
+        fields::SPAN_ID.to_string(),
         json!(span.id.to_string()),
     );
 
     values.insert(
         // magic honeycomb string (trace.trace_id)
-        "trace.trace_id".to_string(),
+        fields::TRACE_ID.to_string(),
         // using explicit trace id passed in from ctx (req'd for lazy eval)
         json!(span.trace_id.to_string()),
     );
 
-    values.insert(
-        // magic honeycomb string (trace.parent_id)
-        "trace.parent_id".to_string(),
-        span.parent_id
-            .map(|pid| json!(pid.to_string()))
-            .unwrap_or(json!(null)),
-    );
+    // magic honeycomb string (trace.parent_id), omitted for the root span of a trace
+    if let Some(parent_id) = span.parent_id {
+        values.insert(fields::PARENT_ID.to_string(), json!(parent_id.to_string()));
+    }
 
     // magic honeycomb string (service_name)
-    values.insert("service_name".to_string(), json!(span.service_name));
+    values.insert(fields::SERVICE_NAME.to_string(), service_name);
+
+    // magic honeycomb string (meta.span_type), only set on the local root of a trace
+    if span.local_root {
+        values.insert(fields::SPAN_TYPE.to_string(), json!(fields::ROOT_SPAN));
+    }
 
-    values.insert("level".to_string(), json!(format!("{}", span.meta.level())));
+    // magic honeycomb string (span.kind), as set by opentelemetry instrumentation
+    values.insert(fields::SPAN_KIND.to_string(), json!(span_kind));
+
+    values.insert(
+        fields::LEVEL.to_string(),
+        json!(format!("{}", span.meta.level())),
+    );
 
     // not honeycomb-special but tracing-provided
-    values.insert("name".to_string(), json!(span.meta.name()));
-    values.insert("target".to_string(), json!(span.meta.target()));
+    values.insert(fields::NAME.to_string(), json!(span.meta.name()));
+    values.insert(fields::TARGET.to_string(), json!(span.meta.target()));
 
-    match span.completed_at.duration_since(span.initialized_at) {
-        Ok(d) => {
-            // honeycomb-special (I think, todo: get full list of known values)
-            values.insert(
-                "duration_ms".to_string(),
-                json!(d.as_secs_f64() * MILLIS_PER_SECOND),
-            );
+    match duration_ms(span.initialized_at, span.completed_at) {
+        Ok(duration_ms) => {
+            // magic honeycomb string (duration_ms)
+            values.insert(fields::DURATION_MS.to_string(), json!(duration_ms));
+
+            if let Some(busy) = span.busy {
+                let busy_ms = busy.as_secs_f64() * MILLIS_PER_SECOND;
+                values.insert(fields::BUSY_MS.to_string(), json!(busy_ms));
+                values.insert(
+                    fields::IDLE_MS.to_string(),
+                    json!((duration_ms - busy_ms).max(0.0)),
+                );
+            }
         }
         Err(e) => {
             eprintln!("error comparing system times in tracing-honeycomb, indicates possible clock skew: {:?}", e);
         }
     }
 
+    if fields_dropped > 0 {
+        values.insert(fields::FIELDS_DROPPED.to_string(), json!(fields_dropped));
+    }
+
     (values, span.initialized_at.into())
 }
+
+const SPAN_KINDS: [&str; 5] = ["server", "client", "producer", "consumer", "internal"];
+
+// removes the `otel.kind` field if it holds a known span kind, returning that kind, or
+// `internal` if it is absent or unknown
+fn take_span_kind(values: &mut HashMap<String, Value>) -> &'static str {
+    let kind = values
+        .get(fields::OTEL_KIND)
+        .and_then(Value::as_str)
+        .and_then(|kind| {
+            SPAN_KINDS
+                .iter()
+                .copied()
+                .find(|known| known.eq_ignore_ascii_case(kind))
+        });
+    match kind {
+        Some(kind) => {
+            values.remove(fields::OTEL_KIND);
+            kind
+        }
+        None => "internal",
+    }
+}
+
+// drops recorded fields beyond the first `max_fields`, sorted by name, returning the number
+// dropped. `service.name` and `honeycomb.dataset` direct how the data is reported, so are kept.
+fn cap_fields(values: &mut HashMap<String, Value>, max_fields: Option<usize>) -> usize {
+    let max_fields = match max_fields {
+        Some(max_fields) => max_fields,
+        None => return 0,
+    };
+
+    let mut names: Vec<&String> = values
+        .keys()
+        .filter(|name| *name != SERVICE_NAME_FIELD && *name != crate::DATASET_FIELD)
+        .collect();
+    if names.len() <= max_fields {
+        return 0;
+    }
+
+    names.sort();
+    let dropped: Vec<String> = names[max_fields..]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    for name in &dropped {
+        values.remove(name);
+    }
+    dropped.len()
+}
+
+// opentelemetry `code.*` fields locating the callsite of a span or event, as far as known
+pub(crate) fn insert_source_location(
+    values: &mut HashMap<String, Value>,
+    meta: &tracing::Metadata<'_>,
+) {
+    if let Some(file) = meta.file() {
+        values.insert(fields::CODE_FILEPATH.to_string(), json!(file));
+    }
+    if let Some(line) = meta.line() {
+        values.insert(fields::CODE_LINENO.to_string(), json!(line));
+    }
+    if let Some(module_path) = meta.module_path() {
+        values.insert(fields::CODE_NAMESPACE.to_string(), json!(module_path));
+    }
+}
+
+// Honeycomb span link annotations, one for each span the span follows from
+pub(crate) fn links_to_values(
+    span: &Span<HoneycombVisitor, SpanId, TraceId>,
+) -> Vec<(HashMap<String, libhoney::Value>, DateTime<Utc>)> {
+    if span.follows_from.is_empty() {
+        return Vec::new();
+    }
+
+    let service_name = service_name(&span.values.values, span.service_name);
+    span.follows_from
+        .iter()
+        .map(|link| {
+            let mut values = HashMap::new();
+            values.insert(
+                fields::TRACE_ID.to_string(),
+                json!(span.trace_id.to_string()),
+            );
+            // magic honeycomb string (trace.parent_id), attaching the link to the linking span
+            values.insert(fields::PARENT_ID.to_string(), json!(span.id.to_string()));
+            values.insert(
+                fields::LINK_TRACE_ID.to_string(),
+                json!(link.trace_id.to_string()),
+            );
+            values.insert(
+                fields::LINK_SPAN_ID.to_string(),
+                json!(link.span_id.to_string()),
+            );
+            values.insert(fields::ANNOTATION_TYPE.to_string(), json!(fields::LINK));
+            values.insert(fields::SERVICE_NAME.to_string(), service_name.clone());
+            (values, span.initialized_at.into())
+        })
+        .collect()
+}
+
+// whether the data is a span link annotation, rather than a span or event
+#[cfg(feature = "use_otlp")]
+pub(crate) fn is_link(data: &HashMap<String, libhoney::Value>) -> bool {
+    match data.get(fields::ANNOTATION_TYPE) {
+        Some(Value::String(annotation_type)) => annotation_type == fields::LINK,
+        _ => false,
+    }
+}
+
+// the recorded `service.name` field if present, taking precedence over the layer's service name
+fn service_name(values: &FieldValues, default: &'static str) -> Value {
+    match values.get(SERVICE_NAME_FIELD) {
+        Some(service_name @ Value::String(_)) => service_name.clone(),
+        _ => json!(default),
+    }
+}
+
+// elapsed time between a span's open and close timestamps, as fractional milliseconds
+fn duration_ms(
+    initialized_at: SystemTime,
+    completed_at: SystemTime,
+) -> Result<f64, std::time::SystemTimeError> {
+    let elapsed = completed_at.duration_since(initialized_at)?;
+    Ok(elapsed.as_secs_f64() * MILLIS_PER_SECOND)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn namespaces_reserved_field_names() {
+        for name in &fields::RESERVED {
+            assert_eq!(mk_field_name(name.to_string()), format!("tracing.{}", name));
+        }
+        assert_eq!(mk_field_name("user".to_string()), "user");
+    }
+
+    #[test]
+    fn computes_duration_in_millis() {
+        use std::time::Duration;
+
+        let initialized_at = SystemTime::now();
+        let completed_at = initialized_at + Duration::from_millis(1250);
+        assert_eq!(duration_ms(initialized_at, completed_at).unwrap(), 1250.0);
+        assert_eq!(duration_ms(initialized_at, initialized_at).unwrap(), 0.0);
+        assert!(duration_ms(completed_at, initialized_at).is_err());
+    }
+
+    #[test]
+    fn redacts_names_case_insensitively() {
+        let redacted = RedactedFields::new(["email", "Authorization"]);
+        assert!(redacted.is_redacted("email"));
+        assert!(redacted.is_redacted("EMAIL"));
+        assert!(redacted.is_redacted("authorization"));
+        assert!(!redacted.is_redacted("email_verified"));
+        assert!(!redacted.is_redacted("user"));
+    }
+
+    #[derive(Debug)]
+    struct TestError(&'static str, Option<Box<TestError>>);
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl Error for TestError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            self.1
+                .as_ref()
+                .map(|err| err.as_ref() as &(dyn Error + 'static))
+        }
+    }
+
+    fn error_field() -> Field {
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            let span = tracing::info_span!("test", error = tracing::field::Empty);
+            span.field("error").unwrap()
+        })
+    }
+
+    #[test]
+    fn records_error_sources() {
+        let err = TestError(
+            "top",
+            Some(Box::new(TestError(
+                "middle",
+                Some(Box::new(TestError("root", None))),
+            ))),
+        );
+
+        let mut visitor = HoneycombVisitor::default();
+        visitor.record_error(&error_field(), &err);
+        assert_eq!(visitor.values.get("error"), Some(&json!("top")));
+        assert_eq!(visitor.values.get("error.message"), Some(&json!("top")));
+        assert_eq!(visitor.values.get("error.source.0"), Some(&json!("middle")));
+        assert_eq!(visitor.values.get("error.source.1"), Some(&json!("root")));
+        assert_eq!(visitor.values.len(), 4);
+
+        let mut visitor = HoneycombVisitor {
+            max_error_sources: 1,
+            ..Default::default()
+        };
+        visitor.record_error(&error_field(), &err);
+        assert_eq!(visitor.values.get("error.source.0"), Some(&json!("middle")));
+        assert_eq!(visitor.values.get("error.source.1"), None);
+
+        let mut visitor = HoneycombVisitor {
+            redacted_fields: Some(Arc::new(RedactedFields::new(["error"]))),
+            ..Default::default()
+        };
+        visitor.record_error(&error_field(), &err);
+        assert_eq!(visitor.values.get("error"), Some(&json!(REDACTED)));
+        assert_eq!(visitor.values.len(), 1);
+    }
+
+    #[test]
+    fn coerces_values_to_field_types() {
+        let coerce = |field_type: FieldType, value: Value| field_type.coerce(value);
+
+        assert_eq!(coerce(FieldType::Int, json!(-3)), Ok(json!(-3)));
+        assert_eq!(coerce(FieldType::Int, json!(u64::MAX)), Ok(json!(u64::MAX)));
+        assert_eq!(coerce(FieldType::Int, json!(4.0)), Ok(json!(4)));
+        assert_eq!(coerce(FieldType::Int, json!(" 42\n")), Ok(json!(42)));
+        assert_eq!(coerce(FieldType::Int, json!(4.5)), Err(json!(4.5)));
+        assert_eq!(coerce(FieldType::Int, json!("4.5")), Err(json!("4.5")));
+        assert_eq!(coerce(FieldType::Int, json!(true)), Err(json!(true)));
+
+        assert_eq!(coerce(FieldType::Float, json!(3)), Ok(json!(3.0)));
+        assert_eq!(coerce(FieldType::Float, json!("1.5")), Ok(json!(1.5)));
+        assert_eq!(coerce(FieldType::Float, json!("NaN")), Err(json!("NaN")));
+
+        assert_eq!(coerce(FieldType::Bool, json!(false)), Ok(json!(false)));
+        assert_eq!(coerce(FieldType::Bool, json!(" TRUE")), Ok(json!(true)));
+        assert_eq!(coerce(FieldType::Bool, json!(1)), Err(json!(1)));
+        assert_eq!(coerce(FieldType::Bool, json!("yes")), Err(json!("yes")));
+
+        assert_eq!(coerce(FieldType::String, json!("a")), Ok(json!("a")));
+        assert_eq!(coerce(FieldType::String, json!(42)), Ok(json!("42")));
+        assert_eq!(coerce(FieldType::String, json!(true)), Ok(json!("true")));
+    }
+
+    #[test]
+    fn escapes_control_chars_if_enabled() {
+        let field = error_field();
+        let value = "a\u{0000}b\x1b[31mred\x1b[0m\ttab\nline";
+
+        let mut visitor = HoneycombVisitor::default();
+        visitor.record_str(&field, value);
+        assert_eq!(visitor.values.get("error"), Some(&json!(value)));
+
+        let mut visitor = HoneycombVisitor {
+            escape_control_chars: true,
+            ..Default::default()
+        };
+        visitor.record_str(&field, value);
+        assert_eq!(
+            visitor.values.get("error"),
+            Some(&json!("a\\u{0}b\\u{1b}[31mred\\u{1b}[0m\ttab\nline"))
+        );
+    }
+
+    #[test]
+    fn records_numbers_without_loss() {
+        let field = error_field();
+        let record = |record: &dyn Fn(&mut HoneycombVisitor)| {
+            let mut visitor = HoneycombVisitor::default();
+            record(&mut visitor);
+            visitor.values.get("error").cloned().unwrap()
+        };
+
+        let value = record(&|v| v.record_u64(&field, u64::MAX));
+        assert_eq!(value.as_u64(), Some(u64::MAX));
+        assert_eq!(value.to_string(), "18446744073709551615");
+        assert_eq!(
+            record(&|v| v.record_i64(&field, i64::MIN)).as_i64(),
+            Some(i64::MIN)
+        );
+        assert_eq!(
+            record(&|v| v.record_i64(&field, i64::MAX)).as_i64(),
+            Some(i64::MAX)
+        );
+
+        assert_eq!(record(&|v| v.record_i128(&field, -1)).as_i64(), Some(-1));
+        let value = record(&|v| v.record_i128(&field, i128::from(u64::MAX)));
+        assert_eq!(value.as_u64(), Some(u64::MAX));
+        assert_eq!(
+            record(&|v| v.record_i128(&field, i128::MIN)),
+            json!(i128::MIN.to_string())
+        );
+        let value = record(&|v| v.record_u128(&field, u128::from(u64::MAX)));
+        assert_eq!(value.as_u64(), Some(u64::MAX));
+        assert_eq!(
+            record(&|v| v.record_u128(&field, u128::MAX)),
+            json!(u128::MAX.to_string())
+        );
+
+        assert_eq!(record(&|v| v.record_f64(&field, 1.5)).as_f64(), Some(1.5));
+        assert_eq!(
+            record(&|v| v.record_f64(&field, f64::MAX)).as_f64(),
+            Some(f64::MAX)
+        );
+        assert_eq!(record(&|v| v.record_f64(&field, f64::NAN)), json!("NaN"));
+        assert_eq!(
+            record(&|v| v.record_f64(&field, f64::NEG_INFINITY)),
+            json!("-inf")
+        );
+
+        assert_eq!(record(&|v| v.record_bool(&field, true)), json!(true));
+        assert_eq!(record(&|v| v.record_str(&field, "42")), json!("42"));
+    }
+
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    #[test]
+    fn flattens_structured_values() {
+        use valuable::Valuable;
+
+        let mut user = HashMap::new();
+        user.insert("roles", vec![1u32, 2]);
+        user.insert("password", vec![3]);
+
+        let mut visitor = HoneycombVisitor {
+            redacted_fields: Some(Arc::new(RedactedFields::new(&["password"]))),
+            ..Default::default()
+        };
+        visitor.flatten("user".to_string(), user.as_value(), 0);
+        assert_eq!(visitor.values.get("user.roles.0"), Some(&json!(1)));
+        assert_eq!(visitor.values.get("user.roles.1"), Some(&json!(2)));
+        assert_eq!(visitor.values.get("user.password"), Some(&json!(REDACTED)));
+        assert_eq!(visitor.values.len(), 3);
+
+        let mut visitor = HoneycombVisitor {
+            max_flatten_depth: 1,
+            ..Default::default()
+        };
+        visitor.flatten("user".to_string(), user.as_value(), 0);
+        assert_eq!(visitor.values.get("user.roles"), Some(&json!("[1, 2]")));
+    }
+
+    #[cfg(all(tracing_unstable, feature = "use_valuable"))]
+    #[test]
+    fn nests_structured_values() {
+        use valuable::Valuable;
+
+        #[derive(Valuable)]
+        enum Role {
+            Admin,
+            Guest(u32),
+            Member { team: String },
+        }
+
+        #[derive(Valuable)]
+        struct User {
+            id: u64,
+            roles: Vec<Role>,
+            password: String,
+            manager: Option<Box<User>>,
+        }
+
+        let user = User {
+            id: 1,
+            roles: vec![
+                Role::Admin,
+                Role::Guest(7),
+                Role::Member {
+                    team: "core".to_string(),
+                },
+            ],
+            password: "hunter2".to_string(),
+            manager: None,
+        };
+
+        let visitor = HoneycombVisitor {
+            redacted_fields: Some(Arc::new(RedactedFields::new(&["password"]))),
+            nest_values: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            visitor.nest(user.as_value(), 0),
+            json!({
+                "id": 1,
+                "roles": ["Admin", {"Guest": [7]}, {"Member": {"team": "core"}}],
+                "password": REDACTED,
+                "manager": null,
+            })
+        );
+
+        let visitor = HoneycombVisitor {
+            max_flatten_depth: 1,
+            nest_values: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            visitor.nest(user.as_value(), 0)["roles"],
+            json!(format!("{:?}", user.roles.as_value()))
+        );
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        use chrono::TimeZone;
+
+        let timestamp = Utc.timestamp_opt(1_614_834_367, 890_123_456).unwrap();
+        assert_eq!(
+            TimestampFormat::Rfc3339.format(timestamp),
+            json!("2021-03-04T05:06:07.890123456Z")
+        );
+        assert_eq!(
+            TimestampFormat::Rfc3339Micros.format(timestamp),
+            json!("2021-03-04T05:06:07.890123Z")
+        );
+        assert_eq!(
+            TimestampFormat::Rfc3339Millis.format(timestamp),
+            json!("2021-03-04T05:06:07.890Z")
+        );
+        assert_eq!(
+            TimestampFormat::EpochMillis.format(timestamp),
+            json!(1_614_834_367_890_i64)
+        );
+        assert_eq!(
+            TimestampFormat::EpochMicros.format(timestamp),
+            json!(1_614_834_367_890_123_i64)
+        );
+    }
+
+    #[test]
+    fn rfc3339_timestamps_parse_back_at_their_precision() {
+        use chrono::TimeZone;
+
+        // a whole number of seconds must still be formatted with every fractional digit
+        for nanos in [0, 5_000, 890_123_456] {
+            let timestamp = Utc.timestamp_opt(1_614_834_367, nanos).unwrap();
+            for (format, digits, unit) in [
+                (TimestampFormat::Rfc3339, 9, 1),
+                (TimestampFormat::Rfc3339Micros, 6, 1_000),
+                (TimestampFormat::Rfc3339Millis, 3, 1_000_000),
+            ] {
+                let formatted = format.format(timestamp);
+                let formatted = formatted.as_str().unwrap();
+                let fraction = &formatted[formatted.find('.').unwrap() + 1..formatted.len() - 1];
+                assert_eq!(fraction.len(), digits, "{}", formatted);
+
+                let parsed = DateTime::parse_from_rfc3339(formatted).unwrap();
+                assert_eq!(parsed.timestamp(), timestamp.timestamp());
+                assert_eq!(parsed.timestamp_subsec_nanos(), nanos / unit * unit);
+            }
+        }
+    }
+
+    #[test]
+    fn converts_durations_to_millis() {
+        use std::time::Duration;
+
+        assert_eq!(duration_as_ms(&Duration::from_millis(1500)), json!(1500.0));
+        assert_eq!(duration_as_ms(&Duration::from_millis(150)), json!(150.0));
+        assert_eq!(duration_as_ms(&Duration::from_micros(250)), json!(0.25));
+        assert_eq!(duration_as_ms(&Duration::from_nanos(3_000_000)), json!(3.0));
+        assert_eq!(duration_as_ms(&"1.5s"), json!("\"1.5s\""));
+        assert_eq!(duration_as_ms(&"fast"), json!("\"fast\""));
+    }
+
+    #[test]
+    fn truncates_on_char_boundaries() {
+        // the marker counts towards the maximum length
+        let mut s = "ab\u{e9}cdef".to_string();
+        assert!(truncate(&mut s, 6));
+        assert_eq!(s, "ab...");
+
+        let mut s = "ab\u{e9}cdef".to_string();
+        assert!(truncate(&mut s, 7));
+        assert_eq!(s, "ab\u{e9}...");
+
+        let mut s = "abcdef".to_string();
+        assert!(truncate(&mut s, 2));
+        assert_eq!(s, "...");
+
+        let mut s = "abc".to_string();
+        assert!(!truncate(&mut s, 3));
+        assert_eq!(s, "abc");
+    }
+
+    #[test]
+    fn field_values_replace_recorded_values() {
+        let mut values = FieldValues::default();
+        values.insert("a".to_string(), json!(1));
+        values.insert("b".to_string(), json!(2));
+        values.insert("a".to_string(), json!(3));
+        assert_eq!(values.get("a"), Some(&json!(3)));
+        assert_eq!(values.len(), 2);
+
+        let map = values.into_map(fields::RESERVED.len());
+        assert_eq!(map.len(), 2);
+        assert!(map.capacity() >= 2 + fields::RESERVED.len());
+    }
+
+    #[test]
+    fn field_values_index_many_fields() {
+        let mut values = FieldValues::default();
+        for i in 0..=INDEXED_FIELDS {
+            values.insert(format!("field.{}", i), json!(i));
+        }
+        assert!(values.index.is_some());
+
+        values.insert("field.0".to_string(), json!("replaced"));
+        values.insert("last".to_string(), json!("added"));
+        assert_eq!(values.get("field.0"), Some(&json!("replaced")));
+        assert_eq!(values.get("field.1"), Some(&json!(1)));
+        assert_eq!(values.get("last"), Some(&json!("added")));
+        assert_eq!(values.get("missing"), None);
+        assert_eq!(values.len(), INDEXED_FIELDS + 2);
+    }
+
+    #[test]
+    fn maps_keys() {
+        let mut values = HashMap::new();
+        values.insert("user".to_string(), json!("alice"));
+        values.insert("tracing.level".to_string(), json!("high"));
+        values.insert(crate::DATASET_FIELD.to_string(), json!("tenant-a"));
+        let key_mapping = KeyMapping::new(|key| format!("app.{}", key));
+
+        let mapped = map_keys(values.clone(), Some(&key_mapping));
+        assert_eq!(mapped.get("app.user"), Some(&json!("alice")));
+        assert_eq!(mapped.get("app.tracing.level"), Some(&json!("high")));
+        assert_eq!(mapped.get(crate::DATASET_FIELD), Some(&json!("tenant-a")));
+        assert_eq!(mapped.len(), 3);
+
+        assert_eq!(map_keys(values.clone(), None), values);
+    }
+
+    #[test]
+    fn redacts_prefixes() {
+        let redacted = RedactedFields::new(vec!["secret_*".to_string()]);
+        assert!(redacted.is_redacted("secret_key"));
+        assert!(redacted.is_redacted("SECRET_"));
+        assert!(!redacted.is_redacted("secret"));
+        assert!(!redacted.is_redacted("my_secret_key"));
+        assert!(!redacted.is_redacted("sécret_key"));
+    }
+}